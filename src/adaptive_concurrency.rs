@@ -0,0 +1,228 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::info;
+use tokio::sync::Notify;
+
+/// the concurrency limit an adaptive run starts at
+const INITIAL_LIMIT: usize = 2;
+
+/// a response slower than this multiple of the fastest smoothed latency counts as congestion
+const LATENCY_TOLERANCE: u32 = 2;
+
+/// Adjusts the number of requests in flight between 1 and the configured concurrency based on
+/// response times and server errors. The limit grows by one after a full window of fast
+/// responses and shrinks by a quarter on a 5xx, a connection error or a slow response
+pub(crate) struct AdaptiveConcurrency {
+    max_limit: usize,
+    state: Mutex<State>,
+
+    /// wakes the requests waiting for a free slot
+    notify: Notify,
+}
+
+struct State {
+    /// the current number of requests allowed in flight
+    limit: usize,
+    inflight: usize,
+
+    /// exponentially weighted moving average of the response times, smooths out file size differences
+    smoothed_latency: Option<Duration>,
+
+    /// the fastest smoothed latency seen, the baseline for an idle server
+    baseline_latency: Option<Duration>,
+
+    /// fast responses since the limit last changed
+    window: usize,
+
+    /// responses to wait for after decreasing, the requests already in flight were sent at the old limit
+    cooldown: usize,
+}
+
+/// A slot under the adaptive concurrency limit, dropping it without calling succeeded counts as a failure
+pub(crate) struct ConcurrencyPermit<'a> {
+    controller: &'a AdaptiveConcurrency,
+    latency: Option<Duration>,
+}
+
+impl ConcurrencyPermit<'_> {
+    /// the request completed without a server error after this long
+    pub(crate) fn succeeded(&mut self, latency: Duration) {
+        self.latency = Some(latency);
+    }
+}
+
+impl Drop for ConcurrencyPermit<'_> {
+    fn drop(&mut self) {
+        self.controller.release(self.latency);
+    }
+}
+
+impl AdaptiveConcurrency {
+    pub(crate) fn new(max_limit: usize) -> Self {
+        let max_limit = max_limit.max(1);
+
+        AdaptiveConcurrency {
+            max_limit,
+            state: Mutex::new(State {
+                limit: INITIAL_LIMIT.min(max_limit),
+                inflight: 0,
+                smoothed_latency: None,
+                baseline_latency: None,
+                window: 0,
+                cooldown: 0,
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    /// wait until there is room under the current limit
+    pub(crate) async fn acquire(&self) -> ConcurrencyPermit<'_> {
+        loop {
+            // register before checking so a release between the check and the await isn't missed
+            let notified = self.notify.notified();
+
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.inflight < state.limit {
+                    state.inflight += 1;
+                    return ConcurrencyPermit {
+                        controller: self,
+                        latency: None,
+                    };
+                }
+            }
+
+            notified.await;
+        }
+    }
+
+    /// adjust the limit from the outcome of a request, None if it failed with a server error
+    fn release(&self, latency: Option<Duration>) {
+        let mut state = self.state.lock().unwrap();
+        state.inflight -= 1;
+
+        let congested = match latency {
+            Some(latency) => {
+                let smoothed = match state.smoothed_latency {
+                    Some(smoothed) => (smoothed * 7 + latency) / 8,
+                    None => latency,
+                };
+                state.smoothed_latency = Some(smoothed);
+
+                let baseline = state.baseline_latency.map_or(smoothed, |b| b.min(smoothed));
+                state.baseline_latency = Some(baseline);
+
+                smoothed > baseline * LATENCY_TOLERANCE
+            }
+            None => true,
+        };
+
+        if state.cooldown > 0 {
+            state.cooldown -= 1;
+        } else if congested {
+            let limit = (state.limit * 3 / 4).max(1);
+            if limit != state.limit {
+                info!("concurrency decreased to {}", limit);
+            }
+            state.limit = limit;
+            state.window = 0;
+            state.cooldown = state.inflight;
+            // the server may have recovered at a slower pace, don't keep comparing against the old best
+            state.baseline_latency = state.smoothed_latency;
+        } else {
+            state.window += 1;
+            if state.window >= state.limit && state.limit < self.max_limit {
+                state.limit += 1;
+                state.window = 0;
+                info!("concurrency increased to {}", state.limit);
+            }
+        }
+
+        drop(state);
+        self.notify.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: Duration = Duration::from_millis(10);
+
+    impl AdaptiveConcurrency {
+        fn limit(&self) -> usize {
+            self.state.lock().unwrap().limit
+        }
+
+        /// one request at a time that took latency, None for a server error
+        async fn respond(&self, latency: Option<Duration>) {
+            let mut permit = self.acquire().await;
+            if let Some(latency) = latency {
+                permit.succeeded(latency);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn limit_grows_by_one_after_a_window_of_fast_responses() {
+        let controller = AdaptiveConcurrency::new(4);
+        assert_eq!(controller.limit(), INITIAL_LIMIT);
+
+        // a window is as many responses as the current limit
+        controller.respond(Some(FAST)).await;
+        assert_eq!(controller.limit(), 2);
+        controller.respond(Some(FAST)).await;
+        assert_eq!(controller.limit(), 3);
+
+        for _ in 0..10 {
+            controller.respond(Some(FAST)).await;
+        }
+        assert_eq!(controller.limit(), 4);
+    }
+
+    #[tokio::test]
+    async fn limit_shrinks_by_a_quarter_on_a_server_error() {
+        let controller = AdaptiveConcurrency::new(8);
+        while controller.limit() < 8 {
+            controller.respond(Some(FAST)).await;
+        }
+
+        controller.respond(None).await;
+        assert_eq!(controller.limit(), 6);
+        controller.respond(None).await;
+        assert_eq!(controller.limit(), 4);
+
+        for _ in 0..4 {
+            controller.respond(None).await;
+        }
+        assert_eq!(controller.limit(), 1);
+    }
+
+    #[tokio::test]
+    async fn slow_responses_count_as_congestion() {
+        let controller = AdaptiveConcurrency::new(8);
+        while controller.limit() < 4 {
+            controller.respond(Some(FAST)).await;
+        }
+
+        // the smoothed latency goes past twice the fastest one
+        controller.respond(Some(FAST * 12)).await;
+        assert_eq!(controller.limit(), 3);
+    }
+
+    #[tokio::test]
+    async fn requests_wait_for_a_slot_under_the_limit() {
+        let controller = AdaptiveConcurrency::new(4);
+        let mut first = controller.acquire().await;
+        let _second = controller.acquire().await;
+
+        let third = tokio::time::timeout(Duration::from_millis(50), controller.acquire()).await;
+        assert!(third.is_err());
+
+        first.succeeded(FAST);
+        drop(first);
+        let third = tokio::time::timeout(Duration::from_secs(5), controller.acquire()).await;
+        assert!(third.is_ok());
+    }
+}
@@ -0,0 +1,548 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+/// The structured formats the /update handler accepts, files in these formats can be posted in batches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BatchFormat {
+    Json,
+    Xml,
+    Csv,
+}
+
+impl BatchFormat {
+    /// the format of the file from its extension, None if it must go through the extract handler
+    pub(crate) fn from_path(path: &str) -> Option<Self> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" | "jsonl" | "ndjson" => Some(BatchFormat::Json),
+            "xml" => Some(BatchFormat::Xml),
+            "csv" => Some(BatchFormat::Csv),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            BatchFormat::Json => "application/json",
+            BatchFormat::Xml => "application/xml",
+            BatchFormat::Csv => "text/csv",
+        }
+    }
+}
+
+/// A unit of work for a single request
+pub(crate) enum PostJob {
+    /// a file posted on its own to the extract handler
+    File(String),
+
+    /// structured files joined into one request to the /update handler
+    Batch(BatchFormat, Vec<String>),
+
+    /// a JSON Lines file posted in chunks of documents, see PostConfig::jsonl_chunk_size
+    JsonLines(String),
+}
+
+/// true for the JSON Lines files e.g. "docs.jsonl" or "docs.ndjson"
+pub(crate) fn is_json_lines(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("jsonl") || extension.eq_ignore_ascii_case("ndjson")
+        })
+}
+
+/// Groups the structured files into batches of batch_size per format, the other files pass through on their own
+/// every file passes through on its own when batch_size is None. JSON Lines files are posted in chunks
+/// instead when chunk_json_lines is set
+pub(crate) struct Batches<I> {
+    files: I,
+    batch_size: Option<usize>,
+    chunk_json_lines: bool,
+
+    /// the batches being filled, at most one per format
+    pending: Vec<(BatchFormat, Vec<String>)>,
+}
+
+impl<I: Iterator<Item = String>> Batches<I> {
+    pub(crate) fn new(files: I, batch_size: Option<usize>, chunk_json_lines: bool) -> Self {
+        Batches {
+            files,
+            batch_size,
+            chunk_json_lines,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = String>> Iterator for Batches<I> {
+    type Item = PostJob;
+
+    fn next(&mut self) -> Option<PostJob> {
+        loop {
+            let Some(file) = self.files.next() else {
+                // out of files, send the partially filled batches
+                return self
+                    .pending
+                    .pop()
+                    .map(|(format, files)| PostJob::Batch(format, files));
+            };
+
+            if self.chunk_json_lines && is_json_lines(&file) {
+                return Some(PostJob::JsonLines(file));
+            }
+
+            let (Some(batch_size), Some(format)) = (self.batch_size, BatchFormat::from_path(&file))
+            else {
+                return Some(PostJob::File(file));
+            };
+
+            let index = match self.pending.iter().position(|(f, _)| *f == format) {
+                Some(index) => index,
+                None => {
+                    self.pending.push((format, Vec::new()));
+                    self.pending.len() - 1
+                }
+            };
+
+            self.pending[index].1.push(file);
+            if self.pending[index].1.len() >= batch_size {
+                let (format, files) = self.pending.swap_remove(index);
+                return Some(PostJob::Batch(format, files));
+            }
+        }
+    }
+}
+
+/// A request body joined from several files, indexes are the positions of the files in the batch
+pub(crate) struct BatchBody {
+    pub(crate) indexes: Vec<usize>,
+    pub(crate) body: Vec<u8>,
+}
+
+/// join the contents of the files of a batch into request bodies, CSV files are grouped by their header line
+/// returns the bodies and the files that could not be joined with the reason
+/// fields are the names and values added to every JSON and XML document e.g. the run id, CSV documents get
+/// them as literal params. a new body is started once a body would grow past max_bytes, the documents of a
+/// file always go in the same body
+pub(crate) fn batch_bodies(
+    format: BatchFormat,
+    documents: Vec<(usize, Vec<u8>)>,
+    fields: &[(&str, &str)],
+    max_bytes: Option<usize>,
+) -> (Vec<BatchBody>, Vec<(usize, String)>) {
+    match format {
+        BatchFormat::Json => json_bodies(documents, fields, max_bytes),
+        BatchFormat::Xml => (xml_bodies(documents, fields, max_bytes), Vec::new()),
+        BatchFormat::Csv => (csv_bodies(documents, max_bytes), Vec::new()),
+    }
+}
+
+/// Joins the documents of the files into bodies of at most max_bytes e.g. "[" doc "," doc "]"
+struct BodyWriter {
+    open: &'static [u8],
+    separator: &'static [u8],
+    close: &'static [u8],
+    max_bytes: Option<usize>,
+    bodies: Vec<BatchBody>,
+}
+
+impl BodyWriter {
+    fn new(
+        open: &'static [u8],
+        separator: &'static [u8],
+        close: &'static [u8],
+        max_bytes: Option<usize>,
+    ) -> Self {
+        BodyWriter {
+            open,
+            separator,
+            close,
+            max_bytes,
+            bodies: Vec::new(),
+        }
+    }
+
+    /// add the documents of a file, a file bigger than max_bytes gets a body of its own
+    fn push(&mut self, index: usize, docs: &[u8]) {
+        let length = self.separator.len() + docs.len() + self.close.len();
+        let fits = self.bodies.last().is_some_and(|body| {
+            self.max_bytes
+                .is_none_or(|max_bytes| body.body.len() + length <= max_bytes)
+        });
+
+        match self.bodies.last_mut() {
+            Some(body) if fits => {
+                body.indexes.push(index);
+                body.body.extend_from_slice(self.separator);
+                body.body.extend_from_slice(docs);
+            }
+            _ => self.bodies.push(BatchBody {
+                indexes: vec![index],
+                body: [self.open, docs].concat(),
+            }),
+        }
+    }
+
+    fn finish(mut self) -> Vec<BatchBody> {
+        for body in self.bodies.iter_mut() {
+            body.body.extend_from_slice(self.close);
+        }
+        self.bodies
+    }
+}
+
+/// join the documents into JSON arrays, each file holds a document object, an array of them, or one
+/// document object per line (JSON Lines)
+fn json_bodies(
+    documents: Vec<(usize, Vec<u8>)>,
+    fields: &[(&str, &str)],
+    max_bytes: Option<usize>,
+) -> (Vec<BatchBody>, Vec<(usize, String)>) {
+    let mut writer = BodyWriter::new(b"[", b",", b"]", max_bytes);
+    let mut invalid = Vec::new();
+
+    for (index, content) in documents {
+        let values: Result<Vec<Value>, _> = serde_json::Deserializer::from_slice(&content)
+            .into_iter()
+            .collect();
+        let file_docs: Vec<Value> = match values {
+            Ok(values) if values.is_empty() => {
+                invalid.push((index, "no JSON documents".to_string()));
+                continue;
+            }
+            Ok(values) => values
+                .into_iter()
+                .flat_map(|value| match value {
+                    Value::Array(docs) => docs,
+                    doc => vec![doc],
+                })
+                .collect(),
+            Err(e) => {
+                invalid.push((index, e.to_string()));
+                continue;
+            }
+        };
+
+        if !file_docs.iter().all(Value::is_object) {
+            invalid.push((
+                index,
+                "expected a JSON object or array of objects".to_string(),
+            ));
+            continue;
+        }
+
+        let mut docs = Vec::new();
+        for mut doc in file_docs {
+            if let Some(doc) = doc.as_object_mut() {
+                for (field, value) in fields {
+                    doc.insert(field.to_string(), Value::String(value.to_string()));
+                }
+            }
+            if !docs.is_empty() {
+                docs.push(b',');
+            }
+            serde_json::to_writer(&mut docs, &doc).expect("JSON values always serialize");
+        }
+        writer.push(index, &docs);
+    }
+
+    (writer.finish(), invalid)
+}
+
+/// join the <doc> elements of the files into <add> elements
+fn xml_bodies(
+    documents: Vec<(usize, Vec<u8>)>,
+    fields: &[(&str, &str)],
+    max_bytes: Option<usize>,
+) -> Vec<BatchBody> {
+    let mut writer = BodyWriter::new(b"<add>", b"", b"</add>", max_bytes);
+
+    for (index, content) in documents {
+        let content = String::from_utf8_lossy(&content);
+        let docs = xml_docs(&content);
+
+        let mut field_elements: String = fields
+            .iter()
+            .map(|(field, value)| {
+                format!(
+                    "<field name=\"{}\">{}</field>",
+                    xml_escape(field),
+                    xml_escape(value)
+                )
+            })
+            .collect();
+        match field_elements.is_empty() {
+            true => writer.push(index, docs.as_bytes()),
+            false => {
+                field_elements.push_str("</doc>");
+                writer.push(index, docs.replace("</doc>", &field_elements).as_bytes());
+            }
+        }
+    }
+
+    writer.finish()
+}
+
+/// the contents of the <add> element of an update file, or the whole file if it is only <doc> elements
+fn xml_docs(content: &str) -> &str {
+    let mut content = content.trim();
+
+    // skip the <?xml ...?> declaration
+    if content.starts_with("<?xml") {
+        if let Some(end) = content.find("?>") {
+            content = content[end + 2..].trim_start();
+        }
+    }
+
+    let is_add = content.starts_with("<add")
+        && content[4..].starts_with(|c: char| c == '>' || c.is_whitespace());
+
+    if is_add {
+        if let (Some(open_end), Some(close)) = (content.find('>'), content.rfind("</add>")) {
+            if open_end < close {
+                return &content[open_end + 1..close];
+            }
+        }
+    }
+
+    content
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// join the rows of the CSV files, files with the same header line share a body with one header until it
+/// would grow past max_bytes
+fn csv_bodies(documents: Vec<(usize, Vec<u8>)>, max_bytes: Option<usize>) -> Vec<BatchBody> {
+    let mut bodies: Vec<(Vec<u8>, BatchBody)> = Vec::new();
+
+    for (index, content) in documents {
+        let header_end = content
+            .iter()
+            .position(|b| *b == b'\n')
+            .map_or(content.len(), |i| i + 1);
+        let header = content[..header_end].trim_ascii_end().to_vec();
+
+        let rows = content.len() - header_end;
+        let open_body = bodies.iter_mut().rev().find(|(h, _)| *h == header);
+        let open_body = open_body.filter(|(_, batch_body)| {
+            max_bytes.is_none_or(|max_bytes| batch_body.body.len() + rows <= max_bytes)
+        });
+
+        let body = match open_body {
+            Some((_, batch_body)) => {
+                batch_body.indexes.push(index);
+                batch_body.body.extend_from_slice(&content[header_end..]);
+                &mut batch_body.body
+            }
+            None => {
+                bodies.push((
+                    header,
+                    BatchBody {
+                        indexes: vec![index],
+                        body: content,
+                    },
+                ));
+                &mut bodies.last_mut().unwrap().1.body
+            }
+        };
+
+        // the next file's rows must start on a new line
+        if !body.is_empty() && !body.ends_with(b"\n") {
+            body.push(b'\n');
+        }
+    }
+
+    bodies
+        .into_iter()
+        .map(|(_, batch_body)| batch_body)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jobs(files: &[&str], batch_size: Option<usize>, chunk_json_lines: bool) -> Vec<String> {
+        let files = files.iter().map(|file| file.to_string());
+        Batches::new(files, batch_size, chunk_json_lines)
+            .map(|job| match job {
+                PostJob::File(file) => format!("file {}", file),
+                PostJob::Batch(format, files) => format!("{:?} {}", format, files.join(" ")),
+                PostJob::JsonLines(file) => format!("chunks {}", file),
+            })
+            .collect()
+    }
+
+    fn bodies(
+        format: BatchFormat,
+        contents: &[&str],
+        fields: &[(&str, &str)],
+        max_bytes: Option<usize>,
+    ) -> Vec<(Vec<usize>, String)> {
+        let documents = contents
+            .iter()
+            .enumerate()
+            .map(|(index, content)| (index, content.as_bytes().to_vec()))
+            .collect();
+        let (bodies, invalid) = batch_bodies(format, documents, fields, max_bytes);
+        assert!(invalid.is_empty());
+        bodies
+            .into_iter()
+            .map(|body| (body.indexes, String::from_utf8(body.body).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn batches_of_batch_size_files_per_format() {
+        let files = ["a.json", "b.xml", "c.json", "d.pdf", "e.json", "f.json"];
+        assert_eq!(
+            jobs(&files, Some(2), false),
+            [
+                "Json a.json c.json",
+                "file d.pdf",
+                "Json e.json f.json",
+                "Xml b.xml",
+            ]
+        );
+    }
+
+    #[test]
+    fn trailing_partial_batches_are_sent() {
+        let files = ["a.csv", "b.csv", "c.csv", "d.xml"];
+        assert_eq!(
+            jobs(&files, Some(2), false),
+            ["Csv a.csv b.csv", "Xml d.xml", "Csv c.csv"]
+        );
+    }
+
+    #[test]
+    fn files_pass_through_without_batch_size() {
+        assert_eq!(
+            jobs(&["a.json", "b.jsonl"], None, true),
+            ["file a.json", "chunks b.jsonl"]
+        );
+    }
+
+    #[test]
+    fn json_documents_join_into_an_array() {
+        let contents = [
+            r#"{"id":"1"}"#,
+            r#"[{"id":"2"},{"id":"3"}]"#,
+            "{\"id\":\"4\"}\n{\"id\":\"5\"}",
+        ];
+        assert_eq!(
+            bodies(BatchFormat::Json, &contents, &[("run_id", "r")], None),
+            [(
+                vec![0, 1, 2],
+                r#"[{"id":"1","run_id":"r"},{"id":"2","run_id":"r"},{"id":"3","run_id":"r"},{"id":"4","run_id":"r"},{"id":"5","run_id":"r"}]"#
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn invalid_json_files_are_left_out() {
+        let documents = vec![
+            (0, b"{\"id\":\"1\"}".to_vec()),
+            (1, b"[1, 2]".to_vec()),
+            (2, b"{".to_vec()),
+        ];
+        let (bodies, invalid) = batch_bodies(BatchFormat::Json, documents, &[], None);
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0].indexes, [0]);
+        assert_eq!(
+            invalid.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+            [1, 2]
+        );
+    }
+
+    #[test]
+    fn xml_documents_join_into_an_add_element() {
+        let contents = [
+            "<?xml version=\"1.0\"?>\n<add><doc><field name=\"id\">1</field></doc></add>",
+            "<doc><field name=\"id\">2</field></doc>",
+        ];
+        assert_eq!(
+            bodies(BatchFormat::Xml, &contents, &[("run_id", "a&b")], None),
+            [(
+                vec![0, 1],
+                concat!(
+                    "<add>",
+                    "<doc><field name=\"id\">1</field><field name=\"run_id\">a&amp;b</field></doc>",
+                    "<doc><field name=\"id\">2</field><field name=\"run_id\">a&amp;b</field></doc>",
+                    "</add>"
+                )
+                .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn csv_files_share_a_body_per_header() {
+        let contents = ["id,title\n1,a\n", "id,size\n2,3\n", "id,title\n4,b"];
+        assert_eq!(
+            bodies(BatchFormat::Csv, &contents, &[], None),
+            [
+                (vec![0, 2], "id,title\n1,a\n4,b\n".to_string()),
+                (vec![1], "id,size\n2,3\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_bodies_are_split_at_max_bytes() {
+        let contents = [r#"{"id":"1"}"#, r#"{"id":"2"}"#, r#"{"id":"3"}"#];
+        // "[" + 2 documents of 10 bytes + "," + "]"
+        assert_eq!(
+            bodies(BatchFormat::Json, &contents, &[], Some(23)),
+            [
+                (vec![0, 1], r#"[{"id":"1"},{"id":"2"}]"#.to_string()),
+                (vec![2], r#"[{"id":"3"}]"#.to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn files_bigger_than_max_bytes_get_a_body_of_their_own() {
+        let contents = ["<doc>1</doc>", "<doc>2</doc>"];
+        assert_eq!(
+            bodies(BatchFormat::Xml, &contents, &[], Some(5)),
+            [
+                (vec![0], "<add><doc>1</doc></add>".to_string()),
+                (vec![1], "<add><doc>2</doc></add>".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_bodies_are_split_at_max_bytes_with_the_header_repeated() {
+        let contents = ["id\n1\n", "id\n2\n", "id\n3\n"];
+        assert_eq!(
+            bodies(BatchFormat::Csv, &contents, &[], Some(7)),
+            [
+                (vec![0, 1], "id\n1\n2\n".to_string()),
+                (vec![2], "id\n3\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn format_from_the_extension() {
+        assert_eq!(
+            BatchFormat::from_path("a/b.NDJSON"),
+            Some(BatchFormat::Json)
+        );
+        assert_eq!(BatchFormat::from_path("b.csv"), Some(BatchFormat::Csv));
+        assert_eq!(BatchFormat::from_path("b.html"), None);
+        assert!(is_json_lines("docs.jsonl"));
+        assert!(!is_json_lines("docs.json"));
+    }
+}
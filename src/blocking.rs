@@ -0,0 +1,22 @@
+use tokio::runtime::Builder;
+
+use crate::{solr_post, ErrorCallback, PostConfig, PostSummary, SolrPostError};
+
+/// Post files to Solr server from synchronous code, e.g. build scripts or non async applications.
+/// Spins up its own tokio runtime and blocks until posting is complete, takes the same
+/// callbacks and returns the same summary as solr_post.
+/// must not be called from within an async runtime
+pub fn solr_post_blocking(
+    config: PostConfig,
+    on_start: Option<Box<dyn FnMut(u64)>>,
+    on_next: Option<Box<dyn FnMut(u64)>>,
+    on_error: Option<ErrorCallback>,
+    on_finish: Option<Box<dyn FnMut()>>,
+) -> Result<PostSummary, SolrPostError> {
+    let runtime = Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(SolrPostError::Runtime)?;
+
+    runtime.block_on(solr_post(config, on_start, on_next, on_error, on_finish))
+}
@@ -0,0 +1,1233 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+
+use regex::Regex;
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Url,
+};
+use tokio_util::sync::CancellationToken;
+use wax::Glob;
+
+#[cfg(feature = "markdown")]
+use crate::MarkdownBody;
+use crate::{
+    collection_url, AuthScheme, CommitPolicy, ConfigError, DocumentRoute, Login, PostConfig,
+    PostMode, RedirectPolicy, MAX_SPLIT_SIZE,
+};
+
+/// Builder for PostConfig that validates each field as it is set, the first invalid value
+/// is returned as an error from build()
+/// e.g. `PostConfig::builder().collection("my_collection").concurrency(16).build()?`
+pub struct PostConfigBuilder {
+    config: PostConfig,
+    error: Option<ConfigError>,
+}
+
+impl PostConfig {
+    /// create a builder starting from the default configuration
+    pub fn builder() -> PostConfigBuilder {
+        PostConfigBuilder {
+            config: PostConfig::default(),
+            error: None,
+        }
+    }
+}
+
+impl PostConfigBuilder {
+    /// keep the first validation error so build() reports the earliest mistake
+    fn invalid(mut self, error: ConfigError) -> Self {
+        self.error.get_or_insert(error);
+        self
+    }
+
+    /// the number of concurrent requests to make to the solr server, must be greater than 0
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        if concurrency == 0 {
+            return self.invalid(ConfigError::ZeroConcurrency);
+        }
+        self.config.concurrency = concurrency;
+        self
+    }
+
+    /// the host of the solr server, must not be empty
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        let host = host.into();
+        if host.trim().is_empty() {
+            return self.invalid(ConfigError::EmptyHost);
+        }
+        self.config.host = host;
+        self
+    }
+
+    /// the port of the solr server
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = port;
+        self
+    }
+
+    /// the solr collection to post to, must not be empty
+    pub fn collection(mut self, collection: impl Into<String>) -> Self {
+        let collection = collection.into();
+        if collection.trim().is_empty() {
+            return self.invalid(ConfigError::EmptyCollection);
+        }
+        self.config.collection = collection;
+        self
+    }
+
+    /// the directory to search for files to post
+    pub fn directory_path(mut self, directory_path: impl Into<PathBuf>) -> Self {
+        self.config.directory_path = directory_path.into();
+        self
+    }
+
+    /// more directories to search for files to post in the same run, a file under several is posted once
+    pub fn directory_paths<I, P>(mut self, directory_paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.config
+            .directory_paths
+            .extend(directory_paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// the file extensions to post, must not be empty e.g. ["html", "txt"]
+    pub fn file_extensions<I, S>(mut self, file_extensions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let file_extensions: Vec<String> = file_extensions.into_iter().map(Into::into).collect();
+        if file_extensions.is_empty() {
+            return self.invalid(ConfigError::NoFileExtensions);
+        }
+        self.config.file_extensions = file_extensions;
+        self
+    }
+
+    /// base Solr update URL, must be a valid http or https URL
+    pub fn update_url(mut self, update_url: impl Into<String>) -> Self {
+        let update_url = update_url.into();
+        if !is_http_url(&update_url) {
+            return self.invalid(ConfigError::InvalidUpdateUrl(update_url));
+        }
+        self.config.update_url = Some(update_url);
+        self
+    }
+
+    /// the update URLs of the same collection on other Solr nodes to spread the requests across, each must be
+    /// a valid http or https URL
+    pub fn update_urls<I, S>(mut self, update_urls: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for update_url in update_urls.into_iter().map(Into::into) {
+            if !is_http_url(&update_url) {
+                return self.invalid(ConfigError::InvalidUpdateUrl(update_url));
+            }
+            self.config.update_urls.push(update_url);
+        }
+        self
+    }
+
+    /// exclude files who's content matches this regex
+    pub fn exclude_regex(mut self, exclude_regex: Regex) -> Self {
+        self.config.exclued_regex = Some(exclude_regex);
+        self
+    }
+
+    /// include only files who's content matches this regex
+    pub fn include_regex(mut self, include_regex: Regex) -> Self {
+        self.config.include_regex = Some(include_regex);
+        self
+    }
+
+    /// basic auth user credentials, must be in the form "user:pass"
+    pub fn basic_auth_creds(mut self, basic_auth_creds: impl Into<String>) -> Self {
+        let basic_auth_creds = basic_auth_creds.into();
+        if !basic_auth_creds.contains(':') {
+            return self.invalid(ConfigError::InvalidBasicAuthCreds);
+        }
+        self.config.auth = Some(AuthScheme::Basic(basic_auth_creds));
+        self
+    }
+
+    /// authenticate with a bearer token e.g. a JWT, must be non empty visible ASCII
+    pub fn bearer_token(mut self, bearer_token: impl Into<String>) -> Self {
+        let bearer_token = bearer_token.into();
+        if bearer_token.is_empty() || !bearer_token.bytes().all(|b| b.is_ascii_graphic()) {
+            return self.invalid(ConfigError::InvalidBearerToken);
+        }
+        self.config.auth = Some(AuthScheme::Bearer(bearer_token));
+        self
+    }
+
+    /// how the requests are authenticated e.g. AuthScheme::Netrc(None), basic auth credentials and
+    /// bearer tokens are checked like basic_auth_creds and bearer_token
+    pub fn auth(mut self, auth: AuthScheme) -> Self {
+        match auth {
+            AuthScheme::Basic(basic_auth_creds) => self.basic_auth_creds(basic_auth_creds),
+            AuthScheme::Bearer(bearer_token) => self.bearer_token(bearer_token),
+            auth => {
+                self.config.auth = Some(auth);
+                self
+            }
+        }
+    }
+
+    /// explicit Solr commit URL, must be a valid http or https URL
+    pub fn commit_url(mut self, commit_url: impl Into<String>) -> Self {
+        let commit_url = commit_url.into();
+        if !is_http_url(&commit_url) {
+            return self.invalid(ConfigError::InvalidCommitUrl(commit_url));
+        }
+        self.config.commit_url = Some(commit_url);
+        self
+    }
+
+    /// tag every document with the run id of this invocation
+    pub fn index_run_id(mut self, index_run_id: bool) -> Self {
+        self.config.index_run_id = index_run_id;
+        self
+    }
+
+    /// the field name the run id is indexed into, must not be empty
+    pub fn run_id_field(mut self, run_id_field: impl Into<String>) -> Self {
+        let run_id_field = run_id_field.into();
+        if run_id_field.trim().is_empty() {
+            return self.invalid(ConfigError::EmptyRunIdField);
+        }
+        self.config.run_id_field = run_id_field;
+        self
+    }
+
+    /// the run id of this invocation
+    pub fn run_id(mut self, run_id: impl Into<String>) -> Self {
+        self.config.run_id = Some(run_id.into());
+        self
+    }
+
+    /// commit after this many documents have been posted, must be greater than 0
+    pub fn commit_every(mut self, commit_every: usize) -> Self {
+        if commit_every == 0 {
+            return self.invalid(ConfigError::ZeroCommitEvery);
+        }
+        self.config.commit_every = Some(commit_every);
+        self
+    }
+
+    /// commit when this much time has passed since the last commit, must not be zero
+    pub fn commit_interval(mut self, commit_interval: Duration) -> Self {
+        if commit_interval.is_zero() {
+            return self.invalid(ConfigError::ZeroCommitInterval);
+        }
+        self.config.commit_interval = Some(commit_interval);
+        self
+    }
+
+    /// memory map files for the content regex scan
+    #[cfg(feature = "mmap")]
+    pub fn mmap_scan(mut self, mmap_scan: bool) -> Self {
+        self.config.mmap_scan = mmap_scan;
+        self
+    }
+
+    /// send the document's `_version_` for optimistic concurrency
+    pub fn optimistic_concurrency(mut self, optimistic_concurrency: bool) -> Self {
+        self.config.optimistic_concurrency = optimistic_concurrency;
+        self
+    }
+
+    /// cancel the run when this token is cancelled
+    pub fn cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.config.cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    /// commit the documents that were already indexed when the run is cancelled
+    pub fn commit_on_cancel(mut self, commit_on_cancel: bool) -> Self {
+        self.config.commit_on_cancel = commit_on_cancel;
+        self
+    }
+
+    /// cap the sum of the body sizes of the requests in flight, must be greater than 0
+    pub fn max_inflight_bytes(mut self, max_inflight_bytes: u64) -> Self {
+        if max_inflight_bytes == 0 {
+            return self.invalid(ConfigError::ZeroMaxInflightBytes);
+        }
+        self.config.max_inflight_bytes = Some(max_inflight_bytes);
+        self
+    }
+
+    /// gzip encode the request bodies
+    pub fn compress_requests(mut self, compress_requests: bool) -> Self {
+        self.config.compress_requests = compress_requests;
+        self
+    }
+
+    /// give up connecting to the solr server after this long, must not be zero
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        if connect_timeout.is_zero() {
+            return self.invalid(ConfigError::ZeroTimeout);
+        }
+        self.config.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// give up on a single request after this long, must not be zero
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        if request_timeout.is_zero() {
+            return self.invalid(ConfigError::ZeroTimeout);
+        }
+        self.config.request_timeout = Some(request_timeout);
+        self
+    }
+
+    /// abort the whole run if it takes longer than this, must not be zero
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        if deadline.is_zero() {
+            return self.invalid(ConfigError::ZeroTimeout);
+        }
+        self.config.deadline = Some(deadline);
+        self
+    }
+
+    /// trip the circuit breaker after this many consecutive server errors, must be greater than 0
+    pub fn circuit_breaker_threshold(mut self, circuit_breaker_threshold: usize) -> Self {
+        if circuit_breaker_threshold == 0 {
+            return self.invalid(ConfigError::ZeroCircuitBreakerThreshold);
+        }
+        self.config.circuit_breaker_threshold = Some(circuit_breaker_threshold);
+        self
+    }
+
+    /// how long to pause posting when the circuit breaker trips
+    pub fn circuit_breaker_wait(mut self, circuit_breaker_wait: Duration) -> Self {
+        self.config.circuit_breaker_wait = circuit_breaker_wait;
+        self
+    }
+
+    /// how many times the circuit breaker may trip before the run is aborted
+    pub fn circuit_breaker_retries(mut self, circuit_breaker_retries: usize) -> Self {
+        self.config.circuit_breaker_retries = circuit_breaker_retries;
+        self
+    }
+
+    /// send at most this many requests per second, must be a positive number
+    pub fn max_requests_per_second(mut self, max_requests_per_second: f64) -> Self {
+        if !(max_requests_per_second.is_finite() && max_requests_per_second > 0.0) {
+            return self.invalid(ConfigError::InvalidRequestRate);
+        }
+        self.config.max_requests_per_second = Some(max_requests_per_second);
+        self
+    }
+
+    /// ramp the concurrency up or down based on response times, concurrency is the upper limit
+    pub fn adaptive_concurrency(mut self, adaptive_concurrency: bool) -> Self {
+        self.config.adaptive_concurrency = adaptive_concurrency;
+        self
+    }
+
+    /// post json, jsonl, xml, and csv files to the /update handler in batches of this many files, must be greater than 0
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        if batch_size == 0 {
+            return self.invalid(ConfigError::ZeroBatchSize);
+        }
+        self.config.batch_size = Some(batch_size);
+        self
+    }
+
+    /// the body size after which a batch is posted in another request, must be greater than 0
+    pub fn batch_max_bytes(mut self, batch_max_bytes: usize) -> Self {
+        if batch_max_bytes == 0 {
+            return self.invalid(ConfigError::ZeroBatchMaxBytes);
+        }
+        self.config.batch_max_bytes = Some(batch_max_bytes);
+        self
+    }
+
+    /// how the posted documents are committed
+    pub fn commit(mut self, commit: CommitPolicy) -> Self {
+        self.config.commit = commit;
+        self
+    }
+
+    /// optimize the collection once posting is complete
+    pub fn optimize_after(mut self, optimize_after: bool) -> Self {
+        self.config.optimize_after = optimize_after;
+        self
+    }
+
+    /// merge down to at most this many segments when optimizing, must be greater than 0
+    pub fn optimize_max_segments(mut self, optimize_max_segments: u32) -> Self {
+        if optimize_max_segments == 0 {
+            return self.invalid(ConfigError::ZeroMaxSegments);
+        }
+        self.config.optimize_max_segments = Some(optimize_max_segments);
+        self
+    }
+
+    /// roll back instead of committing once this many files have failed, must be greater than 0
+    pub fn rollback_on_failures(mut self, rollback_on_failures: usize) -> Self {
+        if rollback_on_failures == 0 {
+            return self.invalid(ConfigError::ZeroRollbackOnFailures);
+        }
+        self.config.rollback_on_failures = Some(rollback_on_failures);
+        self
+    }
+
+    /// delete the documents of files that no longer exist after indexing
+    pub fn sync(mut self, sync: bool) -> Self {
+        self.config.sync = sync;
+        self
+    }
+
+    /// delete every document in the collection before posting
+    pub fn clean(mut self, clean: bool) -> Self {
+        self.config.clean = clean;
+        self
+    }
+
+    /// record the indexed files in this state file after each run
+    pub fn state_file(mut self, state_file: impl Into<PathBuf>) -> Self {
+        self.config.state_file = Some(state_file.into());
+        self
+    }
+
+    /// skip the files that haven't changed since they were recorded in the state file
+    pub fn incremental(mut self, incremental: bool) -> Self {
+        self.config.incremental = incremental;
+        self
+    }
+
+    /// append the indexed files to this journal so an interrupted run can be resumed
+    pub fn journal_file(mut self, journal_file: impl Into<PathBuf>) -> Self {
+        self.config.journal_file = Some(journal_file.into());
+        self
+    }
+
+    /// skip the files recorded in the journal by an interrupted run
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.config.resume = resume;
+        self
+    }
+
+    /// write the failed files with their errors to this JSON lines report
+    pub fn error_report(mut self, error_report: impl Into<PathBuf>) -> Self {
+        self.config.error_report = Some(error_report.into());
+        self
+    }
+
+    /// post only the files listed in an error report of a previous run
+    pub fn from_report(mut self, from_report: impl Into<PathBuf>) -> Self {
+        self.config.from_report = Some(from_report.into());
+        self
+    }
+
+    /// abort the run once this many files have failed
+    pub fn max_errors(mut self, max_errors: usize) -> Self {
+        if max_errors == 0 {
+            return self.invalid(ConfigError::ZeroMaxErrors);
+        }
+        self.config.max_errors = Some(max_errors);
+        self
+    }
+
+    /// push the metrics of the run to this Prometheus Pushgateway
+    #[cfg(feature = "metrics")]
+    pub fn metrics_push_url(mut self, metrics_push_url: impl Into<String>) -> Self {
+        let metrics_push_url = metrics_push_url.into();
+        if Url::parse(&metrics_push_url).is_err() {
+            return self.invalid(ConfigError::InvalidMetricsPushUrl(metrics_push_url));
+        }
+        self.config.metrics_push_url = Some(metrics_push_url);
+        self
+    }
+
+    /// serve the metrics of the run for Prometheus to scrape on this address
+    #[cfg(feature = "metrics")]
+    pub fn metrics_listen(mut self, metrics_listen: SocketAddr) -> Self {
+        self.config.metrics_listen = Some(metrics_listen);
+        self
+    }
+
+    /// send StatsD counters and timers for every document to this host:port
+    #[cfg(feature = "statsd")]
+    pub fn statsd_addr(mut self, statsd_addr: impl Into<String>) -> Self {
+        self.config.statsd_addr = Some(statsd_addr.into());
+        self
+    }
+
+    /// check the collection's ping handler before scanning
+    pub fn ping(mut self, ping: bool) -> Self {
+        self.config.ping = ping;
+        self
+    }
+
+    /// wait up to this long for Solr to answer the ping before posting, must not be zero
+    pub fn wait_for_solr(mut self, wait_for_solr: Duration) -> Self {
+        if wait_for_solr.is_zero() {
+            return self.invalid(ConfigError::ZeroTimeout);
+        }
+        self.config.wait_for_solr = Some(wait_for_solr);
+        self
+    }
+
+    /// count the documents in Solr after the final commit and fail if fewer than the indexed files
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.config.verify = verify;
+        self
+    }
+
+    /// read the SolrCloud nodes of the collection from this ZooKeeper ensemble and spread the posts across them
+    pub fn zk_hosts(mut self, zk_hosts: impl Into<String>) -> Self {
+        let zk_hosts = zk_hosts.into();
+        if zk_hosts.trim().is_empty() {
+            return self.invalid(ConfigError::EmptyZkHosts);
+        }
+        self.config.zk_hosts = Some(zk_hosts);
+        self
+    }
+
+    /// more collections every file is also indexed into, none may be empty
+    pub fn extra_collections<I, S>(mut self, extra_collections: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for collection in extra_collections.into_iter().map(Into::into) {
+            if collection.trim().is_empty() {
+                return self.invalid(ConfigError::EmptyCollection);
+            }
+            self.config.extra_collections.push(collection);
+        }
+        self
+    }
+
+    /// the `_route_` param sent with each document for collections using the implicit router, a fixed route
+    /// must not be empty
+    pub fn route(mut self, route: DocumentRoute) -> Self {
+        if matches!(&route, DocumentRoute::Fixed(route) if route.trim().is_empty()) {
+            return self.invalid(ConfigError::EmptyRoute);
+        }
+        self.config.route = Some(route);
+        self
+    }
+
+    /// build the URLs from host, port, and collection with the v2 API paths /api/collections/<collection>/...
+    pub fn v2_api(mut self, v2_api: bool) -> Self {
+        self.config.v2_api = v2_api;
+        self
+    }
+
+    /// a PEM file with the certificates of the CA that signed Solr's certificate, trusted for https URLs
+    pub fn ca_cert(mut self, ca_cert: impl Into<PathBuf>) -> Self {
+        self.config.ca_cert = Some(ca_cert.into());
+        self
+    }
+
+    /// DANGEROUS: accept any TLS certificate without verifying it, only for dev clusters with self-signed certificates
+    pub fn accept_invalid_certs(mut self, accept_invalid_certs: bool) -> Self {
+        self.config.accept_invalid_certs = accept_invalid_certs;
+        self
+    }
+
+    /// the client certificate presented for mutual TLS, a PEM certificate or a PKCS#12 archive (.p12 or .pfx)
+    pub fn client_cert(mut self, client_cert: impl Into<PathBuf>) -> Self {
+        self.config.client_cert = Some(client_cert.into());
+        self
+    }
+
+    /// the PKCS#8 PEM private key of a PEM client certificate, requires client_cert
+    pub fn client_key(mut self, client_key: impl Into<PathBuf>) -> Self {
+        self.config.client_key = Some(client_key.into());
+        self
+    }
+
+    /// the password of a PKCS#12 client certificate
+    pub fn client_cert_password(mut self, client_cert_password: impl Into<String>) -> Self {
+        self.config.client_cert_password = Some(client_cert_password.into());
+        self
+    }
+
+    /// a header sent with every request e.g. header("X-Api-Key", "abc"), can be called again for more headers
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        match (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            (Ok(name), Ok(value)) => {
+                self.config.extra_headers.append(name, value);
+                self
+            }
+            _ => self.invalid(ConfigError::InvalidHeader(name.to_string())),
+        }
+    }
+
+    /// the proxy for every request, must be a valid http or https URL
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        let proxy = proxy.into();
+        if !is_http_url(&proxy) {
+            return self.invalid(ConfigError::InvalidProxyUrl(proxy));
+        }
+        self.config.proxy = Some(proxy);
+        self
+    }
+
+    /// post this login form before the first request and send its session cookie with every request,
+    /// the URL must be a valid http or https URL e.g. login("https://sso.example.com/login", [("user", "indexer")])
+    pub fn login<K, V>(
+        mut self,
+        url: impl Into<String>,
+        form: impl IntoIterator<Item = (K, V)>,
+    ) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let url = url.into();
+        if !is_http_url(&url) {
+            return self.invalid(ConfigError::InvalidLoginUrl(url));
+        }
+        self.config.login = Some(Login {
+            url,
+            form: form
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+        });
+        self
+    }
+
+    /// follow up to N redirects, or never follow them and fail on a 3xx response
+    pub fn redirect(mut self, redirect: RedirectPolicy) -> Self {
+        self.config.redirect = redirect;
+        self
+    }
+
+    /// connect to the address instead of resolving the host with DNS, can be called again for more hosts
+    pub fn resolve(mut self, host: impl Into<String>, addr: SocketAddr) -> Self {
+        self.config.resolve.push((host.into(), addr));
+        self
+    }
+
+    /// make the connections from this local address
+    pub fn local_address(mut self, local_address: IpAddr) -> Self {
+        self.config.local_address = Some(local_address);
+        self
+    }
+
+    /// add the field with the value to every document, the field must not be empty. can be called again for
+    /// more fields
+    pub fn literal(mut self, field: impl Into<String>, value: impl Into<String>) -> Self {
+        let field = field.into();
+        if field.is_empty() {
+            return self.invalid(ConfigError::EmptyLiteralField);
+        }
+        self.config.literals.push((field, value.into()));
+        self
+    }
+
+    /// add the param to the query string of every update request e.g. param("uprefix", "attr_"), the name must
+    /// not be empty. can be called again for more params
+    pub fn param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let name = name.into();
+        if name.is_empty() {
+            return self.invalid(ConfigError::EmptyParamName);
+        }
+        self.config.params.push((name, value.into()));
+        self
+    }
+
+    /// return the extracted content and metadata of each file instead of indexing it
+    pub fn extract_only(mut self, extract_only: bool) -> Self {
+        self.config.extract_only = extract_only;
+        self
+    }
+
+    /// write the extracted content and metadata of each file to this directory, implies extract_only
+    pub fn extract_output(mut self, extract_output: impl Into<PathBuf>) -> Self {
+        self.config.extract_only = true;
+        self.config.extract_output = Some(extract_output.into());
+        self
+    }
+
+    /// extract the XHTML element into a separate field, must not be empty. can be called again for more elements
+    pub fn capture(mut self, element: impl Into<String>) -> Self {
+        let element = element.into();
+        if element.is_empty() {
+            return self.invalid(ConfigError::EmptyCapture);
+        }
+        self.config.capture.push(element);
+        self
+    }
+
+    /// index the attributes of the XHTML elements into separate fields
+    pub fn capture_attr(mut self, capture_attr: bool) -> Self {
+        self.config.capture_attr = capture_attr;
+        self
+    }
+
+    /// only index the XHTML content matching the XPath expression, must not be empty
+    pub fn xpath(mut self, xpath: impl Into<String>) -> Self {
+        let xpath = xpath.into();
+        if xpath.is_empty() {
+            return self.invalid(ConfigError::EmptyXpath);
+        }
+        self.config.xpath = Some(xpath);
+        self
+    }
+
+    /// post structured files to the /update handler as Solr documents instead of extracting them
+    pub fn structured_update(mut self, structured_update: bool) -> Self {
+        self.config.structured_update = structured_update;
+        self
+    }
+
+    /// how the files are posted e.g. PostMode::JsonDocs
+    pub fn mode(mut self, mode: PostMode) -> Self {
+        self.config.mode = mode;
+        self
+    }
+
+    /// split the JSON of PostMode::JsonDocs into documents at this path, must start with / e.g. "/exams"
+    pub fn json_split(mut self, json_split: impl Into<String>) -> Self {
+        let json_split = json_split.into();
+        if !json_split.starts_with('/') {
+            return self.invalid(ConfigError::InvalidJsonPath(json_split));
+        }
+        self.config.json_split = Some(json_split);
+        self
+    }
+
+    /// take a field from the JSON of PostMode::JsonDocs, the f param e.g. "title:/book/title" or "/**".
+    /// can be called again for more fields
+    pub fn json_field(mut self, json_field: impl Into<String>) -> Self {
+        let json_field = json_field.into();
+        if json_field.is_empty() {
+            return self.invalid(ConfigError::InvalidJsonPath(json_field));
+        }
+        self.config.json_fields.push(json_field);
+        self
+    }
+
+    /// separate the values of PostMode::Csv by this character instead of ',' e.g. ';'
+    pub fn csv_separator(mut self, csv_separator: char) -> Self {
+        self.config.csv_separator = Some(csv_separator);
+        self
+    }
+
+    /// false if the first line of the CSV files is not a header, the field names must then be set
+    pub fn csv_header(mut self, csv_header: bool) -> Self {
+        self.config.csv_header = csv_header;
+        self
+    }
+
+    /// the field names of the CSV columns in order, an empty name skips the column. can be called
+    /// again for the next columns
+    pub fn csv_fieldname(mut self, csv_fieldname: impl Into<String>) -> Self {
+        let csv_fieldname = csv_fieldname.into();
+        if csv_fieldname.contains(',') {
+            return self.invalid(ConfigError::InvalidCsvFieldName(csv_fieldname));
+        }
+        self.config.csv_fieldnames.push(csv_fieldname);
+        self
+    }
+
+    /// skip this many lines at the start of the CSV files before the header
+    pub fn csv_skip_lines(mut self, csv_skip_lines: usize) -> Self {
+        self.config.csv_skip_lines = Some(csv_skip_lines);
+        self
+    }
+
+    /// quote the CSV values holding the separator with this character e.g. '\''
+    pub fn csv_encapsulator(mut self, csv_encapsulator: char) -> Self {
+        self.config.csv_encapsulator = Some(csv_encapsulator);
+        self
+    }
+
+    /// post .jsonl and .ndjson files in chunks of this many documents instead of as one body, must be greater than 0
+    pub fn jsonl_chunk_size(mut self, jsonl_chunk_size: usize) -> Self {
+        if jsonl_chunk_size == 0 {
+            return self.invalid(ConfigError::ZeroJsonlChunkSize);
+        }
+        self.config.jsonl_chunk_size = Some(jsonl_chunk_size);
+        self
+    }
+
+    /// post plain text files larger than this many bytes as several documents of at most this many bytes,
+    /// must be greater than 0 and at most MAX_SPLIT_SIZE e.g. 10 * 1024 * 1024
+    pub fn split_large_files(mut self, split_size: u64) -> Self {
+        if split_size == 0 {
+            return self.invalid(ConfigError::ZeroSplitSize);
+        }
+        if split_size > MAX_SPLIT_SIZE {
+            return self.invalid(ConfigError::SplitSizeTooLarge);
+        }
+        self.config.split_large_files = Some(split_size);
+        self
+    }
+
+    /// index the front matter of Markdown files as literal fields and post the body after it
+    #[cfg(feature = "markdown")]
+    pub fn markdown(mut self, body: MarkdownBody) -> Self {
+        self.config.markdown = Some(body);
+        self
+    }
+
+    /// send the title, description, canonical URL, og: properties, and lang of HTML files as literal fields
+    pub fn html_metadata(mut self, html_metadata: bool) -> Self {
+        self.config.html_metadata = html_metadata;
+        self
+    }
+
+    /// add the fields of the sidecar file with this suffix next to each document as literals
+    /// e.g. DEFAULT_SIDECAR_SUFFIX
+    pub fn sidecar_suffix(mut self, sidecar_suffix: impl Into<String>) -> Self {
+        let sidecar_suffix = sidecar_suffix.into();
+        if sidecar_suffix.is_empty() {
+            return self.invalid(ConfigError::EmptySidecarSuffix);
+        }
+        self.config.sidecar_suffix = Some(sidecar_suffix);
+        self
+    }
+
+    /// prepend this to the id of every document e.g. "docs:", clean, sync, and verify are scoped to it
+    pub fn id_prefix(mut self, id_prefix: impl Into<String>) -> Self {
+        let id_prefix = id_prefix.into();
+        if id_prefix.is_empty() {
+            return self.invalid(ConfigError::EmptyIdPrefix);
+        }
+        self.config.id_prefix = Some(id_prefix);
+        self
+    }
+
+    /// use the URLs of the files under this site as their ids and index them in the url field,
+    /// must be a valid http or https URL e.g. "https://example.com/"
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        let base_url = base_url.into();
+        if !is_http_url(&base_url) {
+            return self.invalid(ConfigError::InvalidBaseUrl(base_url));
+        }
+        self.config.base_url = Some(base_url);
+        self
+    }
+
+    /// the directory removed from the paths of the files before they are appended to the base_url
+    /// e.g. "./build", defaults to the directory being posted
+    pub fn strip_path(mut self, strip_path: impl Into<PathBuf>) -> Self {
+        self.config.strip_path = Some(strip_path.into());
+        self
+    }
+
+    /// send the named groups of the regex matching the path of a file below the directory as literal fields
+    /// e.g. "^docs/(?P<lang>[a-z]{2})/", must have a named group. can be called again for more regexes
+    pub fn path_field(mut self, regex: Regex) -> Self {
+        if regex.capture_names().flatten().next().is_none() {
+            return self.invalid(ConfigError::PathFieldWithoutNames(regex.to_string()));
+        }
+        self.config.path_fields.push(regex);
+        self
+    }
+
+    /// send the directory at the next depth below the directory as a literal field with this name, an empty
+    /// name skips the directory e.g. "category" then "subcategory"
+    pub fn directory_field(mut self, directory_field: impl Into<String>) -> Self {
+        self.config.directory_fields.push(directory_field.into());
+        self
+    }
+
+    /// send the modification and creation times of each file as the last_modified and created fields
+    pub fn file_dates(mut self, file_dates: bool) -> Self {
+        self.config.file_dates = file_dates;
+        self
+    }
+
+    /// send the size of each file in bytes as the file_size_bytes field
+    pub fn file_size(mut self, file_size: bool) -> Self {
+        self.config.file_size = file_size;
+        self
+    }
+
+    /// send the hex sha256 of the contents of each file as the content_hash field
+    pub fn content_hash(mut self, content_hash: bool) -> Self {
+        self.config.content_hash = content_hash;
+        self
+    }
+
+    /// detect the language of the text files and send its ISO 639-3 code as the language field
+    #[cfg(feature = "language")]
+    pub fn detect_language(mut self, detect_language: bool) -> Self {
+        self.config.detect_language = detect_language;
+        self
+    }
+
+    /// post the files with this extension with this Content-Type instead of the guessed one
+    /// e.g. mime_override("tmpl", "text/html"), the MIME type must be valid. can be called again for more
+    pub fn mime_override(
+        mut self,
+        extension: impl Into<String>,
+        mime_type: impl Into<String>,
+    ) -> Self {
+        let extension = extension.into().trim_start_matches('.').to_lowercase();
+        let mime_type = mime_type.into();
+        if extension.is_empty() || mime_type.parse::<mime_guess::mime::Mime>().is_err() {
+            return self.invalid(ConfigError::InvalidMimeOverride(format!(
+                "{}={}",
+                extension, mime_type
+            )));
+        }
+        self.config.mime_overrides.insert(extension, mime_type);
+        self
+    }
+
+    /// post the text files that aren't UTF-8 transcoded to UTF-8 from the encoding detected for them
+    pub fn transcode(mut self, transcode: bool) -> Self {
+        self.config.transcode = transcode;
+        self
+    }
+
+    /// post the files below the directory matched by the glob instead of the ones with the file extensions
+    /// e.g. "content/**/{posts,pages}/*.html", must be a valid glob. can be called again for more globs
+    pub fn glob(mut self, glob: impl Into<String>) -> Self {
+        let glob = glob.into();
+        if Glob::new(&glob).is_err() {
+            return self.invalid(ConfigError::InvalidGlob(glob));
+        }
+        self.config.globs.push(glob);
+        self
+    }
+
+    /// skip the files below the directories matched by the glob, a matched directory isn't walked
+    /// e.g. "**/node_modules/**", must be a valid glob. can be called again for more globs
+    pub fn exclude_glob(mut self, exclude_glob: impl Into<String>) -> Self {
+        let exclude_glob = exclude_glob.into();
+        if Glob::new(&exclude_glob).is_err() {
+            return self.invalid(ConfigError::InvalidGlob(exclude_glob));
+        }
+        self.config.exclude_globs.push(exclude_glob);
+        self
+    }
+
+    /// skip the files whose path below the directories matches this regex e.g. "^archive/", the path has
+    /// / separators on every platform. takes precedence over include_path_regex
+    pub fn exclude_path_regex(mut self, exclude_path_regex: Regex) -> Self {
+        self.config.exclude_path_regex = Some(exclude_path_regex);
+        self
+    }
+
+    /// include only the files whose path below the directories matches this regex e.g. "^docs/"
+    pub fn include_path_regex(mut self, include_path_regex: Regex) -> Self {
+        self.config.include_path_regex = Some(include_path_regex);
+        self
+    }
+
+    /// skip the files ignored by the .gitignore and .solrignore files above them
+    pub fn respect_ignore_files(mut self, respect_ignore_files: bool) -> Self {
+        self.config.respect_ignore_files = respect_ignore_files;
+        self
+    }
+
+    /// post exactly the files listed in this file, one path per line, instead of walking the directories
+    pub fn file_list(mut self, file_list: impl Into<PathBuf>) -> Self {
+        self.config.file_list = Some(file_list.into());
+        self
+    }
+
+    /// return the configuration, or the first invalid value that was set.
+    /// also checks that the commit URL can be determined from the update URL
+    pub fn build(self) -> Result<PostConfig, ConfigError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        if self.config.incremental && self.config.state_file.is_none() {
+            return Err(ConfigError::IncrementalWithoutStateFile);
+        }
+
+        if self.config.resume && self.config.journal_file.is_none() {
+            return Err(ConfigError::ResumeWithoutJournal);
+        }
+
+        if self.config.verify
+            && !matches!(self.config.commit, CommitPolicy::Hard | CommitPolicy::Soft)
+        {
+            return Err(ConfigError::VerifyWithoutCommit);
+        }
+
+        if self.config.strip_path.is_some() && self.config.base_url.is_none() {
+            return Err(ConfigError::StripPathWithoutBaseUrl);
+        }
+
+        if self.config.file_list.is_some() && self.config.from_report.is_some() {
+            return Err(ConfigError::FileListWithFromReport);
+        }
+
+        if self.config.client_key.is_some() && self.config.client_cert.is_none() {
+            return Err(ConfigError::ClientKeyWithoutCert);
+        }
+
+        if matches!(self.config.route, Some(DocumentRoute::PathComponent(_)))
+            && self.config.update_batch_size().is_some()
+        {
+            return Err(ConfigError::RouteFromPathWithBatching);
+        }
+
+        if self.config.mode != PostMode::Extract && self.config.update_batch_size().is_some() {
+            return Err(ConfigError::ModeWithBatching(self.config.mode));
+        }
+
+        if self.config.csv_separator.is_some()
+            && self.config.csv_separator == self.config.csv_encapsulator
+        {
+            return Err(ConfigError::CsvSeparatorIsEncapsulator);
+        }
+
+        if !self.config.csv_header && self.config.csv_fieldnames.is_empty() {
+            return Err(ConfigError::CsvWithoutFieldNames);
+        }
+
+        if self.config.extract_only {
+            let config = &self.config;
+            let modifies_index = [
+                ("batching", config.update_batch_size().is_some()),
+                ("JSON Lines chunks", config.jsonl_chunk_size.is_some()),
+                (
+                    "a mode other than extract",
+                    config.mode != PostMode::Extract,
+                ),
+                ("clean", config.clean),
+                ("sync", config.sync),
+                ("a state file", config.state_file.is_some()),
+                ("verify", config.verify),
+                ("optimize", config.optimize_after),
+            ];
+            if let Some((option, _)) = modifies_index.into_iter().find(|(_, set)| *set) {
+                return Err(ConfigError::ExtractOnlyWith(option));
+            }
+        }
+
+        let commit_url = self.config.commit_url().map_err(ConfigError::CommitUrl)?;
+
+        // every URL of a run is derived from these two, so each extra collection must fit in both
+        for collection in &self.config.extra_collections {
+            collection_url(&self.config.update_endpoint(), collection)
+                .map_err(ConfigError::CommitUrl)?;
+            collection_url(&commit_url, collection).map_err(ConfigError::CommitUrl)?;
+        }
+
+        Ok(self.config)
+    }
+}
+
+/// true if the string parses as an http or https URL with a host
+fn is_http_url(url: &str) -> bool {
+    Url::parse(url)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host_str().is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CommitUrlError;
+
+    #[test]
+    fn default_configuration_builds() {
+        assert!(PostConfig::builder().build().is_ok());
+    }
+
+    #[test]
+    fn first_invalid_value_is_returned() {
+        let error = PostConfig::builder()
+            .concurrency(0)
+            .host(" ")
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(error, ConfigError::ZeroConcurrency);
+    }
+
+    #[test]
+    fn split_size_is_a_sane_request_size() {
+        let builder = |split_size| PostConfig::builder().split_large_files(split_size);
+        assert_eq!(
+            builder(MAX_SPLIT_SIZE + 1).build().err().unwrap(),
+            ConfigError::SplitSizeTooLarge
+        );
+        assert!(builder(MAX_SPLIT_SIZE).build().is_ok());
+    }
+
+    #[test]
+    fn incremental_needs_a_state_file() {
+        let builder = || PostConfig::builder().incremental(true);
+        assert_eq!(
+            builder().build().err().unwrap(),
+            ConfigError::IncrementalWithoutStateFile
+        );
+        assert!(builder().state_file("state.json").build().is_ok());
+    }
+
+    #[test]
+    fn resume_needs_a_journal() {
+        assert_eq!(
+            PostConfig::builder().resume(true).build().err().unwrap(),
+            ConfigError::ResumeWithoutJournal
+        );
+    }
+
+    #[test]
+    fn verify_needs_a_hard_or_soft_commit() {
+        let builder = |commit| PostConfig::builder().verify(true).commit(commit);
+        assert_eq!(
+            builder(CommitPolicy::None).build().err().unwrap(),
+            ConfigError::VerifyWithoutCommit
+        );
+        assert_eq!(
+            builder(CommitPolicy::Within(1000)).build().err().unwrap(),
+            ConfigError::VerifyWithoutCommit
+        );
+        assert!(builder(CommitPolicy::Soft).build().is_ok());
+    }
+
+    #[test]
+    fn strip_path_needs_a_base_url() {
+        let builder = || PostConfig::builder().strip_path("./build");
+        assert_eq!(
+            builder().build().err().unwrap(),
+            ConfigError::StripPathWithoutBaseUrl
+        );
+        assert!(builder().base_url("https://example.com").build().is_ok());
+    }
+
+    #[test]
+    fn file_list_can_not_be_combined_with_a_report() {
+        assert_eq!(
+            PostConfig::builder()
+                .file_list("changed.txt")
+                .from_report("failed.jsonl")
+                .build()
+                .err()
+                .unwrap(),
+            ConfigError::FileListWithFromReport
+        );
+    }
+
+    #[test]
+    fn client_key_needs_a_client_cert() {
+        assert_eq!(
+            PostConfig::builder()
+                .client_key("client.key")
+                .build()
+                .err()
+                .unwrap(),
+            ConfigError::ClientKeyWithoutCert
+        );
+    }
+
+    #[test]
+    fn route_from_path_can_not_be_batched() {
+        assert_eq!(
+            PostConfig::builder()
+                .route(DocumentRoute::PathComponent(0))
+                .batch_size(100)
+                .build()
+                .err()
+                .unwrap(),
+            ConfigError::RouteFromPathWithBatching
+        );
+        assert!(PostConfig::builder()
+            .route(DocumentRoute::Fixed("tenant_a".to_string()))
+            .batch_size(100)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn modes_can_not_be_batched() {
+        assert_eq!(
+            PostConfig::builder()
+                .mode(PostMode::JsonDocs)
+                .batch_size(100)
+                .build()
+                .err()
+                .unwrap(),
+            ConfigError::ModeWithBatching(PostMode::JsonDocs)
+        );
+    }
+
+    #[test]
+    fn csv_separator_is_not_the_encapsulator() {
+        assert_eq!(
+            PostConfig::builder()
+                .csv_separator('"')
+                .csv_encapsulator('"')
+                .build()
+                .err()
+                .unwrap(),
+            ConfigError::CsvSeparatorIsEncapsulator
+        );
+    }
+
+    #[test]
+    fn csv_without_header_needs_field_names() {
+        let builder = || PostConfig::builder().csv_header(false);
+        assert_eq!(
+            builder().build().err().unwrap(),
+            ConfigError::CsvWithoutFieldNames
+        );
+        assert!(builder().csv_fieldname("id").build().is_ok());
+    }
+
+    #[test]
+    fn extract_only_leaves_the_index_as_is() {
+        assert_eq!(
+            PostConfig::builder()
+                .extract_only(true)
+                .sync(true)
+                .build()
+                .err()
+                .unwrap(),
+            ConfigError::ExtractOnlyWith("sync")
+        );
+        assert!(PostConfig::builder().extract_only(true).build().is_ok());
+    }
+
+    #[test]
+    fn commit_url_must_be_inferable() {
+        let update_url = "http://localhost:8983/indexer/post";
+        assert_eq!(
+            PostConfig::builder()
+                .update_url(update_url)
+                .build()
+                .err()
+                .unwrap(),
+            ConfigError::CommitUrl(CommitUrlError::CannotInfer(update_url.to_string()))
+        );
+        assert!(PostConfig::builder()
+            .update_url(update_url)
+            .commit_url("http://localhost:8983/indexer/commit")
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn extra_collections_need_a_collection_path() {
+        let error = PostConfig::builder()
+            .update_url("http://localhost:8983/indexer/update")
+            .extra_collections(["other"])
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(
+            error,
+            ConfigError::CommitUrl(CommitUrlError::NoCollection(_))
+        ));
+    }
+}
@@ -0,0 +1,128 @@
+use std::path::Path;
+use std::sync::LazyLock;
+
+use chardetng::EncodingDetector;
+use mime_guess::from_path;
+use regex::bytes::Regex;
+
+use crate::html_meta::is_html;
+use crate::SolrPostError;
+
+/// the bytes an HTML charset declaration must be in, like the prescan of browsers
+const HTML_PRESCAN_SIZE: usize = 1024;
+
+/// a charset declared by a <meta charset="..."> or <meta http-equiv="Content-Type" content="...; charset=...">
+static HTML_CHARSET: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<meta\b[^>]*charset\s*=").unwrap());
+
+/// true for the text files e.g. "notes.txt", "index.html", or "post.md"
+pub(crate) fn is_text(path: &Path) -> bool {
+    from_path(path)
+        .first()
+        .is_some_and(|mime| mime.type_() == mime_guess::mime::TEXT)
+}
+
+/// read the text file transcoded to UTF-8 from the encoding detected for it, see PostConfig::transcode.
+/// None when it is already UTF-8, or an HTML page declaring its charset which Tika reads itself
+pub(crate) async fn read_transcoded(path: &Path) -> Result<Option<Vec<u8>>, SolrPostError> {
+    let content = tokio::fs::read(path)
+        .await
+        .map_err(|source| SolrPostError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+    if std::str::from_utf8(&content).is_ok() || (is_html(path) && declares_charset(&content)) {
+        return Ok(None);
+    }
+
+    Ok(Some(to_utf8(&content).into_bytes()))
+}
+
+/// the content decoded from the encoding detected for it e.g. windows-1252 or Shift_JIS, a byte order
+/// mark takes precedence. the bytes that aren't valid in the encoding become U+FFFD
+pub(crate) fn to_utf8(content: &[u8]) -> String {
+    let mut detector = EncodingDetector::new();
+    detector.feed(content, true);
+    let encoding = detector.guess(None, true);
+
+    let (decoded, _, _) = encoding.decode(content);
+    decoded.into_owned()
+}
+
+fn declares_charset(content: &[u8]) -> bool {
+    HTML_CHARSET.is_match(&content[..content.len().min(HTML_PRESCAN_SIZE)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the text in ISO-8859-1, every character of it is below U+0100
+    fn latin1(text: &str) -> Vec<u8> {
+        text.chars().map(|c| c as u8).collect()
+    }
+
+    const TEXT: &str = "Le café du coin sert une crème brûlée à déjà cinq clients.\n";
+
+    #[test]
+    fn latin1_is_transcoded() {
+        assert_eq!(to_utf8(&latin1(TEXT)), TEXT);
+    }
+
+    #[test]
+    fn a_byte_order_mark_takes_precedence() {
+        let mut utf16 = vec![0xff, 0xfe];
+        utf16.extend("café".encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(to_utf8(&utf16), "café");
+    }
+
+    #[test]
+    fn html_charset_declarations() {
+        assert!(declares_charset(
+            b"<html><head><META charset=\"iso-8859-1\">"
+        ));
+        assert!(declares_charset(
+            b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=windows-1252\">"
+        ));
+        assert!(!declares_charset(
+            b"<meta name=\"description\" content=\"charset\">"
+        ));
+
+        // only the start of the page is looked at
+        let mut late = vec![b' '; HTML_PRESCAN_SIZE];
+        late.extend_from_slice(b"<meta charset=\"utf-8\">");
+        assert!(!declares_charset(&late));
+    }
+
+    #[tokio::test]
+    async fn only_the_files_that_need_it_are_transcoded() {
+        let directory = std::env::temp_dir().join(format!("solr_post_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let write = |name: &str, content: &[u8]| {
+            let path = directory.join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        };
+
+        let text = write("notes.txt", &latin1(TEXT));
+        let utf8 = write("utf8.txt", TEXT.as_bytes());
+        let mut page = b"<meta charset=\"iso-8859-1\">".to_vec();
+        page.extend(latin1(TEXT));
+        let html = write("page.html", &page);
+
+        let transcoded = read_transcoded(&text).await;
+        let not_transcoded = (read_transcoded(&utf8).await, read_transcoded(&html).await);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(transcoded.unwrap(), Some(TEXT.as_bytes().to_vec()));
+        assert!(matches!(not_transcoded, (Ok(None), Ok(None))));
+    }
+
+    #[test]
+    fn text_files() {
+        assert!(is_text(Path::new("notes.txt")));
+        assert!(is_text(Path::new("index.html")));
+        assert!(!is_text(Path::new("report.pdf")));
+    }
+}
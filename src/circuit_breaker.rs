@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+/// What to do after recording the outcome of a request
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum BreakerAction {
+    /// keep posting
+    Continue,
+
+    /// the breaker tripped, pause posting for this long before trying the server again
+    Pause(Duration),
+
+    /// the breaker tripped and there are no retries left, abort the run
+    Abort,
+}
+
+/// Trips after `threshold` consecutive server errors (5xx or connection errors) so a dead Solr
+/// doesn't produce thousands of identical failures. Each trip pauses posting for `wait` and then
+/// tries again, after `retries` trips the run is aborted
+pub(crate) struct CircuitBreaker {
+    threshold: usize,
+    wait: Duration,
+    retries: usize,
+
+    /// server errors in a row since the last success or pause
+    consecutive_failures: usize,
+
+    /// the number of times the breaker has tripped since the last success
+    trips: usize,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(threshold: usize, wait: Duration, retries: usize) -> Self {
+        CircuitBreaker {
+            threshold,
+            wait,
+            retries,
+            consecutive_failures: 0,
+            trips: 0,
+        }
+    }
+
+    /// a request reached a healthy server, close the breaker
+    pub(crate) fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+        self.trips = 0;
+    }
+
+    /// a request failed with a server error, trips the breaker once the threshold is reached
+    pub(crate) fn record_failure(&mut self) -> BreakerAction {
+        self.consecutive_failures += 1;
+
+        if self.consecutive_failures < self.threshold {
+            return BreakerAction::Continue;
+        }
+
+        if self.trips >= self.retries {
+            return BreakerAction::Abort;
+        }
+
+        // after the pause the server gets another threshold worth of requests to recover
+        self.trips += 1;
+        self.consecutive_failures = 0;
+        BreakerAction::Pause(self.wait)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAIT: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn pauses_after_threshold_consecutive_failures() {
+        let mut breaker = CircuitBreaker::new(3, WAIT, 1);
+        assert_eq!(breaker.record_failure(), BreakerAction::Continue);
+        assert_eq!(breaker.record_failure(), BreakerAction::Continue);
+        assert_eq!(breaker.record_failure(), BreakerAction::Pause(WAIT));
+    }
+
+    #[test]
+    fn aborts_when_out_of_retries() {
+        let mut breaker = CircuitBreaker::new(2, WAIT, 1);
+        breaker.record_failure();
+        assert_eq!(breaker.record_failure(), BreakerAction::Pause(WAIT));
+
+        // the server gets another threshold worth of requests after the pause
+        assert_eq!(breaker.record_failure(), BreakerAction::Continue);
+        assert_eq!(breaker.record_failure(), BreakerAction::Abort);
+    }
+
+    #[test]
+    fn aborts_without_pausing_without_retries() {
+        let mut breaker = CircuitBreaker::new(1, WAIT, 0);
+        assert_eq!(breaker.record_failure(), BreakerAction::Abort);
+    }
+
+    #[test]
+    fn a_success_closes_the_breaker() {
+        let mut breaker = CircuitBreaker::new(2, WAIT, 1);
+        breaker.record_failure();
+        breaker.record_success();
+        assert_eq!(breaker.record_failure(), BreakerAction::Continue);
+        assert_eq!(breaker.record_failure(), BreakerAction::Pause(WAIT));
+
+        // the retries start over too
+        breaker.record_success();
+        breaker.record_failure();
+        assert_eq!(breaker.record_failure(), BreakerAction::Pause(WAIT));
+    }
+}
@@ -1,15 +1,70 @@
 use argh::FromArgs;
+use console::Term;
+use indicatif::{HumanBytes, ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
+use log::LevelFilter;
 use regex::Regex;
-use solr_post::{solr_post, PostConfig};
-use std::io::{self, Write};
-use std::sync::{Mutex, OnceLock};
+use reqwest::header::{HeaderName, HeaderValue};
+use serde_json::{json, Value};
+#[cfg(feature = "watch")]
+use solr_post::solr_watch;
+#[cfg(feature = "markdown")]
+use solr_post::MarkdownBody;
+use solr_post::{
+    new_run_id, solr_delete_by_query, solr_dry_run, solr_list_files, solr_post,
+    solr_post_with_events, AuthScheme, CommitPolicy, ConfigError, DocumentRoute, FileResult,
+    PostConfig, PostConfigBuilder, PostEvent, PostMode, PostSummary, RedirectPolicy, SolrPostError,
+    DEFAULT_SIDECAR_SUFFIX,
+};
+use std::collections::HashSet;
+use std::fs::{File, TryLockError};
+use std::io::{self, IsTerminal, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// nothing was indexed, e.g. Solr is unreachable, the commit failed, every file failed, or the run was rolled back
+const EXIT_FAILURE: i32 = 1;
+
+/// some of the files failed to index, the rest were indexed and committed
+const EXIT_PARTIAL_FAILURE: i32 = 2;
+
+/// the run was cancelled with ctrl-c, like a shell reports a process killed by SIGINT
+const EXIT_CANCELLED: i32 = 130;
+
+/// How the CLI prints the progress of a run
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// progress and a summary for humans
+    Text,
+
+    /// one JSON object per line for each event of the run
+    Json,
+}
+
+/// The options of each run that aren't part of the PostConfig
+struct RunOptions {
+    concurrency: usize,
+    index_run_id: bool,
+    lock_file: Option<PathBuf>,
+    output: Output,
+
+    /// show a progress bar instead of only printing the start and the end of the run
+    progress: bool,
+
+    /// print nothing but errors
+    quiet: bool,
+}
 
 #[derive(FromArgs)]
-/// Post files to a solr collection
+/// Post files to a solr collection, see `solr-post delete --help` to delete documents and
+/// `solr-post list --help` to list the files that would be posted
 struct SolrPostArgs {
-    /// the solr collection to post to
+    /// the solr collection to post to, repeat to post the same files to more collections
     #[argh(option, short = 'c')]
-    collection: String,
+    collection: Vec<String>,
 
     /// the host of the solr server defaults to localhost
     #[argh(option, short = 'h', default = "String::from(\"localhost\")")]
@@ -21,18 +76,51 @@ struct SolrPostArgs {
 
     /// base Solr update URL
     /// e.g. http://localhost:8983/solr/my_collection/update
-    /// if this is set, the collection, host, and port are ignored
+    /// if this is set, the collection, host, and port are ignored.
+    /// repeat it to spread the posts across several Solr nodes
     #[argh(option)]
-    url: Option<String>,
+    url: Vec<String>,
+
+    /// explicit Solr commit URL
+    /// e.g. http://localhost:8983/solr/my_collection/update?commit=true
+    /// required when it can not be inferred from --url
+    #[argh(option)]
+    commit_url: Option<String>,
 
     /// basic auth user credentials
     /// e.g. "username:password"
     #[argh(option, short = 'u')]
     user: Option<String>,
 
-    /// the directory to search for files to post
+    /// authenticate with this bearer token e.g. a JWT for Solr's JWT authentication
+    /// plugin or an API gateway, instead of -u
+    #[argh(option)]
+    bearer_token: Option<String>,
+
+    /// authenticate with Kerberos, a SPNEGO token for each request from the ticket
+    /// cache of kinit
+    #[cfg(feature = "kerberos")]
+    #[argh(switch)]
+    negotiate: bool,
+
+    /// basic auth with the login and password of the Solr host in ~/.netrc
+    #[argh(switch)]
+    netrc: bool,
+
+    /// basic auth with the login and password of the Solr host in this .netrc file
+    #[argh(option)]
+    netrc_file: Option<PathBuf>,
+
+    /// basic auth as this user with the password stored in the OS keyring for the
+    /// Solr host
+    #[cfg(feature = "keyring")]
+    #[argh(option)]
+    keyring: Option<String>,
+
+    /// the directory to search for files to post, repeat to post several
+    /// directories in one run with one commit
     #[argh(option, short = 'd')]
-    directory: String,
+    directory: Vec<String>,
 
     /// the file extensions to post defaults to xml,json,jsonl,csv,pdf,doc,docx,ppt,pptx,xls,xlsx,odt,odp,ods,ott,otp,ots,rtf,htm,html,txt,log
     /// e.g. "html,txt,json"
@@ -43,6 +131,34 @@ struct SolrPostArgs {
     )]
     file_extensions: String,
 
+    /// post the files matched by this glob below the directory instead of the
+    /// ones with the file extensions e.g. "content/**/{posts,pages}/*.html", can
+    /// be repeated
+    #[argh(option, from_str_fn(parse_glob))]
+    glob: Vec<String>,
+
+    /// skip the files matched by this glob below the directory without reading
+    /// them, a matched directory isn't walked e.g. "**/node_modules/**", can be
+    /// repeated
+    #[argh(option, from_str_fn(parse_glob))]
+    exclude_glob: Vec<String>,
+
+    /// skip the files whose path below the directory matches this regex
+    /// without reading them e.g. "-draft\.html$" or "^archive/". this is case
+    /// sensitive and takes precedence over include_path_regex
+    #[argh(option, from_str_fn(parse_path_regex))]
+    exclude_path_regex: Option<Regex>,
+
+    /// include only the files whose path below the directory matches this
+    /// regex e.g. "^(docs|blog)/". this is case sensitive
+    #[argh(option, from_str_fn(parse_path_regex))]
+    include_path_regex: Option<Regex>,
+
+    /// skip the files ignored by the .gitignore files, and by .solrignore files
+    /// with the same syntax, of their directories and the ones above them
+    #[argh(switch)]
+    respect_ignore_files: bool,
+
     /// concurrency level defauls to 8
     /// the number of concurrent requests to make to the solr server
     #[argh(option, default = "8")]
@@ -63,88 +179,2284 @@ struct SolrPostArgs {
     /// if both exclude_regex and include_regex are set, exclude_regex will takes precedence.
     #[argh(option, short = 'i')]
     include_regex: Option<String>,
+
+    /// tag every document with a unique id for this run
+    /// so everything indexed by one run can later be queried or deleted
+    #[argh(switch)]
+    index_run_id: bool,
+
+    /// the field name the run id is indexed into defaults to run_id
+    #[argh(option, default = "String::from(\"run_id\")")]
+    run_id_field: String,
+
+    /// add the field with the value to every document e.g. --literal site=docs, can
+    /// be repeated
+    #[argh(option, from_str_fn(parse_name_value))]
+    literal: Vec<(String, String)>,
+
+    /// add the param to every update request like the -params of bin/post e.g.
+    /// --param uprefix=attr_ or --param fmap.content=text, can be repeated
+    #[argh(option, from_str_fn(parse_name_value))]
+    param: Vec<(String, String)>,
+
+    /// print the content and metadata Tika extracts from each file instead of
+    /// indexing it, the index is not changed
+    #[argh(switch)]
+    extract_only: bool,
+
+    /// write the extracted content and metadata of each file to
+    /// <dir>/<path>.json instead of printing it, implies --extract-only
+    #[argh(option)]
+    extract_output: Option<PathBuf>,
+
+    /// extract this XHTML element e.g. div into a separate field named after it,
+    /// map it with --param fmap.div=content, can be repeated
+    #[argh(option)]
+    capture: Vec<String>,
+
+    /// index the attributes of the XHTML elements into separate fields e.g. the
+    /// href of links
+    #[argh(switch)]
+    capture_attr: bool,
+
+    /// only index the XHTML content matching this XPath expression e.g.
+    /// "/xhtml:html/xhtml:body/xhtml:div//node()"
+    #[argh(option)]
+    xpath: Option<String>,
+
+    /// commit after this many documents have been posted since the last commit.
+    /// if --commit-interval is also set, whichever is reached first triggers the commit
+    /// and both are reset after each commit
+    #[argh(option)]
+    commit_every: Option<usize>,
+
+    /// commit when this many seconds have passed since the last commit.
+    /// if --commit-every is also set, whichever is reached first triggers the commit
+    /// and both are reset after each commit
+    #[argh(option)]
+    commit_interval: Option<u64>,
+
+    /// memory map files for the exclude/include regex scan instead of reading them into memory.
+    /// files must not be modified during the scan
+    #[cfg(feature = "mmap")]
+    #[argh(switch)]
+    mmap_scan: bool,
+
+    /// send each document's _version_ for optimistic concurrency, read from a
+    /// sidecar file e.g. "page.html.version" or the _version_ field of a JSON document.
+    /// stale updates are rejected by Solr and reported as conflicts
+    #[argh(switch)]
+    optimistic_concurrency: bool,
+
+    /// cap the total size in bytes of the files being posted at the same time
+    /// e.g. 536870912. a file larger than this is posted on its own
+    #[argh(option)]
+    max_inflight_bytes: Option<u64>,
+
+    /// gzip compress the files as they are posted, sent with Content-Encoding: gzip
+    #[argh(switch)]
+    compress: bool,
+
+    /// seconds to wait while connecting to the solr server before giving up
+    #[argh(option)]
+    connect_timeout: Option<u64>,
+
+    /// seconds to wait for each request to complete before giving up on the file
+    #[argh(option)]
+    timeout: Option<u64>,
+
+    /// seconds the whole run may take before it is aborted without committing
+    #[argh(option)]
+    deadline: Option<u64>,
+
+    /// abort after this many consecutive server errors (5xx or connection errors)
+    /// instead of failing through the whole corpus
+    #[argh(option)]
+    circuit_breaker: Option<usize>,
+
+    /// seconds to pause when the circuit breaker trips before trying the server again defaults to 30
+    #[argh(option, default = "30")]
+    circuit_breaker_wait: u64,
+
+    /// how many times the circuit breaker may pause before aborting defaults to 0
+    #[argh(option, default = "0")]
+    circuit_breaker_retries: usize,
+
+    /// send at most this many requests per second e.g. 50 or 0.5, not limited by default
+    #[argh(option)]
+    max_requests_per_second: Option<f64>,
+
+    /// start at a low concurrency and ramp up or down based on response times and server errors,
+    /// --concurrency is the upper limit
+    #[argh(switch)]
+    auto_concurrency: bool,
+
+    /// post json, jsonl, xml, and csv files to the /update handler in batches of this many files,
+    /// the documents must contain their own ids
+    #[argh(option)]
+    batch_size: Option<usize>,
+
+    /// start another request for a batch once its body would grow past this many bytes,
+    /// a larger file is still posted in one request
+    #[argh(option)]
+    batch_max_bytes: Option<usize>,
+
+    /// post json, jsonl, xml, and csv files to the /update handler as Solr documents
+    /// instead of extracting them with Tika, one request per file
+    #[argh(switch)]
+    structured_update: bool,
+
+    /// how the files are posted: extract (default) through Tika, json-docs to post
+    /// .json and .jsonl files as they are to /update/json/docs, xml to post .xml
+    /// files with Solr XML update commands as they are to /update, or csv to post
+    /// .csv and .tsv files as they are to /update
+    #[argh(option, default = "PostMode::Extract", from_str_fn(parse_mode))]
+    mode: PostMode,
+
+    /// split the JSON into documents at this path in the json-docs mode e.g. /exams
+    #[argh(option)]
+    split: Option<String>,
+
+    /// take this field from the JSON in the json-docs mode e.g. title:/book/title,
+    /// can be repeated
+    #[argh(option)]
+    json_field: Vec<String>,
+
+    /// the character separating the values in the csv mode, defaults to a tab for
+    /// .tsv files and , for the others. also accepts "tab" or "\t"
+    #[argh(option, from_str_fn(parse_separator))]
+    csv_separator: Option<char>,
+
+    /// the first line of the CSV files is not a header, requires --csv-fieldnames
+    #[argh(switch)]
+    csv_no_header: bool,
+
+    /// the comma separated field names of the CSV columns in the csv mode, an empty
+    /// name skips the column e.g. "id,,title"
+    #[argh(option)]
+    csv_fieldnames: Option<String>,
+
+    /// skip this many lines at the start of the CSV files before the header
+    #[argh(option)]
+    csv_skip_lines: Option<usize>,
+
+    /// the character quoting the CSV values holding the separator e.g. "'"
+    #[argh(option)]
+    csv_encapsulator: Option<char>,
+
+    /// post .jsonl and .ndjson files in chunks of this many documents, each chunk
+    /// a JSON array, instead of sending the whole file in one request e.g. 10000
+    #[argh(option)]
+    jsonl_chunk_size: Option<usize>,
+
+    /// post .txt and .log files larger than this many bytes as several documents of
+    /// at most this many bytes, cut at line ends, with the ids <path>#part<n> and the
+    /// part number in the part field e.g. 10485760
+    #[argh(option)]
+    split_large_files: Option<u64>,
+
+    /// index the YAML or TOML front matter of .md and .markdown files as literal
+    /// fields e.g. title and tags, and post the Markdown after it
+    #[cfg(feature = "markdown")]
+    #[argh(switch)]
+    markdown: bool,
+
+    /// like --markdown, with the Markdown rendered to HTML before it is posted
+    #[cfg(feature = "markdown")]
+    #[argh(switch)]
+    markdown_html: bool,
+
+    /// send the title, meta description, canonical URL, og: properties and lang
+    /// of the HTML files as literal fields, parsed from their head
+    #[argh(switch)]
+    html_metadata: bool,
+
+    /// add the key/value pairs of each document's <file>.meta.json sidecar file as
+    /// literal fields, the sidecar files aren't posted themselves
+    #[argh(switch)]
+    sidecar_meta: bool,
+
+    /// the suffix of the sidecar files instead of .meta.json, implies --sidecar-meta
+    /// e.g. .fields.json
+    #[argh(option)]
+    sidecar_suffix: Option<String>,
+
+    /// prepend this to the id of every document e.g. "docs:", so several sources
+    /// can share a collection. --clean, --sync and --verify only touch the
+    /// documents with the prefix
+    #[argh(option)]
+    id_prefix: Option<String>,
+
+    /// use the URLs of the files under this site as their ids and index them in
+    /// the url field e.g. https://example.com/ for ./en/page.html
+    #[argh(option, from_str_fn(parse_base_url))]
+    base_url: Option<String>,
+
+    /// the directory removed from the paths of the files before they are appended
+    /// to --base-url, defaults to the directory e.g. ./build
+    #[argh(option)]
+    strip_path: Option<PathBuf>,
+
+    /// a regex matched against the path of each file below the directory, its
+    /// named groups are sent as fields e.g. "^docs/(?P<lang>[a-z]{2})/" for
+    /// lang=en, can be repeated
+    #[argh(option, from_str_fn(parse_path_field))]
+    path_field: Vec<Regex>,
+
+    /// the comma separated field names of the directories of each file by depth
+    /// below the directory, an empty name skips one e.g. "category,subcategory"
+    #[argh(option)]
+    directory_fields: Option<String>,
+
+    /// send the modification and creation times of each file as the last_modified
+    /// and created date fields
+    #[argh(switch)]
+    file_dates: bool,
+
+    /// send the size of each file in bytes as the file_size_bytes field
+    #[argh(switch)]
+    file_size: bool,
+
+    /// send the sha256 of the contents of each file as the content_hash field
+    #[argh(switch)]
+    content_hash: bool,
+
+    /// detect the language of the text, HTML and Markdown files and send its ISO
+    /// 639-3 code e.g. "eng" as the language field
+    #[cfg(feature = "language")]
+    #[argh(switch)]
+    detect_language: bool,
+
+    /// post the files with the extension with this Content-Type instead of the
+    /// guessed one e.g. --mime log=text/plain, can be repeated
+    #[argh(option, from_str_fn(parse_mime_override))]
+    mime: Vec<(String, String)>,
+
+    /// detect the encoding of the text files that aren't UTF-8 e.g. Windows-1252
+    /// and post them transcoded to UTF-8
+    #[argh(switch)]
+    transcode: bool,
+
+    /// don't commit, for collections that rely on autoCommit or forbid client commits
+    #[argh(switch)]
+    no_commit: bool,
+
+    /// soft commit instead of hard commit
+    #[argh(switch)]
+    soft_commit: bool,
+
+    /// don't commit, ask Solr to commit each document within this many milliseconds instead
+    #[argh(option)]
+    commit_within: Option<u64>,
+
+    /// optimize the collection once posting is complete
+    #[argh(switch)]
+    optimize: bool,
+
+    /// merge down to at most this many segments when optimizing
+    #[argh(option)]
+    max_segments: Option<u32>,
+
+    /// once this many files have failed, stop posting and roll back the uncommitted
+    /// documents instead of committing them
+    #[argh(option)]
+    rollback_on_failures: Option<usize>,
+
+    /// after indexing, delete the documents under the directory whose file no longer exists
+    #[argh(switch)]
+    sync: bool,
+
+    /// delete every document in the collection, or with the --id-prefix, before
+    /// posting, asks for confirmation unless --yes is set
+    #[argh(switch)]
+    clean: bool,
+
+    /// don't ask for confirmation before --clean deletes the documents
+    #[argh(switch)]
+    yes: bool,
+
+    /// record the mtime and content hash of the indexed files in this JSON file
+    /// defaults to .solr-post-state.json when --incremental is set
+    #[argh(option)]
+    state_file: Option<PathBuf>,
+
+    /// skip the files that haven't changed since the last run recorded in the state file
+    #[argh(switch)]
+    incremental: bool,
+
+    /// append each indexed file to this journal so an interrupted run can be resumed,
+    /// removed once the run completes. defaults to .solr-post-journal when --resume is set
+    #[argh(option)]
+    journal: Option<PathBuf>,
+
+    /// skip the files the interrupted run recorded in the journal
+    #[argh(switch)]
+    resume: bool,
+
+    /// write the failed files with their errors to this JSON lines file e.g. failed.jsonl
+    #[argh(option)]
+    error_report: Option<PathBuf>,
+
+    /// post only the files listed in an --error-report of a previous run instead of
+    /// scanning the directory
+    #[argh(option)]
+    from_report: Option<PathBuf>,
+
+    /// post exactly the files listed in this file, one path per line, instead of
+    /// scanning the directory e.g. the files changed by a publish. relative paths
+    /// are below the directory
+    #[argh(option)]
+    filelist: Option<PathBuf>,
+
+    /// abort without committing once this many files have failed, e.g. when the schema
+    /// is wrong or the collection name has a typo
+    #[argh(option)]
+    max_errors: Option<usize>,
+
+    /// push the metrics of the run to this Prometheus Pushgateway when it finishes,
+    /// e.g. http://pushgateway:9091
+    #[cfg(feature = "metrics")]
+    #[argh(option)]
+    metrics_push_url: Option<String>,
+
+    /// serve the metrics of the run for Prometheus to scrape on this address while
+    /// the run lasts, e.g. 0.0.0.0:9898
+    #[cfg(feature = "metrics")]
+    #[argh(option)]
+    metrics_listen: Option<SocketAddr>,
+
+    /// send StatsD/DogStatsD counters and timers for every document to this
+    /// host:port, e.g. localhost:8125
+    #[cfg(feature = "statsd")]
+    #[argh(option)]
+    statsd: Option<String>,
+
+    /// don't check the collection's admin/ping handler before posting, for Solr
+    /// setups without a ping handler
+    #[argh(switch)]
+    no_ping: bool,
+
+    /// poll the ping handler with backoff until Solr is ready before posting, giving
+    /// up after this long e.g. "120s", for Solr containers started with the indexer
+    #[argh(option, from_str_fn(parse_duration))]
+    wait_for_solr: Option<Duration>,
+
+    /// after the commit, count the documents in Solr and fail if there are fewer than
+    /// the indexed files. counts only this run with --index-run-id
+    #[argh(switch)]
+    verify: bool,
+
+    /// the ZooKeeper ensemble of a SolrCloud cluster e.g. "zk1:2181,zk2:2181/solr",
+    /// posts are spread across the live nodes hosting the collection instead of -h/-p
+    #[argh(option)]
+    zk_hosts: Option<String>,
+
+    /// the _route_ of every document, for collections using the implicit router
+    #[argh(option)]
+    route: Option<String>,
+
+    /// route each document to the shard named by the directory at this depth below
+    /// -d, 0 is the first directory. can't be combined with --batch-size
+    #[argh(option)]
+    route_from_path: Option<usize>,
+
+    /// use the v2 API paths /api/collections/<collection>/... instead of /solr/<collection>/...
+    /// for gateways that only expose the v2 API
+    #[argh(switch)]
+    v2_api: bool,
+
+    /// a PEM file with the certificates of the CA that signed Solr's certificate,
+    /// trusted next to the system's root certificates
+    #[argh(option)]
+    cacert: Option<PathBuf>,
+
+    /// DANGEROUS: don't verify the TLS certificate of Solr, only for dev clusters with
+    /// self-signed certificates
+    #[argh(switch)]
+    insecure: bool,
+
+    /// the client certificate for mutual TLS, a PEM certificate or a PKCS#12 archive
+    /// (.p12 or .pfx) holding the certificate and the key
+    #[argh(option)]
+    cert: Option<PathBuf>,
+
+    /// the PKCS#8 PEM private key of a PEM --cert, defaults to the --cert file
+    #[argh(option)]
+    key: Option<PathBuf>,
+
+    /// the password of a PKCS#12 --cert
+    #[argh(option)]
+    cert_password: Option<String>,
+
+    /// a header sent with every request e.g. --header "X-Api-Key: abc", can be
+    /// repeated
+    #[argh(option, from_str_fn(parse_header))]
+    header: Vec<(String, String)>,
+
+    /// send the requests through this proxy e.g. http://proxy.corp:3128, instead
+    /// of the HTTP_PROXY or HTTPS_PROXY of the environment
+    #[argh(option, from_str_fn(parse_proxy))]
+    proxy: Option<String>,
+
+    /// follow up to this many redirects, 0 fails a redirected request instead,
+    /// default 10
+    #[argh(option)]
+    max_redirects: Option<usize>,
+
+    /// connect to this address for the host and port like curl --resolve e.g.
+    /// --resolve solr-0.internal:8983:10.0.0.5, can be repeated
+    #[argh(option, from_str_fn(parse_resolve))]
+    resolve: Vec<(String, SocketAddr)>,
+
+    /// connect to Solr from this local IP address e.g. the address on the storage
+    /// network of a multi-homed host
+    #[argh(option)]
+    local_address: Option<IpAddr>,
+
+    /// post a login form to this URL before the first request and reuse the
+    /// session cookie it sets e.g. for Solr behind an SSO proxy
+    #[argh(option, from_str_fn(parse_login_url))]
+    login_url: Option<String>,
+
+    /// a field of the --login-url form e.g. --login-field username=indexer, can be
+    /// repeated
+    #[argh(option, from_str_fn(parse_name_value))]
+    login_field: Vec<(String, String)>,
+
+    /// print the progress as text, or as json with one JSON object per line for each
+    /// event of the run, defaults to text
+    #[argh(option, default = "Output::Text", from_str_fn(parse_output))]
+    output: Output,
+
+    /// show a progress bar even when stderr is not a terminal
+    #[argh(switch)]
+    progress: bool,
+
+    /// only print errors
+    #[argh(switch, short = 'q')]
+    quiet: bool,
+
+    /// log what the library is doing, -v for each indexed file, -vv for debug and -vvv
+    /// for trace output including the HTTP client. RUST_LOG overrides this
+    #[argh(switch, short = 'v')]
+    verbose: u8,
+
+    /// scan and filter the files and print the requests that would be sent without
+    /// sending anything
+    #[argh(switch)]
+    dry_run: bool,
+
+    /// stay resident and post again on this schedule e.g. "15m" or "1h 30m", best combined
+    /// with --incremental. a run that takes longer than the interval skips the missed runs
+    #[argh(option, from_str_fn(parse_duration))]
+    interval: Option<Duration>,
+
+    /// skip the run when another solr-post holds the lock on this file, e.g. a cron job
+    /// started while the previous run is still posting
+    #[argh(option)]
+    lock_file: Option<PathBuf>,
+
+    /// after posting, keep watching the directory and post created or modified files
+    /// and delete the documents of removed files until ctrl-c
+    #[cfg(feature = "watch")]
+    #[argh(switch)]
+    watch: bool,
 }
 
-// implement into for SOlrPostArgs to convert it to PostConfig
-impl From<SolrPostArgs> for PostConfig {
-    fn from(val: SolrPostArgs) -> Self {
-        PostConfig {
-            collection: val.collection,
-            host: val.host,
-            port: val.port,
-            directory_path: val.directory.into(),
-            file_extensions: val
-                .file_extensions
-                .split(',')
-                .map(|s| s.to_string())
-                .collect(),
-            update_url: val.url,
-            concurrency: val.concurrency,
+#[derive(FromArgs)]
+/// Delete the documents matching a query from a solr collection
+struct DeleteArgs {
+    /// the solr collection to delete from
+    #[argh(option, short = 'c')]
+    collection: String,
+
+    /// the host of the solr server defaults to localhost
+    #[argh(option, short = 'h', default = "String::from(\"localhost\")")]
+    host: String,
 
-            // create regex objects from the exclude and include regex strings ignore case
-            exclued_regex: val
-                .exclude_regex
-                .map(|s| Regex::new(&format!("(?i){}", s)).unwrap()),
-            include_regex: val
-                .include_regex
-                .map(|s| Regex::new(&format!("(?i){}", s)).unwrap()),
+    /// the port of the solr server defaults to 8983
+    #[argh(option, short = 'p', default = "8983")]
+    port: u16,
 
-            basic_auth_creds: val.user,
-        }
-    }
+    /// base Solr update URL
+    /// e.g. http://localhost:8983/solr/my_collection/update
+    /// if this is set, the collection, host, and port are ignored
+    #[argh(option)]
+    url: Option<String>,
+
+    /// basic auth user credentials
+    /// e.g. -u "username:password"
+    #[argh(option, short = 'u')]
+    user: Option<String>,
+
+    /// authenticate with this bearer token instead of -u
+    #[argh(option)]
+    bearer_token: Option<String>,
+
+    /// authenticate with Kerberos, a SPNEGO token from the ticket cache of kinit
+    #[cfg(feature = "kerberos")]
+    #[argh(switch)]
+    negotiate: bool,
+
+    /// basic auth with the login and password of the Solr host in ~/.netrc
+    #[argh(switch)]
+    netrc: bool,
+
+    /// basic auth with the login and password of the Solr host in this .netrc file
+    #[argh(option)]
+    netrc_file: Option<PathBuf>,
+
+    /// basic auth as this user with the password in the OS keyring for the Solr host
+    #[cfg(feature = "keyring")]
+    #[argh(option)]
+    keyring: Option<String>,
+
+    /// the query matching the documents to delete e.g. -q "source:docs"
+    #[argh(option, short = 'q')]
+    query: String,
+
+    /// don't commit the delete
+    #[argh(switch)]
+    no_commit: bool,
+
+    /// soft commit instead of hard commit
+    #[argh(switch)]
+    soft_commit: bool,
+
+    /// use the v2 API paths /api/collections/<collection>/... instead of /solr/<collection>/...
+    #[argh(switch)]
+    v2_api: bool,
+
+    /// a PEM file with the certificates of the CA that signed Solr's certificate
+    #[argh(option)]
+    cacert: Option<PathBuf>,
+
+    /// DANGEROUS: don't verify the TLS certificate of Solr
+    #[argh(switch)]
+    insecure: bool,
+
+    /// the client certificate for mutual TLS, a PEM certificate or a PKCS#12 archive
+    #[argh(option)]
+    cert: Option<PathBuf>,
+
+    /// the PKCS#8 PEM private key of a PEM --cert, defaults to the --cert file
+    #[argh(option)]
+    key: Option<PathBuf>,
+
+    /// the password of a PKCS#12 --cert
+    #[argh(option)]
+    cert_password: Option<String>,
+
+    /// a header sent with every request e.g. --header "X-Api-Key: abc", can be
+    /// repeated
+    #[argh(option, from_str_fn(parse_header))]
+    header: Vec<(String, String)>,
+
+    /// send the requests through this proxy e.g. http://proxy.corp:3128, instead
+    /// of the HTTP_PROXY or HTTPS_PROXY of the environment
+    #[argh(option, from_str_fn(parse_proxy))]
+    proxy: Option<String>,
+
+    /// follow up to this many redirects, 0 fails a redirected request instead,
+    /// default 10
+    #[argh(option)]
+    max_redirects: Option<usize>,
+
+    /// connect to this address for the host and port like curl --resolve e.g.
+    /// --resolve solr-0.internal:8983:10.0.0.5, can be repeated
+    #[argh(option, from_str_fn(parse_resolve))]
+    resolve: Vec<(String, SocketAddr)>,
+
+    /// connect to Solr from this local IP address e.g. the address on the storage
+    /// network of a multi-homed host
+    #[argh(option)]
+    local_address: Option<IpAddr>,
+
+    /// post a login form to this URL before the first request and reuse the
+    /// session cookie it sets e.g. for Solr behind an SSO proxy
+    #[argh(option, from_str_fn(parse_login_url))]
+    login_url: Option<String>,
+
+    /// a field of the --login-url form e.g. --login-field username=indexer, can be
+    /// repeated
+    #[argh(option, from_str_fn(parse_name_value))]
+    login_field: Vec<(String, String)>,
 }
 
-#[tokio::main]
-async fn main() {
-    let args: SolrPostArgs = argh::from_env();
+#[derive(FromArgs)]
+/// List the files that would be posted after the extension and regex filters, one path per line
+struct ListArgs {
+    /// the directory to search for files, can be repeated
+    #[argh(option, short = 'd')]
+    directory: Vec<String>,
 
-    // make sure that total_files_to_index lives for the entire duration of the program
-    // Make total_files_to_index 'static' to ensure it lives for the entire program duration
-    static TOTAL_FILES_TO_INDEX: OnceLock<Mutex<u64>> = OnceLock::new();
+    /// the file extensions to list defaults to xml,json,jsonl,csv,pdf,doc,docx,ppt,pptx,xls,xlsx,odt,odp,ods,ott,otp,ots,rtf,htm,html,txt,log
+    /// e.g. "html,txt,json"
+    #[argh(
+        option,
+        short = 'f',
+        default = "String::from(\"xml,json,jsonl,csv,pdf,doc,docx,ppt,pptx,xls,xlsx,odt,odp,ods,ott,otp,ots,rtf,htm,html,txt,log\")"
+    )]
+    file_extensions: String,
 
-    TOTAL_FILES_TO_INDEX.get_or_init(|| Mutex::new(0u64));
+    /// list the files matched by this glob below the directory instead of the
+    /// ones with the file extensions, can be repeated
+    #[argh(option, from_str_fn(parse_glob))]
+    glob: Vec<String>,
 
-    let on_start = move |total_files: u64| {
-        // Retrieve the total_files_to_index from the static variable
-        let total_files_to_index = TOTAL_FILES_TO_INDEX.get().unwrap();
+    /// skip the files matched by this glob below the directory, can be repeated
+    #[argh(option, from_str_fn(parse_glob))]
+    exclude_glob: Vec<String>,
 
-        // Lock the mutex to update the value
-        let mut total_files_to_index = total_files_to_index.lock().unwrap();
+    /// skip the files whose path below the directory matches this regex, case sensitive
+    #[argh(option, from_str_fn(parse_path_regex))]
+    exclude_path_regex: Option<Regex>,
 
-        // Initialize the total_files_to_index to total_files
-        *total_files_to_index = total_files;
+    /// include only the files whose path below the directory matches this regex, case sensitive
+    #[argh(option, from_str_fn(parse_path_regex))]
+    include_path_regex: Option<Regex>,
 
-        println!(
-            "Start indexing {} files with concurrency {}",
-            total_files_to_index, args.concurrency
-        );
-    };
+    /// skip the files ignored by the .gitignore and .solrignore files
+    #[argh(switch)]
+    respect_ignore_files: bool,
 
-    let on_next = |indexed_count: u64| {
-        let total_files_to_index = TOTAL_FILES_TO_INDEX.get().unwrap();
+    /// exclude files who's content contains this regex pattern, case insensitive
+    #[argh(option, short = 'e')]
+    exclude_regex: Option<String>,
 
-        let total_files_to_index = total_files_to_index.lock().unwrap();
+    /// include only files who's content contains this regex pattern, case insensitive
+    #[argh(option, short = 'i')]
+    include_regex: Option<String>,
 
-        // get the percent complete as a float
-        let percetn_complete = (indexed_count as f64 / *total_files_to_index as f64) * 100.0;
+    /// print one JSON object per line with the path, document id, size, and mime type
+    #[argh(switch)]
+    ndjson: bool,
+}
 
-        // print the precent complete to presicion of 2 decimal places
-        print!(
-            "{}/{} indexed {:.2}%\r",
-            indexed_count, *total_files_to_index, percetn_complete
-        );
-        io::stdout().flush().unwrap(); // Flush the output buffer
-    };
+/// set the options that were given on the command line, the defaults of the builder stay otherwise
+trait GivenOption: Sized {
+    /// call the setter with the value of the option if it was given
+    fn given<T>(self, value: Option<T>, set: impl FnOnce(Self, T) -> Self) -> Self;
+}
 
-    let on_finish = || {
-        println!("\nFinished indexing.");
-    };
+impl GivenOption for PostConfigBuilder {
+    fn given<T>(self, value: Option<T>, set: impl FnOnce(Self, T) -> Self) -> Self {
+        match value {
+            Some(value) => set(self, value),
+            None => self,
+        }
+    }
+}
 
-    solr_post(
-        args.into(),
-        Some(Box::new(on_start)),
-        Some(Box::new(on_next)),
+impl TryFrom<ListArgs> for PostConfig {
+    type Error = ConfigError;
+
+    fn try_from(val: ListArgs) -> Result<Self, Self::Error> {
+        let mut directories = val.directory.into_iter();
+        let builder = PostConfig::builder()
+            .given(directories.next(), PostConfigBuilder::directory_path)
+            .directory_paths(directories)
+            .file_extensions(val.file_extensions.split(','))
+            .given(
+                val.exclude_path_regex,
+                PostConfigBuilder::exclude_path_regex,
+            )
+            .given(
+                val.include_path_regex,
+                PostConfigBuilder::include_path_regex,
+            )
+            .respect_ignore_files(val.respect_ignore_files)
+            .given(
+                val.exclude_regex
+                    .map(|s| Regex::new(&format!("(?i){}", s)).unwrap()),
+                PostConfigBuilder::exclude_regex,
+            )
+            .given(
+                val.include_regex
+                    .map(|s| Regex::new(&format!("(?i){}", s)).unwrap()),
+                PostConfigBuilder::include_regex,
+            );
+        let builder = val.glob.into_iter().fold(builder, PostConfigBuilder::glob);
+        let builder = val
+            .exclude_glob
+            .into_iter()
+            .fold(builder, PostConfigBuilder::exclude_glob);
+        builder.build()
+    }
+}
+
+impl TryFrom<DeleteArgs> for PostConfig {
+    type Error = ConfigError;
+
+    fn try_from(val: DeleteArgs) -> Result<Self, Self::Error> {
+        let auth = val.auth_args().scheme();
+        let login_field = val.login_field;
+        let builder = PostConfig::builder()
+            .collection(val.collection)
+            .host(val.host)
+            .port(val.port)
+            .given(val.url, PostConfigBuilder::update_url)
+            .given(auth, PostConfigBuilder::auth)
+            .commit(if val.no_commit {
+                CommitPolicy::None
+            } else if val.soft_commit {
+                CommitPolicy::Soft
+            } else {
+                CommitPolicy::Hard
+            })
+            .v2_api(val.v2_api)
+            .given(val.cacert, PostConfigBuilder::ca_cert)
+            .accept_invalid_certs(val.insecure)
+            .given(val.cert, PostConfigBuilder::client_cert)
+            .given(val.key, PostConfigBuilder::client_key)
+            .given(val.cert_password, PostConfigBuilder::client_cert_password)
+            .given(val.proxy, PostConfigBuilder::proxy)
+            .redirect(redirect_policy(val.max_redirects))
+            .given(val.local_address, PostConfigBuilder::local_address)
+            .given(val.login_url, |builder, url| {
+                builder.login(url, login_field)
+            });
+        let builder = val
+            .header
+            .into_iter()
+            .fold(builder, |builder, (name, value)| {
+                builder.header(&name, &value)
+            });
+        let builder = val
+            .resolve
+            .into_iter()
+            .fold(builder, |builder, (host, addr)| builder.resolve(host, addr));
+        builder.build()
+    }
+}
+
+impl TryFrom<SolrPostArgs> for PostConfig {
+    type Error = ConfigError;
+
+    fn try_from(val: SolrPostArgs) -> Result<Self, Self::Error> {
+        let auth = val.auth_args().scheme();
+        let mut collections = val.collection.into_iter();
+        let mut directories = val.directory.into_iter();
+        let mut urls = val.url.into_iter();
+        let login_field = val.login_field;
+        let builder = PostConfig::builder()
+            // the collection is part of the --url when it isn't given
+            .given(collections.next(), PostConfigBuilder::collection)
+            .extra_collections(collections)
+            .host(val.host)
+            .port(val.port)
+            .given(directories.next(), PostConfigBuilder::directory_path)
+            .directory_paths(directories)
+            .file_extensions(val.file_extensions.split(','))
+            .given(urls.next(), PostConfigBuilder::update_url)
+            .update_urls(urls)
+            .concurrency(val.concurrency)
+            // create regex objects from the exclude and include regex strings ignore case
+            .given(
+                val.exclude_regex
+                    .map(|s| Regex::new(&format!("(?i){}", s)).unwrap()),
+                PostConfigBuilder::exclude_regex,
+            )
+            .given(
+                val.include_regex
+                    .map(|s| Regex::new(&format!("(?i){}", s)).unwrap()),
+                PostConfigBuilder::include_regex,
+            )
+            .given(auth, PostConfigBuilder::auth)
+            .given(val.commit_url, PostConfigBuilder::commit_url)
+            .index_run_id(val.index_run_id)
+            .run_id_field(val.run_id_field)
+            .extract_only(val.extract_only)
+            .given(val.extract_output, PostConfigBuilder::extract_output)
+            .capture_attr(val.capture_attr)
+            .given(val.xpath, PostConfigBuilder::xpath)
+            .given(val.commit_every, PostConfigBuilder::commit_every)
+            .given(
+                val.commit_interval.map(Duration::from_secs),
+                PostConfigBuilder::commit_interval,
+            )
+            .optimistic_concurrency(val.optimistic_concurrency)
+            .given(
+                val.max_inflight_bytes,
+                PostConfigBuilder::max_inflight_bytes,
+            )
+            .compress_requests(val.compress)
+            .given(
+                val.connect_timeout.map(Duration::from_secs),
+                PostConfigBuilder::connect_timeout,
+            )
+            .given(
+                val.timeout.map(Duration::from_secs),
+                PostConfigBuilder::request_timeout,
+            )
+            .given(
+                val.deadline.map(Duration::from_secs),
+                PostConfigBuilder::deadline,
+            )
+            .given(
+                val.circuit_breaker,
+                PostConfigBuilder::circuit_breaker_threshold,
+            )
+            .circuit_breaker_wait(Duration::from_secs(val.circuit_breaker_wait))
+            .circuit_breaker_retries(val.circuit_breaker_retries)
+            .given(
+                val.max_requests_per_second,
+                PostConfigBuilder::max_requests_per_second,
+            )
+            .adaptive_concurrency(val.auto_concurrency)
+            .given(val.batch_size, PostConfigBuilder::batch_size)
+            .given(val.batch_max_bytes, PostConfigBuilder::batch_max_bytes)
+            .structured_update(val.structured_update)
+            .mode(val.mode)
+            .given(val.split, PostConfigBuilder::json_split)
+            .given(val.csv_separator, PostConfigBuilder::csv_separator)
+            .csv_header(!val.csv_no_header)
+            .given(val.csv_skip_lines, PostConfigBuilder::csv_skip_lines)
+            .given(val.csv_encapsulator, PostConfigBuilder::csv_encapsulator)
+            .given(val.jsonl_chunk_size, PostConfigBuilder::jsonl_chunk_size)
+            .given(val.split_large_files, PostConfigBuilder::split_large_files)
+            .html_metadata(val.html_metadata)
+            .given(
+                match val.sidecar_suffix {
+                    Some(sidecar_suffix) => Some(sidecar_suffix),
+                    None => val.sidecar_meta.then(|| DEFAULT_SIDECAR_SUFFIX.to_string()),
+                },
+                PostConfigBuilder::sidecar_suffix,
+            )
+            .given(val.id_prefix, PostConfigBuilder::id_prefix)
+            .given(val.base_url, PostConfigBuilder::base_url)
+            .given(val.strip_path, PostConfigBuilder::strip_path)
+            .file_dates(val.file_dates)
+            .file_size(val.file_size)
+            .content_hash(val.content_hash)
+            .transcode(val.transcode)
+            .given(
+                val.exclude_path_regex,
+                PostConfigBuilder::exclude_path_regex,
+            )
+            .given(
+                val.include_path_regex,
+                PostConfigBuilder::include_path_regex,
+            )
+            .respect_ignore_files(val.respect_ignore_files)
+            .commit(if val.no_commit {
+                CommitPolicy::None
+            } else if let Some(ms) = val.commit_within {
+                CommitPolicy::Within(ms)
+            } else if val.soft_commit {
+                CommitPolicy::Soft
+            } else {
+                CommitPolicy::Hard
+            })
+            .optimize_after(val.optimize)
+            .given(val.max_segments, PostConfigBuilder::optimize_max_segments)
+            .given(
+                val.rollback_on_failures,
+                PostConfigBuilder::rollback_on_failures,
+            )
+            .sync(val.sync)
+            .clean(val.clean)
+            .given(
+                match (val.state_file, val.incremental) {
+                    (Some(state_file), _) => Some(state_file),
+                    (None, true) => Some(PathBuf::from(".solr-post-state.json")),
+                    (None, false) => None,
+                },
+                PostConfigBuilder::state_file,
+            )
+            .incremental(val.incremental)
+            .given(
+                match (val.journal, val.resume) {
+                    (Some(journal), _) => Some(journal),
+                    (None, true) => Some(PathBuf::from(".solr-post-journal")),
+                    (None, false) => None,
+                },
+                PostConfigBuilder::journal_file,
+            )
+            .resume(val.resume)
+            .given(val.error_report, PostConfigBuilder::error_report)
+            .given(val.from_report, PostConfigBuilder::from_report)
+            .given(val.filelist, PostConfigBuilder::file_list)
+            .given(val.max_errors, PostConfigBuilder::max_errors)
+            .ping(!val.no_ping)
+            .given(val.wait_for_solr, PostConfigBuilder::wait_for_solr)
+            .verify(val.verify)
+            .given(val.zk_hosts, PostConfigBuilder::zk_hosts)
+            .given(
+                match (val.route, val.route_from_path) {
+                    (Some(route), _) => Some(DocumentRoute::Fixed(route)),
+                    (None, Some(depth)) => Some(DocumentRoute::PathComponent(depth)),
+                    (None, None) => None,
+                },
+                PostConfigBuilder::route,
+            )
+            .v2_api(val.v2_api)
+            .given(val.cacert, PostConfigBuilder::ca_cert)
+            .accept_invalid_certs(val.insecure)
+            .given(val.cert, PostConfigBuilder::client_cert)
+            .given(val.key, PostConfigBuilder::client_key)
+            .given(val.cert_password, PostConfigBuilder::client_cert_password)
+            .given(val.proxy, PostConfigBuilder::proxy)
+            .redirect(redirect_policy(val.max_redirects))
+            .given(val.local_address, PostConfigBuilder::local_address)
+            .given(val.login_url, |builder, url| {
+                builder.login(url, login_field)
+            });
+
+        #[cfg(feature = "mmap")]
+        let builder = builder.mmap_scan(val.mmap_scan);
+        #[cfg(feature = "markdown")]
+        let builder = builder.given(
+            match (val.markdown, val.markdown_html) {
+                (_, true) => Some(MarkdownBody::Html),
+                (true, false) => Some(MarkdownBody::Text),
+                (false, false) => None,
+            },
+            PostConfigBuilder::markdown,
+        );
+        #[cfg(feature = "language")]
+        let builder = builder.detect_language(val.detect_language);
+        #[cfg(feature = "metrics")]
+        let builder = builder
+            .given(val.metrics_push_url, PostConfigBuilder::metrics_push_url)
+            .given(val.metrics_listen, PostConfigBuilder::metrics_listen);
+        #[cfg(feature = "statsd")]
+        let builder = builder.given(val.statsd, PostConfigBuilder::statsd_addr);
+
+        let builder = val
+            .literal
+            .into_iter()
+            .fold(builder, |builder, (field, value)| {
+                builder.literal(field, value)
+            });
+        let builder = val
+            .param
+            .into_iter()
+            .fold(builder, |builder, (name, value)| builder.param(name, value));
+        let builder = val
+            .capture
+            .into_iter()
+            .fold(builder, PostConfigBuilder::capture);
+        let builder = val
+            .json_field
+            .into_iter()
+            .fold(builder, PostConfigBuilder::json_field);
+        let builder = val
+            .csv_fieldnames
+            .iter()
+            .flat_map(|names| names.split(','))
+            .fold(builder, PostConfigBuilder::csv_fieldname);
+        let builder = val
+            .path_field
+            .into_iter()
+            .fold(builder, PostConfigBuilder::path_field);
+        let builder = val
+            .directory_fields
+            .iter()
+            .flat_map(|names| names.split(','))
+            .fold(builder, PostConfigBuilder::directory_field);
+        let builder = val
+            .mime
+            .into_iter()
+            .fold(builder, |builder, (extension, mime_type)| {
+                builder.mime_override(extension, mime_type)
+            });
+        let builder = val.glob.into_iter().fold(builder, PostConfigBuilder::glob);
+        let builder = val
+            .exclude_glob
+            .into_iter()
+            .fold(builder, PostConfigBuilder::exclude_glob);
+        let builder = val
+            .header
+            .into_iter()
+            .fold(builder, |builder, (name, value)| {
+                builder.header(&name, &value)
+            });
+        let builder = val
+            .resolve
+            .into_iter()
+            .fold(builder, |builder, (host, addr)| builder.resolve(host, addr));
+        builder.build()
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    // posting takes no subcommand so existing invocations keep working
+    match args.get(1).map(String::as_str) {
+        Some("delete") => delete(parse_args("solr-post delete", &args[2..])).await,
+        Some("list") => list(parse_args("solr-post list", &args[2..])),
+        _ => post(parse_args("solr-post", &expand_verbose(&args[1..]))).await,
+    }
+}
+
+/// argh doesn't combine short switches, expand e.g. -vv into -v -v
+fn expand_verbose(args: &[String]) -> Vec<String> {
+    args.iter()
+        .flat_map(|arg| match arg.strip_prefix('-') {
+            Some(vs) if vs.len() > 1 && vs.chars().all(|c| c == 'v') => {
+                vec![String::from("-v"); vs.len()]
+            }
+            _ => vec![arg.clone()],
+        })
+        .collect()
+}
+
+/// parse the args of the command, exits on --help or invalid args like argh::from_env
+fn parse_args<T: FromArgs>(command: &str, args: &[String]) -> T {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    T::from_args(&[command], &args).unwrap_or_else(|early_exit| match early_exit.status {
+        Ok(()) => {
+            println!("{}", early_exit.output);
+            std::process::exit(0);
+        }
+        Err(()) => {
+            eprintln!(
+                "{}\nRun {} --help for more information.",
+                early_exit.output, command
+            );
+            std::process::exit(1);
+        }
+    })
+}
+
+/// ask before deleting every document of the collection, or every one with the id prefix
+fn confirm_clean(collections: &[String], id_prefix: Option<&str>) -> bool {
+    let documents = match id_prefix {
+        Some(id_prefix) => format!("documents with the id prefix {}", id_prefix),
+        None => String::from("documents"),
+    };
+    print!(
+        "Delete all {} in collection{} {} before posting? [y/N] ",
+        documents,
+        if collections.len() > 1 { "s" } else { "" },
+        collections.join(", ")
+    );
+    io::stdout().flush().unwrap();
+
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// the configuration of the args, exits with the first invalid option
+fn build_config<T>(args: T) -> PostConfig
+where
+    PostConfig: TryFrom<T, Error = ConfigError>,
+{
+    PostConfig::try_from(args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(EXIT_FAILURE);
+    })
+}
+
+/// print the requests a run would send
+fn dry_run(config: PostConfig) {
+    let clean = config.clean;
+    let sync = config.sync;
+    let id_prefix = config.id_prefix_for();
+
+    let dry_run = match solr_dry_run(config) {
+        Ok(dry_run) => dry_run,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_FAILURE);
+        }
+    };
+
+    if clean {
+        match id_prefix {
+            Some(id_prefix) => println!(
+                "Would delete every document with the id prefix {} in the collection.",
+                id_prefix
+            ),
+            None => println!("Would delete every document in the collection."),
+        }
+    }
+
+    let mut files = 0;
+    for request in &dry_run.requests {
+        println!("POST {} ({})", request.url, request.content_type);
+        files += request.files.len();
+
+        if request.files.len() > 1 {
+            for file in &request.files {
+                println!("  {}", file.display());
+            }
+        }
+    }
+
+    if sync {
+        println!("Would delete the documents of removed files.");
+    }
+
+    if let Some(commit_url) = &dry_run.commit_url {
+        println!("Would commit with {}", commit_url);
+    }
+
+    println!(
+        "Would post {} files in {} requests, {} skipped, {} unchanged, {} already indexed, {} failed to scan.",
+        files,
+        dry_run.requests.len(),
+        dry_run.skipped,
+        dry_run.unchanged,
+        dry_run.resumed,
+        dry_run.failed
+    );
+}
+
+fn list(args: ListArgs) {
+    if args.directory.is_empty() {
+        eprintln!("Required options not provided:\n    --directory");
+        std::process::exit(EXIT_FAILURE);
+    }
+
+    let ndjson = args.ndjson;
+    let config = build_config(args);
+
+    let files = match solr_list_files(&config) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_FAILURE);
+        }
+    };
+
+    let mut stdout = io::stdout().lock();
+    for file in files {
+        let line = match ndjson {
+            true => serde_json::json!({
+                "path": file,
+                "id": file,
+                "size": file.metadata().map(|metadata| metadata.len()).ok(),
+                "mime": mime_guess::from_path(&file).first_or_octet_stream().to_string(),
+            })
+            .to_string(),
+            false => file.display().to_string(),
+        };
+
+        // stop quietly when piped into e.g. head
+        if writeln!(stdout, "{}", line).is_err() {
+            return;
+        }
+    }
+}
+
+async fn delete(args: DeleteArgs) {
+    args.auth_args().check();
+
+    if !args.login_field.is_empty() && args.login_url.is_none() {
+        eprintln!("--login-field requires --login-url");
+        std::process::exit(EXIT_FAILURE);
+    }
+
+    let query = args.query.clone();
+    let config = build_config(args);
+
+    match solr_delete_by_query(&config, &query).await {
+        Ok(()) => println!("Deleted documents matching {}", query),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_FAILURE);
+        }
+    }
+}
+
+async fn post(args: SolrPostArgs) {
+    init_logger(args.verbose, args.quiet);
+
+    // log lines would tear through the progress bar
+    let options = RunOptions {
+        concurrency: args.concurrency,
+        index_run_id: args.index_run_id,
+        lock_file: args.lock_file.clone(),
+        output: args.output,
+        progress: !args.quiet
+            && (args.progress || (io::stderr().is_terminal() && args.verbose == 0)),
+        quiet: args.quiet,
+    };
+    let interval = args.interval;
+    #[cfg(feature = "watch")]
+    let watch = args.watch;
+
+    #[cfg(feature = "watch")]
+    if watch && interval.is_some() {
+        eprintln!("--watch can not be combined with --interval");
+        std::process::exit(EXIT_FAILURE);
+    }
+
+    // the collection is part of the --url
+    if args.collection.is_empty() && args.url.is_empty() {
+        eprintln!("Required options not provided:\n    --collection");
+        std::process::exit(EXIT_FAILURE);
+    }
+
+    if args.directory.is_empty() {
+        eprintln!("Required options not provided:\n    --directory");
+        std::process::exit(EXIT_FAILURE);
+    }
+
+    args.auth_args().check();
+
+    if !args.login_field.is_empty() && args.login_url.is_none() {
+        eprintln!("--login-field requires --login-url");
+        std::process::exit(EXIT_FAILURE);
+    }
+
+    if args.route.is_some() && args.route_from_path.is_some() {
+        eprintln!("--route can not be combined with --route-from-path");
+        std::process::exit(EXIT_FAILURE);
+    }
+
+    if args.mode != PostMode::JsonDocs && (args.split.is_some() || !args.json_field.is_empty()) {
+        eprintln!("--split and --json-field require --mode json-docs");
+        std::process::exit(EXIT_FAILURE);
+    }
+
+    let csv_options = args.csv_separator.is_some()
+        || args.csv_no_header
+        || args.csv_fieldnames.is_some()
+        || args.csv_skip_lines.is_some()
+        || args.csv_encapsulator.is_some();
+    if args.mode != PostMode::Csv && csv_options {
+        eprintln!("the --csv-* options require --mode csv");
+        std::process::exit(EXIT_FAILURE);
+    }
+
+    let dry = args.dry_run;
+    let yes = args.yes;
+    let collections = args.collection.clone();
+    let mut config = build_config(args);
+
+    if dry {
+        dry_run(config);
+        return;
+    }
+
+    if config.clean && !yes && !confirm_clean(&collections, config.id_prefix_for().as_deref()) {
+        println!("Aborted.");
+        std::process::exit(EXIT_FAILURE);
+    }
+
+    // on ctrl-c finish the requests in flight and commit what was already indexed,
+    // a second ctrl-c exits immediately
+    let cancellation_token = CancellationToken::new();
+    config.cancellation_token = Some(cancellation_token.clone());
+    config.commit_on_cancel = true;
+    let stop = cancellation_token.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!(
+                "\nCancelling, finishing requests in flight. Press ctrl-c again to exit now."
+            );
+            cancellation_token.cancel();
+
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(EXIT_CANCELLED);
+            }
+        }
+    });
+
+    let Some(interval) = interval else {
+        let Some(summary) = post_once(config.clone(), &options).await else {
+            std::process::exit(EXIT_FAILURE);
+        };
+
+        #[cfg(feature = "watch")]
+        if watch && !summary.cancelled {
+            watch_directory(config, options.output).await;
+            return;
+        }
+
+        std::process::exit(exit_code(&summary));
+    };
+
+    // runs never overlap, when a run takes longer than the interval the missed runs are skipped
+    // and the next one starts on the following tick
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {}
+            _ = stop.cancelled() => break,
+        }
+
+        post_once(config.clone(), &options).await;
+
+        if stop.is_cancelled() {
+            break;
+        }
+
+        if options.output == Output::Text && !options.quiet {
+            println!("Next run in {}.", humantime::format_duration(interval));
+        }
+    }
+}
+
+/// post the files once and print the summary, None if posting failed or was skipped because the lock
+/// file is held by another run
+async fn post_once(mut config: PostConfig, options: &RunOptions) -> Option<PostSummary> {
+    // hold the lock until the run is finished, it is released when the file is closed
+    let _lock = match &options.lock_file {
+        Some(path) => match lock(path) {
+            Ok(Some(file)) => Some(file),
+            Ok(None) => {
+                let message = format!("{} is locked by another run", path.display());
+                match options.output {
+                    Output::Text => println!("Skipping run, {}.", message),
+                    Output::Json => print_json(json!({ "event": "skipped", "reason": message })),
+                }
+                return None;
+            }
+            Err(e) => {
+                eprintln!("failed to lock {}: {}", path.display(), e);
+                return None;
+            }
+        },
+        None => None,
+    };
+
+    // generate the run id here so it can be printed at start and in the summary
+    let run_id = new_run_id();
+    config.run_id = Some(run_id.clone());
+
+    match options.output {
+        Output::Text if config.extract_only && config.extract_output.is_none() => {
+            post_extract_only(config).await
+        }
+        Output::Text => post_text(config, options, run_id).await,
+        Output::Json => post_json(config, run_id).await,
+    }
+}
+
+/// print the content and metadata Tika extracted from each file, the failures on stderr
+async fn post_extract_only(config: PostConfig) -> Option<PostSummary> {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(100);
+    let printer = tokio::spawn(async move {
+        while let Some(event) = receiver.recv().await {
+            match event {
+                PostEvent::FileIndexed(result) => {
+                    let extracted = match serde_json::from_str::<Value>(&result.response_body) {
+                        Ok(response) => {
+                            serde_json::to_string_pretty(&response).unwrap_or(result.response_body)
+                        }
+                        Err(_) => result.response_body,
+                    };
+                    println!("==> {} <==\n{}\n", result.path.display(), extracted);
+                }
+                PostEvent::FileFailed(result) => match result.error {
+                    Some(e) => eprintln!("Failed {}: {}", result.path.display(), e),
+                    None => eprintln!("Failed {}", result.path.display()),
+                },
+                _ => {}
+            }
+        }
+    });
+
+    let result = solr_post_with_events(config, sender).await;
+    let _ = printer.await;
+
+    match result {
+        Ok(summary) => {
+            println!(
+                "Extracted {} of {} files, {} failed, {} skipped",
+                summary.indexed, summary.total, summary.failed, summary.skipped
+            );
+            Some(summary)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    }
+}
+
+/// post the files printing the progress and summary for humans
+async fn post_text(
+    config: PostConfig,
+    options: &RunOptions,
+    run_id: String,
+) -> Option<PostSummary> {
+    if options.quiet {
+        return match solr_post(config, None, None, None, None).await {
+            Ok(summary) => Some(summary),
+            Err(e) => {
+                eprintln!("{}", e);
+                None
+            }
+        };
+    }
+
+    let result = match options.progress {
+        true => post_with_progress_bar(config, options, run_id).await,
+        false => post_plain(config, options, run_id).await,
+    };
+
+    match result {
+        Ok(summary) => {
+            if summary.cancelled {
+                println!("Cancelled.");
+            }
+
+            if summary.resumed > 0 {
+                println!(
+                    "Skipped {} files indexed before the interruption.",
+                    summary.resumed
+                );
+            }
+
+            if summary.unchanged > 0 {
+                println!("Skipped {} unchanged files.", summary.unchanged);
+            }
+
+            if summary.deleted > 0 {
+                println!("Deleted {} documents of removed files.", summary.deleted);
+            }
+
+            println!(
+                "Indexed {} of {} files, {} failed, {} skipped, {} conflicts, {} bytes sent in {:.2}s ({:.2} docs/sec)",
+                summary.indexed,
+                summary.total,
+                summary.failed,
+                summary.skipped,
+                summary.conflicts,
+                summary.bytes_sent,
+                summary.elapsed.as_secs_f64(),
+                summary.docs_per_sec()
+            );
+
+            if summary.rolled_back {
+                println!("Rolled back.");
+            }
+
+            if let Some(found) = summary.verified {
+                println!("Verified {} documents in Solr.", found);
+            }
+
+            Some(summary)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    }
+}
+
+/// post the files printing only the start and the end of the run, e.g. when the output is a log file
+async fn post_plain(
+    config: PostConfig,
+    options: &RunOptions,
+    run_id: String,
+) -> Result<PostSummary, SolrPostError> {
+    let concurrency = options.concurrency;
+    let index_run_id = options.index_run_id;
+
+    let start_run_id = run_id.clone();
+    let on_start = move |total_files: u64| {
+        println!(
+            "Start indexing {} files with concurrency {}",
+            total_files, concurrency
+        );
+
+        if index_run_id {
+            println!("Run id: {}", start_run_id);
+        }
+    };
+
+    let on_finish = move || {
+        println!("Finished indexing.");
+
+        if index_run_id {
+            println!("Run id: {}", run_id);
+        }
+    };
+
+    solr_post(
+        config,
+        Some(Box::new(on_start)),
+        None,
+        None,
         Some(Box::new(on_finish)),
     )
-    .await;
+    .await
+}
+
+/// post the files showing a progress bar with the rates, ETA, and failures on stderr
+async fn post_with_progress_bar(
+    config: PostConfig,
+    options: &RunOptions,
+    run_id: String,
+) -> Result<PostSummary, SolrPostError> {
+    let concurrency = options.concurrency;
+    let index_run_id = options.index_run_id;
+
+    let bytes_sent = Arc::new(AtomicU64::new(0));
+    // the stderr target hides the bar when stderr isn't a terminal, --progress draws it anyway
+    let draw_target = match io::stderr().is_terminal() {
+        true => ProgressDrawTarget::stderr(),
+        false => ProgressDrawTarget::term_like(Box::new(Term::stderr())),
+    };
+    let bar = ProgressBar::with_draw_target(Some(0), draw_target)
+        .with_style(progress_style(bytes_sent.clone()));
+
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(100);
+    let printer = tokio::spawn(async move {
+        let mut failed = 0;
+
+        // print the first failure of each kind above the bar, the bar counts them all
+        let mut reported = HashSet::new();
+
+        while let Some(event) = receiver.recv().await {
+            match event {
+                PostEvent::ScanFinished { total, .. } => {
+                    bar.println(format!(
+                        "Start indexing {} files with concurrency {}",
+                        total, concurrency
+                    ));
+                    if index_run_id {
+                        bar.println(format!("Run id: {}", run_id));
+                    }
+
+                    bar.set_length(total as u64);
+                    bar.reset_elapsed();
+                }
+                PostEvent::FileIndexed(result) => {
+                    bytes_sent.fetch_add(result.bytes_sent, Ordering::Relaxed);
+                    bar.inc(1);
+                }
+                PostEvent::FileFailed(result) => {
+                    bytes_sent.fetch_add(result.bytes_sent, Ordering::Relaxed);
+
+                    if !result.is_conflict() {
+                        failed += 1;
+                        bar.set_message(format!("{} failed", failed));
+
+                        let kind = match result.status {
+                            Some(status) => status.to_string(),
+                            None => String::from("no response"),
+                        };
+                        if let (Some(error), true) = (&result.error, reported.insert(kind)) {
+                            bar.println(format!(
+                                "failed to index {}: {}",
+                                result.path.display(),
+                                error
+                            ));
+                        }
+                    }
+
+                    bar.inc(1);
+                }
+                PostEvent::Finished(_) => {
+                    // the summary that follows has the final numbers
+                    bar.finish_and_clear();
+                    println!("Finished indexing.");
+
+                    if index_run_id {
+                        println!("Run id: {}", run_id);
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
+
+    let result = solr_post_with_events(config, sender).await;
+    let _ = printer.await;
+
+    result
+}
+
+/// files, files/sec, bytes/sec, ETA, and the number of failures
+fn progress_style(bytes_sent: Arc<AtomicU64>) -> ProgressStyle {
+    ProgressStyle::with_template(
+        "[{elapsed_precise}] {wide_bar} {pos}/{len} {files_per_sec} {sent_per_sec} ETA {eta} {msg}",
+    )
+    .expect("the progress template is valid")
+    .with_key(
+        "files_per_sec",
+        |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+            let _ = write!(w, "{:.1} files/s", state.per_sec());
+        },
+    )
+    .with_key(
+        "sent_per_sec",
+        move |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+            let secs = state.elapsed().as_secs_f64();
+            let rate = match secs > 0.0 {
+                true => bytes_sent.load(Ordering::Relaxed) as f64 / secs,
+                false => 0.0,
+            };
+            let _ = write!(w, "{}/s", HumanBytes(rate as u64));
+        },
+    )
+}
+
+/// post the files printing one JSON object per line for each event of the run. with --index-run-id the
+/// run id is printed at the start and in the finished event, e.g. to delete the documents of the run
+async fn post_json(config: PostConfig, run_id: String) -> Option<PostSummary> {
+    let run_id = config.index_run_id.then_some(run_id);
+    if let Some(run_id) = &run_id {
+        print_json(json!({ "event": "started", "run_id": run_id }));
+    }
+
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(100);
+    let printer = tokio::spawn(async move {
+        while let Some(event) = receiver.recv().await {
+            if let Some(json) = event_json(&event, run_id.as_deref()) {
+                print_json(json);
+            }
+        }
+    });
+
+    let result = solr_post_with_events(config, sender).await;
+    let _ = printer.await;
+
+    match result {
+        Ok(summary) => Some(summary),
+        Err(e) => {
+            print_json(json!({ "event": "error", "message": e.to_string() }));
+            None
+        }
+    }
+}
+
+/// the JSON line of an event, None for the events that are too noisy to print e.g. every queued file.
+/// the run id is added to the finished event when it is given
+fn event_json(event: &PostEvent, run_id: Option<&str>) -> Option<Value> {
+    let file_json = |event: &str, result: &FileResult| {
+        json!({
+            "event": event,
+            "path": result.path,
+            "status": result.status.map(|status| status.as_u16()),
+            "bytes_sent": result.bytes_sent,
+            "elapsed_ms": result.elapsed.as_millis() as u64,
+            "qtime_ms": result.qtime.map(|qtime| qtime.as_millis() as u64),
+            "error": result.error.as_ref().map(|e| e.to_string()),
+        })
+    };
+
+    let mut json = match event {
+        PostEvent::ScanStarted { directory_path } => {
+            json!({ "event": "scan_started", "directory": directory_path })
+        }
+        PostEvent::ScanFinished { total, skipped } => {
+            json!({ "event": "scan_finished", "total": total, "skipped": skipped })
+        }
+        PostEvent::Cleaned => json!({ "event": "cleaned" }),
+        PostEvent::FileQueued { .. } => return None,
+        PostEvent::FileIndexed(result) => file_json("file_indexed", result),
+        PostEvent::FileFailed(result) => file_json("file_failed", result),
+        PostEvent::FileDeleted { path } => json!({ "event": "file_deleted", "path": path }),
+        PostEvent::Committed => json!({ "event": "committed" }),
+        PostEvent::Optimized => json!({ "event": "optimized" }),
+        PostEvent::RolledBack => json!({ "event": "rolled_back" }),
+        PostEvent::Synced { deleted } => json!({ "event": "synced", "deleted": deleted }),
+        PostEvent::Verified { found } => json!({ "event": "verified", "found": found }),
+        PostEvent::Finished(summary) => json!({
+            "event": "finished",
+            "total": summary.total,
+            "indexed": summary.indexed,
+            "failed": summary.failed,
+            "conflicts": summary.conflicts,
+            "skipped": summary.skipped,
+            "unchanged": summary.unchanged,
+            "resumed": summary.resumed,
+            "verified": summary.verified,
+            "deleted": summary.deleted,
+            "bytes_sent": summary.bytes_sent,
+            "elapsed_ms": summary.elapsed.as_millis() as u64,
+            "docs_per_sec": summary.docs_per_sec(),
+            "cancelled": summary.cancelled,
+            "rolled_back": summary.rolled_back,
+        }),
+    };
+
+    if let (PostEvent::Finished(_), Some(run_id)) = (event, run_id) {
+        json["run_id"] = json!(run_id);
+    }
+
+    Some(json)
+}
+
+fn print_json(event: Value) {
+    println!("{}", event);
+}
+
+/// The authentication flags of posting and deleting, at most one of them may be given
+struct AuthArgs {
+    user: Option<String>,
+    bearer_token: Option<String>,
+    netrc: bool,
+    netrc_file: Option<PathBuf>,
+    #[cfg(feature = "keyring")]
+    keyring: Option<String>,
+    #[cfg(feature = "kerberos")]
+    negotiate: bool,
+}
+
+impl AuthArgs {
+    /// the flags that were given
+    fn given(&self) -> Vec<&'static str> {
+        let mut given = Vec::new();
+        if self.user.is_some() {
+            given.push("-u");
+        }
+        if self.bearer_token.is_some() {
+            given.push("--bearer-token");
+        }
+        if self.netrc || self.netrc_file.is_some() {
+            given.push("--netrc");
+        }
+        #[cfg(feature = "keyring")]
+        if self.keyring.is_some() {
+            given.push("--keyring");
+        }
+        #[cfg(feature = "kerberos")]
+        if self.negotiate {
+            given.push("--negotiate");
+        }
+        given
+    }
+
+    /// exit if more than one flag was given, or the credentials of the environment are invalid
+    fn check(&self) {
+        let given = self.given();
+        if given.len() > 1 {
+            eprintln!(
+                "{} can not be combined with {}",
+                given[0],
+                given[1..].join(" or ")
+            );
+            std::process::exit(EXIT_FAILURE);
+        }
+
+        if given.is_empty() {
+            if let Err(e) = env_credentials() {
+                eprintln!("{}", e);
+                std::process::exit(EXIT_FAILURE);
+            }
+        }
+    }
+
+    /// the flag that was given, otherwise the basic auth credentials of the environment
+    fn scheme(self) -> Option<AuthScheme> {
+        if let Some(bearer_token) = self.bearer_token {
+            return Some(AuthScheme::Bearer(bearer_token));
+        }
+        if let Some(user) = self.user {
+            return Some(AuthScheme::Basic(user));
+        }
+        if self.netrc || self.netrc_file.is_some() {
+            return Some(AuthScheme::Netrc(self.netrc_file));
+        }
+        #[cfg(feature = "keyring")]
+        if let Some(user) = self.keyring {
+            return Some(AuthScheme::Keyring { user });
+        }
+        #[cfg(feature = "kerberos")]
+        if self.negotiate {
+            return Some(AuthScheme::Negotiate);
+        }
+        env_credentials().ok().flatten().map(AuthScheme::Basic)
+    }
+}
+
+impl SolrPostArgs {
+    fn auth_args(&self) -> AuthArgs {
+        AuthArgs {
+            user: self.user.clone(),
+            bearer_token: self.bearer_token.clone(),
+            netrc: self.netrc,
+            netrc_file: self.netrc_file.clone(),
+            #[cfg(feature = "keyring")]
+            keyring: self.keyring.clone(),
+            #[cfg(feature = "kerberos")]
+            negotiate: self.negotiate,
+        }
+    }
+}
+
+impl DeleteArgs {
+    fn auth_args(&self) -> AuthArgs {
+        AuthArgs {
+            user: self.user.clone(),
+            bearer_token: self.bearer_token.clone(),
+            netrc: self.netrc,
+            netrc_file: self.netrc_file.clone(),
+            #[cfg(feature = "keyring")]
+            keyring: self.keyring.clone(),
+            #[cfg(feature = "kerberos")]
+            negotiate: self.negotiate,
+        }
+    }
+}
+
+/// the basic auth credentials of SOLR_POST_USER and SOLR_POST_PASSWORD, or SOLR_AUTH as "user:pass",
+/// so they don't show up in the shell history and ps output
+fn env_credentials() -> Result<Option<String>, String> {
+    let var = |name| {
+        std::env::var(name)
+            .ok()
+            .filter(|value: &String| !value.is_empty())
+    };
+
+    match (var("SOLR_POST_USER"), var("SOLR_POST_PASSWORD")) {
+        (Some(user), Some(password)) => return Ok(Some(format!("{}:{}", user, password))),
+        (Some(_), None) => return Err(String::from("SOLR_POST_USER requires SOLR_POST_PASSWORD")),
+        (None, Some(_)) => return Err(String::from("SOLR_POST_PASSWORD requires SOLR_POST_USER")),
+        (None, None) => {}
+    }
+
+    match var("SOLR_AUTH") {
+        Some(creds) if !creds.contains(':') => Err(String::from(
+            "SOLR_AUTH must be basic auth credentials in the form \"user:pass\"",
+        )),
+        creds => Ok(creds),
+    }
+}
+
+fn parse_mode(value: &str) -> Result<PostMode, String> {
+    match value {
+        "extract" => Ok(PostMode::Extract),
+        "json-docs" => Ok(PostMode::JsonDocs),
+        "xml" => Ok(PostMode::XmlUpdate),
+        "csv" => Ok(PostMode::Csv),
+        _ => Err(format!(
+            "unknown mode {}, expected extract, json-docs, xml, or csv",
+            value
+        )),
+    }
+}
+
+fn parse_separator(value: &str) -> Result<char, String> {
+    match value {
+        "tab" | "\\t" => Ok('\t'),
+        _ => {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(separator), None) => Ok(separator),
+                _ => Err(format!(
+                    "invalid separator {}, expected a single character or tab",
+                    value
+                )),
+            }
+        }
+    }
+}
+
+fn parse_output(value: &str) -> Result<Output, String> {
+    match value {
+        "text" => Ok(Output::Text),
+        "json" => Ok(Output::Json),
+        _ => Err(format!("unknown output {}, expected text or json", value)),
+    }
+}
+
+/// a header in the form of curl -H e.g. "X-Api-Key: abc"
+fn parse_header(value: &str) -> Result<(String, String), String> {
+    let (name, header_value) = value
+        .split_once(':')
+        .ok_or_else(|| format!("invalid header {}, expected \"Name: value\"", value))?;
+    let (name, header_value) = (name.trim(), header_value.trim());
+    HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("invalid header name {}", name))?;
+    HeaderValue::from_str(header_value).map_err(|_| format!("invalid value of header {}", name))?;
+    Ok((name.to_string(), header_value.to_string()))
+}
+
+/// --max-redirects 0 never follows a redirect
+fn redirect_policy(max_redirects: Option<usize>) -> RedirectPolicy {
+    match max_redirects {
+        Some(0) => RedirectPolicy::Never,
+        Some(max) => RedirectPolicy::Follow(max),
+        None => RedirectPolicy::default(),
+    }
+}
+
+/// host:port:addr like curl --resolve, the address of IPv6 in brackets or not e.g. "solr:8983:[::1]"
+fn parse_resolve(value: &str) -> Result<(String, SocketAddr), String> {
+    let invalid = || format!("invalid resolve {}, expected host:port:address", value);
+    let mut parts = value.splitn(3, ':');
+    let (Some(host), Some(port), Some(addr)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(invalid());
+    };
+    let port: u16 = port.parse().map_err(|_| invalid())?;
+    let addr: IpAddr = addr
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .map_err(|_| invalid())?;
+    if host.is_empty() {
+        return Err(invalid());
+    }
+    Ok((host.to_string(), SocketAddr::new(addr, port)))
+}
+
+fn parse_proxy(value: &str) -> Result<String, String> {
+    match is_http_url(value) {
+        true => Ok(value.to_string()),
+        false => Err(format!(
+            "invalid proxy URL {}, expected http://host:port",
+            value
+        )),
+    }
+}
+
+fn parse_login_url(value: &str) -> Result<String, String> {
+    match is_http_url(value) {
+        true => Ok(value.to_string()),
+        false => Err(format!("invalid login URL {}", value)),
+    }
+}
+
+fn parse_base_url(value: &str) -> Result<String, String> {
+    match is_http_url(value) {
+        true => Ok(value.to_string()),
+        false => Err(format!("invalid base URL {}", value)),
+    }
+}
+
+/// a regex with at least one named group e.g. "^docs/(?P<lang>[a-z]{2})/"
+fn parse_path_field(value: &str) -> Result<Regex, String> {
+    let regex = Regex::new(value).map_err(|e| format!("invalid path field regex: {}", e))?;
+    match regex.capture_names().flatten().next() {
+        Some(_) => Ok(regex),
+        None => Err(format!(
+            "the path field regex {} has no named groups",
+            value
+        )),
+    }
+}
+
+/// a regex matched against the paths below the directory e.g. "^archive/"
+fn parse_path_regex(value: &str) -> Result<Regex, String> {
+    Regex::new(value).map_err(|e| format!("invalid path regex: {}", e))
+}
+
+/// an extension and its MIME type e.g. "log=text/plain", the extension is lowercased without the dot
+fn parse_mime_override(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((extension, mime_type))
+            if !extension.trim_start_matches('.').is_empty()
+                && mime_type.parse::<mime_guess::mime::Mime>().is_ok() =>
+        {
+            Ok((
+                extension.trim_start_matches('.').to_lowercase(),
+                mime_type.to_string(),
+            ))
+        }
+        _ => Err(format!("invalid {}, expected extension=mime/type", value)),
+    }
+}
+
+fn parse_glob(value: &str) -> Result<String, String> {
+    match wax::Glob::new(value) {
+        Ok(_) => Ok(value.to_string()),
+        Err(e) => Err(format!("invalid glob {}: {}", value, e)),
+    }
+}
+
+/// a field and its value e.g. "site=docs"
+fn parse_name_value(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, field_value)) if !name.is_empty() => {
+            Ok((name.to_string(), field_value.to_string()))
+        }
+        _ => Err(format!("invalid {}, expected name=value", value)),
+    }
+}
+
+fn is_http_url(value: &str) -> bool {
+    reqwest::Url::parse(value)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host_str().is_some())
+}
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    match humantime::parse_duration(value) {
+        Ok(duration) if duration.is_zero() => Err(String::from("the duration must not be zero")),
+        Ok(duration) => Ok(duration),
+        Err(e) => Err(format!("invalid duration {}: {}", value, e)),
+    }
+}
+
+/// show the log lines of the library on stderr, RUST_LOG overrides the level of the flags
+fn init_logger(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Warn,
+        (false, 1) => LevelFilter::Info,
+        (false, 2) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+
+    // the HTTP client is noisy below warn, only show it at trace
+    let dependencies = match level {
+        LevelFilter::Trace => LevelFilter::Trace,
+        _ => LevelFilter::Warn,
+    };
+
+    env_logger::Builder::new()
+        .filter_level(dependencies)
+        .filter_module("solr_post", level)
+        .parse_default_env()
+        .init();
+}
+
+/// the exit code of a run, so scripts and CI pipelines can tell a partial failure from a broken run
+fn exit_code(summary: &PostSummary) -> i32 {
+    if summary.cancelled {
+        EXIT_CANCELLED
+    } else if summary.rolled_back || (summary.failed > 0 && summary.indexed == 0) {
+        EXIT_FAILURE
+    } else if summary.failed > 0 {
+        EXIT_PARTIAL_FAILURE
+    } else {
+        0
+    }
+}
+
+/// lock the file exclusively, None if another process holds the lock
+fn lock(path: &Path) -> io::Result<Option<File>> {
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
+
+/// print each change until ctrl-c cancels the watch
+#[cfg(feature = "watch")]
+async fn watch_directory(config: PostConfig, output: Output) {
+    if output == Output::Text {
+        let directories: Vec<String> = std::iter::once(&config.directory_path)
+            .chain(&config.directory_paths)
+            .map(|directory| directory.display().to_string())
+            .collect();
+        println!(
+            "Watching {} for changes, press ctrl-c to stop.",
+            directories.join(", ")
+        );
+    }
+
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(100);
+    let printer = tokio::spawn(async move {
+        while let Some(event) = receiver.recv().await {
+            if output == Output::Json {
+                if let Some(event) = event_json(&event, None) {
+                    print_json(event);
+                }
+                continue;
+            }
+
+            match event {
+                PostEvent::FileIndexed(result) => println!("Indexed {}", result.path.display()),
+                PostEvent::FileFailed(result) => match result.error {
+                    Some(e) => eprintln!("Failed {}: {}", result.path.display(), e),
+                    None => eprintln!("Failed {}", result.path.display()),
+                },
+                PostEvent::FileDeleted { path } => println!("Deleted {}", path.display()),
+                PostEvent::Committed => println!("Committed."),
+                _ => {}
+            }
+        }
+    });
+
+    let result = solr_watch(config, sender).await;
+    let _ = printer.await;
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(EXIT_FAILURE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_result(status: Option<u16>, error: Option<SolrPostError>) -> FileResult {
+        FileResult {
+            path: PathBuf::from("/site/index.html"),
+            status: status.map(|status| reqwest::StatusCode::from_u16(status).unwrap()),
+            response_body: String::new(),
+            bytes_sent: 120,
+            elapsed: Duration::from_millis(15),
+            qtime: Some(Duration::from_millis(3)),
+            error,
+        }
+    }
+
+    #[test]
+    fn queued_files_are_not_printed() {
+        let event = PostEvent::FileQueued {
+            path: PathBuf::from("/site/index.html"),
+        };
+        assert_eq!(event_json(&event, Some("run")), None);
+    }
+
+    #[test]
+    fn scan_and_command_events() {
+        let event = PostEvent::ScanFinished {
+            total: 3,
+            skipped: 1,
+        };
+        assert_eq!(
+            event_json(&event, None),
+            Some(json!({ "event": "scan_finished", "total": 3, "skipped": 1 }))
+        );
+        assert_eq!(
+            event_json(&PostEvent::Committed, Some("run")),
+            Some(json!({ "event": "committed" }))
+        );
+        assert_eq!(
+            event_json(&PostEvent::Synced { deleted: 2 }, None),
+            Some(json!({ "event": "synced", "deleted": 2 }))
+        );
+    }
+
+    #[test]
+    fn file_events() {
+        assert_eq!(
+            event_json(&PostEvent::FileIndexed(file_result(Some(200), None)), None),
+            Some(json!({
+                "event": "file_indexed",
+                "path": "/site/index.html",
+                "status": 200,
+                "bytes_sent": 120,
+                "elapsed_ms": 15,
+                "qtime_ms": 3,
+                "error": null,
+            }))
+        );
+
+        let error = SolrPostError::InvalidDocument {
+            path: PathBuf::from("/site/index.html"),
+            message: "no JSON documents".to_string(),
+        };
+        let message = error.to_string();
+        let json =
+            event_json(&PostEvent::FileFailed(file_result(None, Some(error))), None).unwrap();
+        assert_eq!(json["event"], "file_failed");
+        assert_eq!(json["status"], Value::Null);
+        assert_eq!(json["error"], message);
+    }
+
+    #[test]
+    fn finished_has_the_summary_and_the_run_id() {
+        let summary = PostSummary {
+            total: 3,
+            indexed: 2,
+            failed: 1,
+            ..PostSummary::default()
+        };
+        let event = PostEvent::Finished(summary);
+
+        let json = event_json(&event, Some("20261016-abc")).unwrap();
+        assert_eq!(json["event"], "finished");
+        assert_eq!(json["indexed"], 2);
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["rolled_back"], false);
+        assert_eq!(json["run_id"], "20261016-abc");
+
+        let json = event_json(&event, None).unwrap();
+        assert!(json.get("run_id").is_none());
+    }
+
+    #[test]
+    fn a_held_lock_file_is_not_locked_again() {
+        let path = std::env::temp_dir().join(format!("solr_post_{}.lock", uuid::Uuid::new_v4()));
+
+        let first = lock(&path).unwrap();
+        assert!(first.is_some());
+        assert!(lock(&path).unwrap().is_none());
+
+        // the lock is released when the file is closed
+        drop(first);
+        assert!(lock(&path).unwrap().is_some());
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn exit_codes() {
+        let summary = |indexed, failed, cancelled, rolled_back| PostSummary {
+            indexed,
+            failed,
+            cancelled,
+            rolled_back,
+            ..PostSummary::default()
+        };
+        let cases = [
+            ("cancelled", summary(2, 1, true, false), EXIT_CANCELLED),
+            ("rolled back", summary(2, 1, false, true), EXIT_FAILURE),
+            ("all failed", summary(0, 3, false, false), EXIT_FAILURE),
+            ("partial", summary(2, 1, false, false), EXIT_PARTIAL_FAILURE),
+            ("ok", summary(3, 0, false, false), 0),
+            ("nothing to index", summary(0, 0, false, false), 0),
+        ];
+        for (case, summary, code) in cases {
+            assert_eq!(exit_code(&summary), code, "{}", case);
+        }
+    }
+
+    fn post_config(args: &[&str]) -> Result<PostConfig, ConfigError> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        PostConfig::try_from(parse_args::<SolrPostArgs>("solr-post", &args))
+    }
+
+    #[test]
+    fn args_are_built_into_the_config() {
+        let config = post_config(&[
+            "-c",
+            "docs",
+            "-c",
+            "archive",
+            "-d",
+            "site",
+            "--incremental",
+            "--literal",
+            "source=web",
+            "--header",
+            "X-Api-Key: abc",
+        ])
+        .ok()
+        .unwrap();
+        assert_eq!(config.collection, "docs");
+        assert_eq!(config.extra_collections, vec!["archive"]);
+        assert_eq!(
+            config.state_file,
+            Some(PathBuf::from(".solr-post-state.json"))
+        );
+        assert_eq!(config.literals, vec![("source".into(), "web".into())]);
+        assert_eq!(config.extra_headers["x-api-key"], "abc");
+    }
+
+    #[test]
+    fn invalid_args_are_config_errors() {
+        let error = |args: &[&str]| post_config(args).err().unwrap();
+        assert_eq!(
+            error(&["-c", "docs", "-d", "site", "--key", "client.key"]),
+            ConfigError::ClientKeyWithoutCert
+        );
+        assert_eq!(
+            error(&["-c", "docs", "-d", "site", "--extract-only", "--clean"]),
+            ConfigError::ExtractOnlyWith("clean")
+        );
+        assert_eq!(
+            error(&["-c", "docs", "-d", "site", "--max-errors", "0"]),
+            ConfigError::ZeroMaxErrors
+        );
+    }
 }
@@ -0,0 +1,133 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::warn;
+use reqwest::{StatusCode, Url};
+
+/// how long a failing node is skipped before it is tried again
+const NODE_DOWN: Duration = Duration::from_secs(30);
+
+/// skip a node after this many consecutive server errors, a single 5xx can be caused by the document
+const NODE_MAX_SERVER_ERRORS: usize = 3;
+
+/// The Solr nodes the requests are spread across, see PostConfig::zk_hosts and PostConfig::update_urls.
+/// each request goes to the node with the fewest requests in flight, ties are broken in round robin order.
+/// a node that refuses connections or keeps failing with server errors is skipped for a while
+pub(crate) struct SolrNodes {
+    nodes: Vec<Node>,
+    next: AtomicUsize,
+}
+
+struct Node {
+    base_url: Url,
+
+    /// the requests sent to the node that haven't completed yet
+    pending: AtomicUsize,
+
+    /// the server errors in a row, reset by a response without a server error
+    server_errors: AtomicUsize,
+
+    /// skip the node until then, None while it is healthy
+    down_until: Mutex<Option<Instant>>,
+}
+
+impl SolrNodes {
+    pub(crate) fn new(base_urls: Vec<Url>) -> Self {
+        SolrNodes {
+            nodes: base_urls
+                .into_iter()
+                .map(|base_url| Node {
+                    base_url,
+                    pending: AtomicUsize::new(0),
+                    server_errors: AtomicUsize::new(0),
+                    down_until: Mutex::new(None),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// the node with the fewest requests in flight that isn't down, any node if every node is down.
+    /// the node counts the request as pending until the NodeRef is dropped
+    pub(crate) fn pick(&self) -> NodeRef<'_> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let now = Instant::now();
+
+        let index = (0..self.nodes.len())
+            .map(|offset| (start + offset) % self.nodes.len())
+            .filter(|&index| {
+                self.nodes[index]
+                    .down_until
+                    .lock()
+                    .unwrap()
+                    .is_none_or(|down_until| down_until <= now)
+            })
+            .min_by_key(|&index| self.nodes[index].pending.load(Ordering::Relaxed))
+            .unwrap_or(start % self.nodes.len());
+
+        self.nodes[index].pending.fetch_add(1, Ordering::Relaxed);
+        NodeRef { nodes: self, index }
+    }
+}
+
+/// A node picked for a request
+pub(crate) struct NodeRef<'a> {
+    nodes: &'a SolrNodes,
+    index: usize,
+}
+
+impl NodeRef<'_> {
+    fn node(&self) -> &Node {
+        &self.nodes.nodes[self.index]
+    }
+
+    /// the URL sent to this node instead, the scheme, host and port of the URL are replaced with the node's
+    /// e.g. "http://localhost:8983/solr/docs/update" -> "http://10.0.0.2:8983/solr/docs/update"
+    pub(crate) fn url(&self, url: &str) -> String {
+        let base_url = &self.node().base_url;
+        let Ok(mut url) = Url::parse(url) else {
+            return url.to_string();
+        };
+
+        let _ = url.set_scheme(base_url.scheme());
+        let _ = url.set_host(base_url.host_str());
+        let _ = url.set_port(base_url.port());
+        url.to_string()
+    }
+
+    /// record the response status, the node is skipped after too many server errors in a row
+    pub(crate) fn completed(&self, status: StatusCode) {
+        let node = self.node();
+        if !status.is_server_error() {
+            node.server_errors.store(0, Ordering::Relaxed);
+        } else if node.server_errors.fetch_add(1, Ordering::Relaxed) + 1 >= NODE_MAX_SERVER_ERRORS {
+            node.server_errors.store(0, Ordering::Relaxed);
+            self.down();
+        }
+    }
+
+    /// skip the node for a while after it refused a connection
+    pub(crate) fn failed(&self) {
+        self.down();
+    }
+
+    fn down(&self) {
+        let node = self.node();
+        warn!(
+            "Solr node {} is failing, skipping it for {:?}",
+            node.base_url, NODE_DOWN
+        );
+        *node.down_until.lock().unwrap() = Some(Instant::now() + NODE_DOWN);
+    }
+}
+
+impl Drop for NodeRef<'_> {
+    fn drop(&mut self) {
+        self.node().pending.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::{Duration, Instant};
+
+/// Decides when to commit while posting, commits when either `commit_every` documents have been
+/// posted or `commit_interval` has elapsed since the last commit, whichever comes first.
+/// Both the document counter and the interval timer are reset after each commit.
+pub(crate) struct CommitScheduler {
+    /// commit after this many documents have been posted since the last commit
+    commit_every: Option<usize>,
+
+    /// commit when this much time has passed since the last commit
+    commit_interval: Option<Duration>,
+
+    /// the number of documents posted since the last commit
+    pending: usize,
+
+    /// when the last commit happened, or when posting started
+    last_commit: Instant,
+}
+
+impl CommitScheduler {
+    pub(crate) fn new(commit_every: Option<usize>, commit_interval: Option<Duration>) -> Self {
+        CommitScheduler {
+            commit_every,
+            commit_interval,
+            pending: 0,
+            last_commit: Instant::now(),
+        }
+    }
+
+    /// record a document that was posted
+    pub(crate) fn record(&mut self) {
+        self.pending += 1;
+    }
+
+    /// when the commit interval will next elapse, None if there is no commit interval
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.commit_interval
+            .map(|interval| self.last_commit + interval)
+    }
+
+    /// true if there are posted documents and either trigger has been reached
+    pub(crate) fn is_due(&self) -> bool {
+        if self.pending == 0 {
+            return false;
+        }
+
+        let count_reached = self
+            .commit_every
+            .is_some_and(|commit_every| self.pending >= commit_every);
+        let interval_elapsed = self
+            .commit_interval
+            .is_some_and(|interval| self.last_commit.elapsed() >= interval);
+
+        count_reached || interval_elapsed
+    }
+
+    /// reset both the document counter and the interval timer, called after each commit
+    /// and when the interval elapses with nothing to commit
+    pub(crate) fn reset(&mut self) {
+        self.pending = 0;
+        self.last_commit = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_to_commit_is_never_due() {
+        let scheduler = CommitScheduler::new(Some(1), Some(Duration::ZERO));
+        assert!(!scheduler.is_due());
+    }
+
+    #[test]
+    fn due_after_commit_every_documents() {
+        let mut scheduler = CommitScheduler::new(Some(2), None);
+        scheduler.record();
+        assert!(!scheduler.is_due());
+        scheduler.record();
+        assert!(scheduler.is_due());
+    }
+
+    #[test]
+    fn due_when_the_interval_elapsed() {
+        let mut scheduler = CommitScheduler::new(None, Some(Duration::ZERO));
+        scheduler.record();
+        assert!(scheduler.is_due());
+
+        let mut scheduler = CommitScheduler::new(None, Some(Duration::from_secs(3600)));
+        scheduler.record();
+        assert!(!scheduler.is_due());
+    }
+
+    #[test]
+    fn never_due_without_triggers() {
+        let mut scheduler = CommitScheduler::new(None, None);
+        scheduler.record();
+        assert!(!scheduler.is_due());
+        assert_eq!(scheduler.deadline(), None);
+    }
+
+    #[test]
+    fn reset_clears_the_pending_documents() {
+        let mut scheduler = CommitScheduler::new(Some(1), Some(Duration::from_secs(60)));
+        scheduler.record();
+        assert!(scheduler.is_due());
+
+        let before = Instant::now();
+        scheduler.reset();
+        assert!(!scheduler.is_due());
+        assert!(scheduler.deadline().unwrap() >= before + Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+
+use crate::SolrPostError;
+
+/// the basic auth credentials of the host from a .netrc file as "user:pass", the `default` entry if no machine
+/// matches. path defaults to $NETRC or ~/.netrc (~/_netrc on Windows), None if there is no entry or no file
+pub(crate) fn netrc_credentials(
+    path: Option<&Path>,
+    host: &str,
+) -> Result<Option<String>, SolrPostError> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => match default_netrc() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(None),
+        },
+    };
+
+    let netrc = std::fs::read_to_string(&path).map_err(|source| SolrPostError::Io {
+        path: path.clone(),
+        source,
+    })?;
+
+    Ok(parse_netrc(&netrc, host))
+}
+
+fn default_netrc() -> Option<PathBuf> {
+    if let Some(netrc) = std::env::var_os("NETRC") {
+        return Some(PathBuf::from(netrc));
+    }
+
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    let name = if cfg!(windows) { "_netrc" } else { ".netrc" };
+    Some(PathBuf::from(home).join(name))
+}
+
+/// an entry of a .netrc file, the machine is None for the default entry
+#[derive(Default)]
+struct NetrcEntry<'a> {
+    machine: Option<&'a str>,
+    login: Option<&'a str>,
+    password: Option<&'a str>,
+}
+
+/// the login and password of the first entry for the host, see the netrc(5) man page
+fn parse_netrc(netrc: &str, host: &str) -> Option<String> {
+    // a macro definition runs until the next empty line and holds no credentials
+    let mut text = String::new();
+    let mut in_macro = false;
+    for line in netrc.lines() {
+        if in_macro {
+            in_macro = !line.trim().is_empty();
+            continue;
+        }
+        if line.split_whitespace().any(|token| token == "macdef") {
+            in_macro = true;
+        } else {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+
+    let mut entries: Vec<NetrcEntry> = Vec::new();
+    let mut tokens = text.split_whitespace();
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => entries.push(NetrcEntry {
+                machine: tokens.next(),
+                ..Default::default()
+            }),
+            "default" => entries.push(NetrcEntry::default()),
+            "login" => {
+                if let Some(entry) = entries.last_mut() {
+                    entry.login = tokens.next();
+                }
+            }
+            "password" => {
+                if let Some(entry) = entries.last_mut() {
+                    entry.password = tokens.next();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let entry = entries
+        .iter()
+        .find(|entry| entry.machine == Some(host))
+        .or_else(|| entries.iter().find(|entry| entry.machine.is_none()))?;
+    Some(format!("{}:{}", entry.login?, entry.password?))
+}
+
+/// the basic auth credentials of the user from the OS keyring as "user:pass", the password is stored with the
+/// Solr host as the service and the user as the account
+#[cfg(feature = "keyring")]
+pub(crate) fn keyring_credentials(user: &str, host: &str) -> Result<String, SolrPostError> {
+    let password = keyring::Entry::new(host, user)
+        .and_then(|entry| entry.get_password())
+        .map_err(|e| {
+            SolrPostError::Credentials(format!(
+                "no password for {} on {} in the keyring: {}",
+                user, host, e
+            ))
+        })?;
+
+    Ok(format!("{}:{}", user, password))
+}
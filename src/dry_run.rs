@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+
+use crate::batch::{BatchFormat, Batches, PostJob};
+use crate::scan::scan_files;
+use crate::state::absolute;
+use crate::stream::{batch_url, file_url, json_lines_url, request_content_type, PostContext};
+use crate::{select_files, PostConfig, SolrPostError};
+
+/// A request a run would send, see solr_dry_run
+#[derive(Debug, Clone)]
+pub struct PlannedRequest {
+    /// the URL with all of its parameters e.g. the document id and run id literals
+    pub url: String,
+
+    /// the Content-Type header of the request
+    pub content_type: String,
+
+    /// the files posted by the request, more than one when batching
+    pub files: Vec<PathBuf>,
+}
+
+/// What a run would do, see solr_dry_run
+#[derive(Debug, Clone, Default)]
+pub struct DryRun {
+    /// the posts in the order they would be sent, sorted by path, with one post per collection
+    pub requests: Vec<PlannedRequest>,
+
+    /// the number of files filtered out by the content regexes
+    pub skipped: usize,
+
+    /// the number of files that could not be read while scanning
+    pub failed: usize,
+
+    /// the number of files incremental would skip because they haven't changed
+    pub unchanged: usize,
+
+    /// the number of files resume would skip because the interrupted run indexed them
+    pub resumed: usize,
+
+    /// the URL of the final commit, None when the commit policy doesn't commit
+    pub commit_url: Option<String>,
+}
+
+/// Scan and filter the files and build the requests a run with this configuration would send,
+/// without sending anything. Useful to check the include/exclude regexes before posting
+pub fn solr_dry_run(config: PostConfig) -> Result<DryRun, SolrPostError> {
+    let context = PostContext::new(config)?;
+
+    let mut scanned = scan_files(&context.config)?;
+    let selection = select_files(&context.config, &mut scanned.files)?;
+
+    let mut files: Vec<String> = scanned.files.into_iter().collect();
+    files.sort();
+
+    let planned: Vec<PlannedRequest> = Batches::new(
+        files.into_iter(),
+        context.config.update_batch_size(),
+        context.config.jsonl_chunk_size.is_some(),
+    )
+    .map(|job| match job {
+        PostJob::File(file) => {
+            let path = PathBuf::from(absolute(&file));
+            PlannedRequest {
+                url: file_url(&context, &path),
+                content_type: request_content_type(&context.config, &path),
+                files: vec![path],
+            }
+        }
+        // CSV files with different headers are split into more requests when they are read
+        // one request per chunk of the documents of the file
+        PostJob::JsonLines(file) => {
+            let path = PathBuf::from(absolute(&file));
+            PlannedRequest {
+                url: json_lines_url(&context, &path),
+                content_type: BatchFormat::Json.content_type().to_string(),
+                files: vec![path],
+            }
+        }
+        PostJob::Batch(format, files) => PlannedRequest {
+            url: batch_url(&context, format),
+            content_type: format.content_type().to_string(),
+            files: files
+                .iter()
+                .map(|file| PathBuf::from(absolute(file)))
+                .collect(),
+        },
+    })
+    .collect();
+
+    // each request is sent to the collection and then to each of the extra_collections
+    let mut requests = Vec::with_capacity(planned.len());
+    for request in planned {
+        for url in context.collection_urls(&request.url)? {
+            requests.push(PlannedRequest {
+                url,
+                ..request.clone()
+            });
+        }
+    }
+
+    Ok(DryRun {
+        requests,
+        skipped: scanned.skipped,
+        failed: scanned.failed,
+        unchanged: selection.unchanged,
+        resumed: selection.resumed,
+        commit_url: context
+            .config
+            .commit
+            .is_explicit()
+            .then(|| context.commit_url.clone()),
+    })
+}
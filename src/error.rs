@@ -0,0 +1,565 @@
+use std::{fmt, io, path::PathBuf, sync::Arc, time::Duration};
+
+use reqwest::StatusCode;
+
+use crate::{PostMode, SolrError, MAX_SPLIT_SIZE};
+
+/// Error returned when the commit URL can not be determined from the configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitUrlError {
+    /// the update or commit URL could not be parsed
+    InvalidUrl(String),
+
+    /// the update URL has no recognizable Solr collection path, an explicit commit URL is required
+    CannotInfer(String),
+
+    /// the URL has no /solr/<collection> path to replace with an extra collection
+    NoCollection(String),
+}
+
+impl fmt::Display for CommitUrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommitUrlError::InvalidUrl(url) => write!(f, "invalid Solr URL: {}", url),
+            CommitUrlError::CannotInfer(url) => write!(
+                f,
+                "can not infer the commit URL from update URL {}, please set an explicit commit URL",
+                url
+            ),
+            CommitUrlError::NoCollection(url) => write!(
+                f,
+                "can not find the collection in URL {}, extra collections need a /solr/<collection> or /api/collections/<collection> path",
+                url
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CommitUrlError {}
+
+/// Error returned by PostConfigBuilder::build for an invalid configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// the collection name is empty
+    EmptyCollection,
+
+    /// the host is empty
+    EmptyHost,
+
+    /// concurrency must be greater than 0
+    ZeroConcurrency,
+
+    /// at least one file extension is required
+    NoFileExtensions,
+
+    /// the update URL is not a valid http or https URL
+    InvalidUpdateUrl(String),
+
+    /// the commit URL is not a valid http or https URL
+    InvalidCommitUrl(String),
+
+    /// the proxy is not a valid http or https URL
+    InvalidProxyUrl(String),
+
+    /// the login URL is not a valid http or https URL
+    InvalidLoginUrl(String),
+
+    /// basic auth credentials must be in the form "user:pass"
+    InvalidBasicAuthCreds,
+
+    /// the bearer token is empty or has characters that can't be sent in a header
+    InvalidBearerToken,
+
+    /// the name or value of the header with this name can't be sent
+    InvalidHeader(String),
+
+    /// the run id field name is empty
+    EmptyRunIdField,
+
+    /// the field name of a literal is empty
+    EmptyLiteralField,
+
+    /// the name of an update request param is empty
+    EmptyParamName,
+
+    /// the captured XHTML element is empty
+    EmptyCapture,
+
+    /// the XPath expression is empty
+    EmptyXpath,
+
+    /// the JSON split path must start with / and the JSON field must not be empty
+    InvalidJsonPath(String),
+
+    /// the sidecar suffix must not be empty, or every file would be its own sidecar
+    EmptySidecarSuffix,
+
+    /// the id prefix must not be empty
+    EmptyIdPrefix,
+
+    /// the base URL must be a valid http or https URL
+    InvalidBaseUrl(String),
+
+    /// the strip path is only removed from the paths mapped to the base URL
+    StripPathWithoutBaseUrl,
+
+    /// a path field regex names the fields with its named groups, so it needs at least one
+    PathFieldWithoutNames(String),
+
+    /// a MIME override needs an extension and a valid MIME type e.g. "text/plain"
+    InvalidMimeOverride(String),
+
+    /// the glob can not be parsed
+    InvalidGlob(String),
+
+    /// the CSV field names are joined with commas, so they must not hold one e.g. "id,title"
+    InvalidCsvFieldName(String),
+
+    /// the CSV separator and encapsulator must be different characters
+    CsvSeparatorIsEncapsulator,
+
+    /// CSV files without a header need the field names of their columns
+    CsvWithoutFieldNames,
+
+    /// the files of a PostMode other than Extract are posted as they are, batching would join them
+    ModeWithBatching(PostMode),
+
+    /// commit_every must be greater than 0
+    ZeroCommitEvery,
+
+    /// commit_interval must not be zero
+    ZeroCommitInterval,
+
+    /// max_inflight_bytes must be greater than 0
+    ZeroMaxInflightBytes,
+
+    /// timeouts and the deadline must not be zero
+    ZeroTimeout,
+
+    /// the circuit breaker threshold must be greater than 0
+    ZeroCircuitBreakerThreshold,
+
+    /// max errors must be greater than 0
+    ZeroMaxErrors,
+
+    /// rollback_on_failures must be greater than 0
+    ZeroRollbackOnFailures,
+
+    /// the request rate must be a positive number
+    InvalidRequestRate,
+
+    /// the batch size must be greater than 0
+    ZeroBatchSize,
+
+    /// batch_max_bytes must be greater than 0
+    ZeroBatchMaxBytes,
+
+    /// jsonl_chunk_size must be greater than 0
+    ZeroJsonlChunkSize,
+
+    /// split_large_files must be greater than 0
+    ZeroSplitSize,
+
+    /// split_large_files must be at most MAX_SPLIT_SIZE
+    SplitSizeTooLarge,
+
+    /// the optimize max segments must be greater than 0
+    ZeroMaxSegments,
+
+    /// the Pushgateway URL can not be parsed
+    InvalidMetricsPushUrl(String),
+
+    /// the ZooKeeper connection string must not be empty
+    EmptyZkHosts,
+
+    /// a fixed document route must not be empty
+    EmptyRoute,
+
+    /// a batch holds files from many directories, so it can't be routed by a path component
+    RouteFromPathWithBatching,
+
+    /// the client key belongs to a client certificate
+    ClientKeyWithoutCert,
+
+    /// the files to post come from either a file list or an error report
+    FileListWithFromReport,
+
+    /// incremental needs a state file to compare against
+    IncrementalWithoutStateFile,
+
+    /// resume needs the journal of the interrupted run
+    ResumeWithoutJournal,
+
+    /// verify counts committed documents, it needs CommitPolicy::Hard or CommitPolicy::Soft
+    VerifyWithoutCommit,
+
+    /// extract only leaves the index as is, so it can't be combined with an option that changes it
+    ExtractOnlyWith(&'static str),
+
+    /// the commit URL can not be determined from the update URL
+    CommitUrl(CommitUrlError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::EmptyCollection => write!(f, "the collection name must not be empty"),
+            ConfigError::EmptyHost => write!(f, "the host must not be empty"),
+            ConfigError::ZeroConcurrency => write!(f, "concurrency must be greater than 0"),
+            ConfigError::NoFileExtensions => write!(f, "at least one file extension is required"),
+            ConfigError::InvalidUpdateUrl(url) => write!(f, "invalid update URL: {}", url),
+            ConfigError::InvalidCommitUrl(url) => write!(f, "invalid commit URL: {}", url),
+            ConfigError::InvalidProxyUrl(url) => write!(f, "invalid proxy URL: {}", url),
+            ConfigError::InvalidLoginUrl(url) => write!(f, "invalid login URL: {}", url),
+            ConfigError::InvalidBasicAuthCreds => {
+                write!(
+                    f,
+                    "basic auth credentials must be in the form \"user:pass\""
+                )
+            }
+            ConfigError::InvalidBearerToken => {
+                write!(f, "the bearer token must be non empty visible ASCII")
+            }
+            ConfigError::InvalidHeader(name) => write!(f, "invalid header {}", name),
+            ConfigError::EmptyRunIdField => write!(f, "the run id field must not be empty"),
+            ConfigError::EmptyLiteralField => write!(f, "the literal field must not be empty"),
+            ConfigError::EmptyParamName => write!(f, "the param name must not be empty"),
+            ConfigError::EmptyCapture => write!(f, "the captured element must not be empty"),
+            ConfigError::EmptyXpath => write!(f, "the XPath expression must not be empty"),
+            ConfigError::InvalidJsonPath(path) => write!(f, "invalid JSON path: {}", path),
+            ConfigError::EmptySidecarSuffix => write!(f, "the sidecar suffix must not be empty"),
+            ConfigError::EmptyIdPrefix => write!(f, "the id prefix must not be empty"),
+            ConfigError::InvalidBaseUrl(url) => write!(f, "invalid base URL: {}", url),
+            ConfigError::StripPathWithoutBaseUrl => write!(f, "strip path requires a base URL"),
+            ConfigError::InvalidGlob(glob) => write!(f, "invalid glob: {}", glob),
+            ConfigError::InvalidMimeOverride(mime_override) => {
+                write!(f, "invalid MIME override: {}", mime_override)
+            }
+            ConfigError::PathFieldWithoutNames(regex) => {
+                write!(f, "the path field regex {} has no named groups", regex)
+            }
+            ConfigError::InvalidCsvFieldName(name) => write!(f, "invalid CSV field name: {}", name),
+            ConfigError::CsvSeparatorIsEncapsulator => {
+                write!(f, "the CSV separator and encapsulator must be different")
+            }
+            ConfigError::CsvWithoutFieldNames => {
+                write!(f, "CSV files without a header need the field names")
+            }
+            ConfigError::ModeWithBatching(mode) => {
+                write!(
+                    f,
+                    "the {} mode can not be combined with batching",
+                    mode.name()
+                )
+            }
+            ConfigError::ZeroCommitEvery => write!(f, "commit_every must be greater than 0"),
+            ConfigError::ZeroCommitInterval => write!(f, "commit_interval must not be zero"),
+            ConfigError::ZeroMaxInflightBytes => {
+                write!(f, "max_inflight_bytes must be greater than 0")
+            }
+            ConfigError::ZeroTimeout => write!(f, "timeouts must not be zero"),
+            ConfigError::IncrementalWithoutStateFile => {
+                write!(f, "incremental indexing requires a state file")
+            }
+            ConfigError::InvalidMetricsPushUrl(url) => {
+                write!(f, "invalid Pushgateway URL: {}", url)
+            }
+            ConfigError::EmptyZkHosts => write!(f, "the ZooKeeper hosts must not be empty"),
+            ConfigError::EmptyRoute => write!(f, "the document route must not be empty"),
+            ConfigError::ClientKeyWithoutCert => {
+                write!(f, "a client key requires a client certificate")
+            }
+            ConfigError::FileListWithFromReport => {
+                write!(f, "a file list can not be combined with an error report")
+            }
+            ConfigError::RouteFromPathWithBatching => {
+                write!(
+                    f,
+                    "routing by a path component can not be combined with batching"
+                )
+            }
+            ConfigError::ResumeWithoutJournal => write!(f, "resume requires a journal file"),
+            ConfigError::VerifyWithoutCommit => write!(f, "verify requires a hard or soft commit"),
+            ConfigError::ExtractOnlyWith(option) => {
+                write!(f, "extract only can not be combined with {}", option)
+            }
+            ConfigError::ZeroMaxSegments => write!(f, "max segments must be greater than 0"),
+            ConfigError::ZeroBatchSize => write!(f, "the batch size must be greater than 0"),
+            ConfigError::ZeroBatchMaxBytes => {
+                write!(f, "the batch max bytes must be greater than 0")
+            }
+            ConfigError::ZeroJsonlChunkSize => {
+                write!(f, "the JSON Lines chunk size must be greater than 0")
+            }
+            ConfigError::ZeroSplitSize => write!(f, "the split size must be greater than 0"),
+            ConfigError::SplitSizeTooLarge => {
+                write!(f, "the split size must be at most {} bytes", MAX_SPLIT_SIZE)
+            }
+            ConfigError::InvalidRequestRate => {
+                write!(f, "max requests per second must be a positive number")
+            }
+            ConfigError::ZeroMaxErrors => write!(f, "max errors must be greater than 0"),
+            ConfigError::ZeroRollbackOnFailures => {
+                write!(f, "rollback on failures must be greater than 0")
+            }
+            ConfigError::ZeroCircuitBreakerThreshold => {
+                write!(f, "the circuit breaker threshold must be greater than 0")
+            }
+            ConfigError::CommitUrl(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::CommitUrl(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Errors that can occur while posting files to Solr
+#[derive(Debug)]
+pub enum SolrPostError {
+    /// the glob expression built from the file extensions is invalid
+    Glob(wax::BuildError),
+
+    /// a file could not be read
+    Io { path: PathBuf, source: io::Error },
+
+    /// the HTTP request could not be sent e.g. connection refused, or the client could not be built
+    Request(reqwest::Error),
+
+    /// Solr responded with a non success status, with the error Solr describes in the body if it has one
+    Response {
+        url: String,
+        status: StatusCode,
+        solr_error: Option<SolrError>,
+    },
+
+    /// the commit URL could not be determined
+    CommitUrl(CommitUrlError),
+
+    /// committing the posted documents failed, wraps the request or response error
+    Commit(Box<SolrPostError>),
+
+    /// optimizing the collection failed, wraps the request or response error
+    Optimize(Box<SolrPostError>),
+
+    /// rolling back the uncommitted documents failed, wraps the request or response error
+    Rollback(Box<SolrPostError>),
+
+    /// deleting documents by query failed, wraps the request or response error
+    Delete(Box<SolrPostError>),
+
+    /// deleting the documents of removed files failed, wraps the request or response error
+    Sync(Box<SolrPostError>),
+
+    /// deleting every document before posting failed, wraps the request or response error
+    Clean(Box<SolrPostError>),
+
+    /// the collection's ping handler failed before posting, wraps the request or response error
+    Ping(Box<SolrPostError>),
+
+    /// counting the documents to verify the run failed, wraps the request or response error
+    Verify(Box<SolrPostError>),
+
+    /// Solr has fewer documents than files were indexed after the commit, see PostConfig::verify
+    VerifyMismatch { indexed: usize, found: usize },
+
+    /// Solr responded with a body that could not be parsed
+    InvalidResponse { url: String, message: String },
+
+    /// the incremental state file could not be parsed
+    StateFile { path: PathBuf, message: String },
+
+    /// the error report to retry could not be parsed
+    InvalidReport { path: PathBuf, message: String },
+
+    /// a PEM certificate file could not be parsed
+    Certificate { path: PathBuf, message: String },
+
+    /// a header value has characters that can't be sent in a header e.g. a newline in the bearer token
+    InvalidHeader { name: String },
+
+    /// the credentials could not be read e.g. from the OS keyring
+    Credentials(String),
+
+    /// the SolrCloud nodes could not be read from ZooKeeper
+    ZooKeeper(String),
+
+    /// the login form of PostConfig::login was not accepted
+    Login(String),
+
+    /// the metrics could not be served
+    Metrics(String),
+
+    /// the directory could not be watched for changes
+    Watch(String),
+
+    /// the async runtime of solr_post_blocking could not be started
+    Runtime(io::Error),
+
+    /// the run took longer than the configured deadline and was aborted
+    DeadlineExceeded(Duration),
+
+    /// the run was aborted by the circuit breaker after too many consecutive server errors
+    CircuitOpen { threshold: usize },
+
+    /// the run was aborted because max_errors files failed
+    TooManyErrors { max_errors: usize },
+
+    /// the batch request the file was part of could not be sent, shared by every file of the batch
+    BatchRequest(Arc<reqwest::Error>),
+
+    /// a structured file could not be added to a batch e.g. invalid JSON
+    InvalidDocument { path: PathBuf, message: String },
+}
+
+impl fmt::Display for SolrPostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolrPostError::Glob(e) => write!(f, "invalid file glob: {}", e),
+            SolrPostError::Io { path, source } => {
+                write!(f, "failed to read file {}: {}", path.display(), source)
+            }
+            SolrPostError::Request(e) => write!(f, "{}", e),
+            SolrPostError::Response {
+                url,
+                status,
+                solr_error,
+            } => {
+                write!(f, "POST {} {}", url, status)?;
+                match solr_error {
+                    Some(solr_error) => write!(f, ": {}", solr_error.message),
+                    None => Ok(()),
+                }
+            }
+            SolrPostError::CommitUrl(e) => write!(f, "{}", e),
+            SolrPostError::Commit(e) => write!(f, "commit failed: {}", e),
+            SolrPostError::Optimize(e) => write!(f, "optimize failed: {}", e),
+            SolrPostError::Rollback(e) => write!(f, "rollback failed: {}", e),
+            SolrPostError::Delete(e) => write!(f, "delete failed: {}", e),
+            SolrPostError::Sync(e) => write!(f, "sync failed: {}", e),
+            SolrPostError::Clean(e) => write!(f, "clean failed: {}", e),
+            SolrPostError::Ping(e) => {
+                write!(f, "ping failed: {}", e)?;
+                match e.as_ref() {
+                    SolrPostError::Request(e) if e.is_connect() => {
+                        write!(f, "\nIs Solr server running?")
+                    }
+                    SolrPostError::Response { status, .. }
+                        if *status == StatusCode::UNAUTHORIZED || *status == StatusCode::FORBIDDEN =>
+                    {
+                        write!(f, "\nAre the user and password correct?")
+                    }
+                    SolrPostError::Response { status, .. } if *status == StatusCode::NOT_FOUND => {
+                        write!(f, "\nDoes the collection exist?")
+                    }
+                    SolrPostError::Response { status, .. } if status.is_redirection() => {
+                        write!(f, "\nIs the URL redirected? See --max-redirects")
+                    }
+                    _ => Ok(()),
+                }
+            }
+            SolrPostError::Verify(e) => write!(f, "verify failed: {}", e),
+            SolrPostError::VerifyMismatch { indexed, found } => write!(
+                f,
+                "verify failed: {} files were indexed but Solr found only {} documents",
+                indexed, found
+            ),
+            SolrPostError::InvalidResponse { url, message } => {
+                write!(f, "invalid response from {}: {}", url, message)
+            }
+            SolrPostError::StateFile { path, message } => {
+                write!(f, "invalid state file {}: {}", path.display(), message)
+            }
+            SolrPostError::InvalidReport { path, message } => {
+                write!(f, "invalid error report {}: {}", path.display(), message)
+            }
+            SolrPostError::Certificate { path, message } => {
+                write!(f, "invalid certificate {}: {}", path.display(), message)
+            }
+            SolrPostError::InvalidHeader { name } => {
+                write!(f, "invalid {} header value", name)
+            }
+            SolrPostError::Credentials(message) => write!(f, "credentials: {}", message),
+            SolrPostError::ZooKeeper(message) => write!(f, "ZooKeeper: {}", message),
+            SolrPostError::Login(message) => write!(f, "login failed: {}", message),
+            SolrPostError::Metrics(message) => write!(f, "metrics: {}", message),
+            SolrPostError::Watch(message) => write!(f, "failed to watch the directory: {}", message),
+            SolrPostError::Runtime(e) => write!(f, "failed to start the async runtime: {}", e),
+            SolrPostError::DeadlineExceeded(deadline) => {
+                write!(f, "run aborted after the {:?} deadline", deadline)
+            }
+            SolrPostError::TooManyErrors { max_errors } => {
+                write!(f, "run aborted after {} files failed", max_errors)
+            }
+            SolrPostError::CircuitOpen { threshold } => write!(
+                f,
+                "run aborted after {} consecutive server errors\nIs Solr server running and collection available?",
+                threshold
+            ),
+            SolrPostError::BatchRequest(e) => write!(f, "{}", e),
+            SolrPostError::InvalidDocument { path, message } => {
+                write!(f, "invalid document {}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SolrPostError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SolrPostError::Glob(e) => Some(e),
+            SolrPostError::Io { source, .. } => Some(source),
+            SolrPostError::Request(e) => Some(e),
+            SolrPostError::Response { .. } => None,
+            SolrPostError::CommitUrl(e) => Some(e),
+            SolrPostError::Commit(e) => Some(e.as_ref()),
+            SolrPostError::Optimize(e) => Some(e.as_ref()),
+            SolrPostError::Rollback(e) => Some(e.as_ref()),
+            SolrPostError::Delete(e) => Some(e.as_ref()),
+            SolrPostError::Sync(e) => Some(e.as_ref()),
+            SolrPostError::Clean(e) => Some(e.as_ref()),
+            SolrPostError::Ping(e) => Some(e.as_ref()),
+            SolrPostError::Verify(e) => Some(e.as_ref()),
+            SolrPostError::VerifyMismatch { .. } => None,
+            SolrPostError::InvalidResponse { .. } => None,
+            SolrPostError::StateFile { .. } => None,
+            SolrPostError::InvalidReport { .. } => None,
+            SolrPostError::Certificate { .. } => None,
+            SolrPostError::InvalidHeader { .. } => None,
+            SolrPostError::Credentials(_) => None,
+            SolrPostError::ZooKeeper(_) => None,
+            SolrPostError::Login(_) => None,
+            SolrPostError::Metrics(_) => None,
+            SolrPostError::Watch(_) => None,
+            SolrPostError::Runtime(e) => Some(e),
+            SolrPostError::DeadlineExceeded(_) => None,
+            SolrPostError::CircuitOpen { .. } => None,
+            SolrPostError::TooManyErrors { .. } => None,
+            SolrPostError::BatchRequest(e) => Some(e.as_ref()),
+            SolrPostError::InvalidDocument { .. } => None,
+        }
+    }
+}
+
+impl From<wax::BuildError> for SolrPostError {
+    fn from(e: wax::BuildError) -> Self {
+        SolrPostError::Glob(e)
+    }
+}
+
+impl From<reqwest::Error> for SolrPostError {
+    fn from(e: reqwest::Error) -> Self {
+        SolrPostError::Request(e)
+    }
+}
+
+impl From<CommitUrlError> for SolrPostError {
+    fn from(e: CommitUrlError) -> Self {
+        SolrPostError::CommitUrl(e)
+    }
+}
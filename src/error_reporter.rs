@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+/// after the first occurrence of an error, print a summary line every this many repeats
+const SUMMARY_INTERVAL: u64 = 100;
+
+/// Deduplicates and throttles repeated error messages so a mass failure (wrong collection,
+/// auth down) doesn't bury the real problem under thousands of identical lines on stderr
+#[derive(Default)]
+pub(crate) struct ErrorReporter {
+    /// the number of times each kind of error has been reported, keyed by the error kind
+    counts: HashMap<String, u64>,
+}
+
+impl ErrorReporter {
+    /// report an error, the full message is only printed the first time an error of this kind is seen
+    /// e.g. key "404 Not Found" message "POST http://... 404 Not Found failed to index file: ..."
+    pub(crate) fn report(&mut self, key: &str, message: &str) {
+        if let Some(line) = self.line(key, message) {
+            eprintln!("{}", line);
+        }
+    }
+
+    /// print the summary of the similar errors that were suppressed since the last summary line
+    pub(crate) fn finish(&mut self) {
+        for line in self.summary() {
+            eprintln!("{}", line);
+        }
+    }
+
+    /// the line to print for an error, None while it is suppressed
+    fn line(&mut self, key: &str, message: &str) -> Option<String> {
+        let count = self.counts.entry(key.to_string()).or_insert(0);
+        *count += 1;
+
+        if *count == 1 {
+            Some(message.to_string())
+        } else if (*count - 1).is_multiple_of(SUMMARY_INTERVAL) {
+            Some(format!("{} more similar errors: {}", SUMMARY_INTERVAL, key))
+        } else {
+            None
+        }
+    }
+
+    /// the summary lines of the suppressed errors, the counts are cleared
+    fn summary(&mut self) -> Vec<String> {
+        self.counts
+            .drain()
+            .filter_map(|(key, count)| {
+                let suppressed = (count - 1) % SUMMARY_INTERVAL;
+                (suppressed > 0).then(|| format!("{} more similar errors: {}", suppressed, key))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_error_of_a_kind_is_printed() {
+        let mut reporter = ErrorReporter::default();
+        assert_eq!(
+            reporter.line("404 Not Found", "POST a.html 404 Not Found"),
+            Some("POST a.html 404 Not Found".to_string())
+        );
+        assert_eq!(
+            reporter.line("404 Not Found", "POST b.html 404 Not Found"),
+            None
+        );
+        assert_eq!(
+            reporter.line("500 Internal Server Error", "POST c.html 500"),
+            Some("POST c.html 500".to_string())
+        );
+    }
+
+    #[test]
+    fn repeats_are_summarized_every_interval() {
+        let mut reporter = ErrorReporter::default();
+        let lines: Vec<String> = (0..=2 * SUMMARY_INTERVAL)
+            .filter_map(|_| reporter.line("timeout", "POST a.html timed out"))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "POST a.html timed out".to_string(),
+                "100 more similar errors: timeout".to_string(),
+                "100 more similar errors: timeout".to_string(),
+            ]
+        );
+        assert!(reporter.summary().is_empty());
+    }
+
+    #[test]
+    fn finish_summarizes_the_rest() {
+        let mut reporter = ErrorReporter::default();
+        for _ in 0..4 {
+            reporter.line("timeout", "POST a.html timed out");
+        }
+        reporter.line("404 Not Found", "POST b.html 404 Not Found");
+
+        assert_eq!(reporter.summary(), ["3 more similar errors: timeout"]);
+        assert!(reporter.summary().is_empty());
+    }
+}
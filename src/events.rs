@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use futures::future::LocalBoxFuture;
+use tokio::sync::mpsc;
+
+use crate::{FileResult, PostSummary};
+
+/// Events emitted while posting files to Solr, see solr_post_with_events
+#[derive(Debug)]
+pub enum PostEvent {
+    /// scanning the directory for files to index has started
+    ScanStarted { directory_path: PathBuf },
+
+    /// scanning finished, total files will be posted and skipped files were filtered out by the content regexes
+    ScanFinished { total: usize, skipped: usize },
+
+    /// every document in the collection was deleted before posting, see PostConfig::clean
+    Cleaned,
+
+    /// a file was queued to be posted
+    FileQueued { path: PathBuf },
+
+    /// a file was indexed by Solr
+    FileIndexed(FileResult),
+
+    /// a file failed to be read or posted, or Solr responded with a non 2xx status, see FileResult::error
+    FileFailed(FileResult),
+
+    /// the document of a removed file was deleted, see solr_watch
+    FileDeleted { path: PathBuf },
+
+    /// the posted documents were committed
+    Committed,
+
+    /// the collection was optimized, see PostConfig::optimize_after
+    Optimized,
+
+    /// too many files failed and the uncommitted documents were rolled back, see PostConfig::rollback_on_failures
+    RolledBack,
+
+    /// the documents of files that no longer exist were deleted, see PostConfig::sync
+    Synced { deleted: usize },
+
+    /// the committed documents were counted, see PostConfig::verify
+    Verified { found: usize },
+
+    /// posting is complete
+    Finished(PostSummary),
+}
+
+/// Receives the events of a run, implemented by each flavor of the public API
+/// e.g. the boxed callbacks of solr_post or the channel of solr_post_with_events
+pub(crate) trait EventSink {
+    fn emit(&mut self, event: PostEvent) -> LocalBoxFuture<'_, ()>;
+}
+
+/// Delivers the events over a tokio mpsc channel
+pub(crate) struct ChannelSink {
+    pub(crate) sender: mpsc::Sender<PostEvent>,
+}
+
+impl EventSink for ChannelSink {
+    fn emit(&mut self, event: PostEvent) -> LocalBoxFuture<'_, ()> {
+        Box::pin(async move {
+            // keep posting even if the receiver was dropped, the summary is still returned
+            let _ = self.sender.send(event).await;
+        })
+    }
+}
@@ -0,0 +1,35 @@
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::{FileResult, SolrPostError};
+
+/// write the extracted content and metadata of the file to <output>/<relative_path>.json,
+/// pretty printed when Solr answered with JSON
+pub(crate) fn save_extracted(
+    output: &Path,
+    relative_path: &Path,
+    result: &FileResult,
+) -> Result<(), SolrPostError> {
+    let mut file_name = relative_path.as_os_str().to_owned();
+    file_name.push(".json");
+    let path = output.join(file_name);
+
+    let content = match serde_json::from_str::<Value>(&result.response_body) {
+        Ok(response) => {
+            serde_json::to_string_pretty(&response).expect("JSON values always serialize")
+        }
+        Err(_) => result.response_body.clone(),
+    };
+
+    let write = || {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, content)
+    };
+    write().map_err(|source| SolrPostError::Io {
+        path: path.clone(),
+        source,
+    })
+}
@@ -0,0 +1,78 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Value};
+
+use crate::{FileResult, SolrPostError};
+
+/// The files that failed in a run, one JSON object per line with the path, HTTP status, error, and Solr
+/// response. Written as the files fail so the report is complete even if the run is aborted
+pub(crate) struct FailureReport {
+    file: File,
+    path: PathBuf,
+}
+
+impl FailureReport {
+    /// the paths of the failed files in a report written by a previous run
+    pub(crate) fn files(path: &Path) -> Result<HashSet<String>, SolrPostError> {
+        let content = fs::read_to_string(path).map_err(|source| SolrPostError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let failure: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+                failure["path"]
+                    .as_str()
+                    .map(String::from)
+                    .ok_or_else(|| format!("missing path in {}", line))
+            })
+            .collect::<Result<_, String>>()
+            .map_err(|message| SolrPostError::InvalidReport {
+                path: path.to_path_buf(),
+                message,
+            })
+    }
+
+    /// create the report, replacing the report of a previous run
+    pub(crate) fn create(path: &Path) -> Result<Self, SolrPostError> {
+        let file = File::create(path).map_err(|source| SolrPostError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        Ok(FailureReport {
+            file,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// append a failed file to the report
+    pub(crate) fn record(&mut self, failed: &FileResult) -> Result<(), SolrPostError> {
+        let failure = json!({
+            "path": failed.path,
+            "status": failed.status.map(|status| status.as_u16()),
+            "error": failed.error.as_ref().map(|e| e.to_string()),
+            "response": failed.response_body,
+            "trace": match &failed.error {
+                Some(SolrPostError::Response {
+                    solr_error: Some(solr_error),
+                    ..
+                }) => solr_error.trace.as_deref(),
+                _ => None,
+            },
+        });
+
+        writeln!(self.file, "{}", failure).map_err(|source| SolrPostError::Io {
+            path: self.path.clone(),
+            source,
+        })
+    }
+}
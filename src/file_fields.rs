@@ -0,0 +1,123 @@
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+
+use crate::{PostConfig, SolrPostError};
+
+/// the fields read from the file system for the file: its last_modified and created dates with
+/// PostConfig::file_dates, its file_size_bytes with PostConfig::file_size, and its content_hash with
+/// PostConfig::content_hash
+pub(crate) async fn read_file_fields(
+    config: &PostConfig,
+    path: &Path,
+) -> Result<Vec<(String, String)>, SolrPostError> {
+    let mut fields = Vec::new();
+    if !config.file_dates && !config.file_size && !config.content_hash {
+        return Ok(fields);
+    }
+
+    let io_error = |source| SolrPostError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let metadata = tokio::fs::metadata(path).await.map_err(io_error)?;
+
+    if config.file_dates {
+        // not every file system records the creation time
+        let dates = [
+            ("last_modified", metadata.modified()),
+            ("created", metadata.created()),
+        ];
+        for (field, time) in dates {
+            if let Some(date) = time.ok().and_then(iso_8601) {
+                fields.push((field.to_string(), date));
+            }
+        }
+    }
+    if config.file_size {
+        fields.push(("file_size_bytes".to_string(), metadata.len().to_string()));
+    }
+    if config.content_hash {
+        let hash = hash_file(path).await.map_err(io_error)?;
+        fields.push(("content_hash".to_string(), hash));
+    }
+    Ok(fields)
+}
+
+/// hex sha256 of the file contents like the hashes of the state file, streamed so large files aren't
+/// read into memory
+async fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// the time in the ISO 8601 format of Solr date fields e.g. "2024-06-01T12:30:00Z", None before 1970
+fn iso_8601(time: SystemTime) -> Option<String> {
+    (time >= UNIX_EPOCH).then(|| humantime::format_rfc3339_seconds(time).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn dates_in_the_format_of_solr() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_717_245_000);
+        assert_eq!(iso_8601(time).unwrap(), "2024-06-01T12:30:00Z");
+
+        // Solr dates have no fractional seconds
+        let time = UNIX_EPOCH + Duration::from_millis(1_717_245_000_999);
+        assert_eq!(iso_8601(time).unwrap(), "2024-06-01T12:30:00Z");
+
+        assert_eq!(iso_8601(UNIX_EPOCH).unwrap(), "1970-01-01T00:00:00Z");
+        assert_eq!(iso_8601(UNIX_EPOCH - Duration::from_secs(1)), None);
+    }
+
+    #[tokio::test]
+    async fn fields_of_the_file() {
+        let path = std::env::temp_dir().join(format!("solr_post_{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "hello").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(1_717_245_000))
+            .unwrap();
+
+        let config = PostConfig {
+            file_dates: true,
+            file_size: true,
+            content_hash: true,
+            ..PostConfig::default()
+        };
+        let fields = read_file_fields(&config, &path).await;
+        let none = read_file_fields(&PostConfig::default(), &path).await;
+        std::fs::remove_file(&path).unwrap();
+
+        let fields = fields.unwrap();
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(field("last_modified"), Some("2024-06-01T12:30:00Z"));
+        assert_eq!(field("file_size_bytes"), Some("5"));
+        assert_eq!(
+            field("content_hash"),
+            Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+        assert!(none.unwrap().is_empty());
+    }
+}
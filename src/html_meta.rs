@@ -0,0 +1,238 @@
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::SolrPostError;
+
+/// the <title> element of a page
+static TITLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<title\b[^>]*>(.*?)</title\s*>").unwrap());
+
+/// the attributes of an <html>, <meta>, or <link> tag e.g. <meta name="description" content="...">
+static TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)<(html|meta|link)\b([^>]*)>").unwrap());
+
+/// an attribute of a tag with a double quoted, single quoted, or unquoted value
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)([a-zA-Z_:][-a-zA-Z0-9_:.]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#)
+        .unwrap()
+});
+
+/// the end of the <head> element, the metadata is only looked for before it
+static HEAD_END: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)</head\s*>").unwrap());
+
+/// true for the HTML files e.g. "index.html" or "page.htm"
+pub(crate) fn is_html(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            ["html", "htm", "xhtml"]
+                .iter()
+                .any(|html| extension.eq_ignore_ascii_case(html))
+        })
+}
+
+/// read the HTML file and parse the metadata of its head, see PostConfig::html_metadata
+pub(crate) async fn read_html_metadata(
+    path: &Path,
+) -> Result<Vec<(String, String)>, SolrPostError> {
+    let content = tokio::fs::read(path)
+        .await
+        .map_err(|source| SolrPostError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+    Ok(html_metadata(&String::from_utf8_lossy(&content)))
+}
+
+/// the title, meta description, canonical URL, og: properties, and lang of the page as fields
+/// e.g. ("title", "Home"), ("og_image", "https://example.com/logo.png"), ("lang", "en")
+fn html_metadata(html: &str) -> Vec<(String, String)> {
+    let head = match HEAD_END.find(html) {
+        Some(head_end) => &html[..head_end.start()],
+        None => html,
+    };
+
+    let mut fields = Vec::new();
+    if let Some(title) = TITLE.captures(head) {
+        let title = collapse_whitespace(&unescape_html(&title[1]));
+        if !title.is_empty() {
+            fields.push(("title".to_string(), title));
+        }
+    }
+
+    let mut lang = None;
+    for tag in TAG.captures_iter(head) {
+        let attributes: Vec<(String, String)> = ATTRIBUTE
+            .captures_iter(&tag[2])
+            .map(|attribute| {
+                let value = attribute
+                    .get(2)
+                    .or(attribute.get(3))
+                    .or(attribute.get(4))
+                    .map_or("", |value| value.as_str());
+                (attribute[1].to_ascii_lowercase(), unescape_html(value))
+            })
+            .collect();
+        let attribute = |name: &str| {
+            attributes
+                .iter()
+                .find(|(attribute, _)| attribute == name)
+                .map(|(_, value)| value.trim())
+        };
+
+        let field = match tag[1].to_ascii_lowercase().as_str() {
+            "html" => {
+                lang = lang.or(attribute("lang").map(str::to_string));
+                continue;
+            }
+            "meta" => match (
+                attribute("name"),
+                attribute("property"),
+                attribute("content"),
+            ) {
+                (Some(name), _, Some(content)) if name.eq_ignore_ascii_case("description") => {
+                    ("description".to_string(), content)
+                }
+                (_, Some(property), Some(content))
+                    if property.len() > 3
+                        && property
+                            .get(..3)
+                            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("og:")) =>
+                {
+                    let name = property[3..].to_ascii_lowercase().replace([':', '-'], "_");
+                    (format!("og_{}", name), content)
+                }
+                _ => continue,
+            },
+            _ => match (attribute("rel"), attribute("href")) {
+                (Some(rel), Some(href))
+                    if rel
+                        .split_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("canonical")) =>
+                {
+                    ("canonical_url".to_string(), href)
+                }
+                _ => continue,
+            },
+        };
+
+        if !field.1.is_empty() {
+            fields.push((field.0, field.1.to_string()));
+        }
+    }
+
+    if let Some(lang) = lang.filter(|lang| !lang.is_empty()) {
+        fields.push(("lang".to_string(), lang));
+    }
+    fields
+}
+
+fn collapse_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// decode the character references of an attribute value or the title e.g. "&amp;" or "&#8217;"
+fn unescape_html(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest.find(';').and_then(|end| {
+            let character = match &rest[1..end] {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                reference => {
+                    let code = match reference.strip_prefix('#') {
+                        Some(hex) if hex.starts_with(['x', 'X']) => {
+                            u32::from_str_radix(&hex[1..], 16).ok()
+                        }
+                        Some(decimal) => decimal.parse().ok(),
+                        None => None,
+                    };
+                    code.and_then(char::from_u32)
+                }
+            };
+            character.map(|character| (character, end))
+        });
+
+        match decoded {
+            Some((character, end)) => {
+                unescaped.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_of_the_head() {
+        let html = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <title>
+    Home &amp; Garden
+  </title>
+  <META NAME="Description" CONTENT="Tools &#8217;n&#x27; plants">
+  <meta property="og:image" content='https://example.com/logo.png'>
+  <meta property="og:image:width" content=1200>
+  <meta name="viewport" content="width=device-width">
+  <link rel="alternate canonical" href="https://example.com/">
+</head>
+<body><meta name="description" content="ignored"></body>
+</html>"#;
+        let fields = html_metadata(html);
+        let fields: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|(field, value)| (field.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("title", "Home & Garden"),
+                ("description", "Tools \u{2019}n' plants"),
+                ("og_image", "https://example.com/logo.png"),
+                ("og_image_width", "1200"),
+                ("canonical_url", "https://example.com/"),
+                ("lang", "en"),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_values_are_left_out() {
+        let html = r#"<html lang=""><title> </title><meta name="description" content=""></html>"#;
+        assert!(html_metadata(html).is_empty());
+    }
+
+    #[test]
+    fn unknown_references_are_kept() {
+        assert_eq!(unescape_html("a &copy; b & c"), "a &copy; b & c");
+        assert_eq!(unescape_html("&lt;p&gt;&nbsp;"), "<p>\u{a0}");
+    }
+
+    #[test]
+    fn html_files() {
+        assert!(is_html(Path::new("index.HTML")));
+        assert!(is_html(Path::new("page.htm")));
+        assert!(!is_html(Path::new("notes.md")));
+    }
+}
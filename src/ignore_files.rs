@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::Gitignore;
+
+/// the ignore file of solr_post, with the .gitignore syntax, for files to keep in git but out of Solr
+pub const SOLRIGNORE_FILE: &str = ".solrignore";
+
+/// the ignore files read in each directory, a .solrignore takes precedence over the .gitignore next to it
+const IGNORE_FILES: [&str; 2] = [".gitignore", SOLRIGNORE_FILE];
+
+/// The .gitignore and .solrignore files of the directories above the files, see PostConfig::respect_ignore_files.
+/// each directory's ignore files are only read once
+#[derive(Default)]
+pub(crate) struct IgnoreFiles {
+    /// the ignore files of a directory, none when it has no ignore files
+    directories: HashMap<PathBuf, Vec<Gitignore>>,
+}
+
+impl IgnoreFiles {
+    /// true if the file is ignored by the ignore files of its directory and the directories above it up to
+    /// the root of its git repository. like git, the ignore files of a deeper directory take precedence and
+    /// a file in an ignored directory is ignored
+    pub(crate) fn is_ignored(&mut self, path: &Path) -> bool {
+        let Ok(path) = std::path::absolute(path) else {
+            return false;
+        };
+
+        let mut ancestors = Vec::new();
+        for directory in path.ancestors().skip(1) {
+            ancestors.push(directory);
+            if directory.join(".git").exists() {
+                break;
+            }
+        }
+
+        let mut ignored = false;
+        for directory in ancestors.into_iter().rev() {
+            for ignore_file in self.ignore_files(directory) {
+                let matched = ignore_file.matched_path_or_any_parents(&path, false);
+                if matched.is_ignore() {
+                    ignored = true;
+                } else if matched.is_whitelist() {
+                    ignored = false;
+                }
+            }
+        }
+        ignored
+    }
+
+    fn ignore_files(&mut self, directory: &Path) -> &[Gitignore] {
+        self.directories
+            .entry(directory.to_path_buf())
+            .or_insert_with(|| {
+                IGNORE_FILES
+                    .iter()
+                    .map(|ignore_file| directory.join(ignore_file))
+                    .filter(|ignore_file| ignore_file.is_file())
+                    // the invalid lines are skipped like git does, the rest of the file still applies
+                    .map(|ignore_file| Gitignore::new(ignore_file).0)
+                    .collect()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// a git repository with a site directory, removed when dropped
+    struct Repository(PathBuf);
+
+    impl Repository {
+        fn new(files: &[(&str, &str)]) -> Self {
+            let root = std::env::temp_dir().join(format!("solr_post_{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(root.join(".git")).unwrap();
+            for (path, content) in files {
+                let path = root.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, content).unwrap();
+            }
+            Repository(root)
+        }
+
+        fn is_ignored(&self, path: &str) -> bool {
+            IgnoreFiles::default().is_ignored(&self.0.join(path))
+        }
+    }
+
+    impl Drop for Repository {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn gitignore_of_the_repository_root() {
+        let repository = Repository::new(&[(".gitignore", "build/\n*.log\n")]);
+        assert!(repository.is_ignored("site/build/page.html"));
+        assert!(repository.is_ignored("site/app.log"));
+        assert!(!repository.is_ignored("site/page.html"));
+    }
+
+    #[test]
+    fn deeper_ignore_files_take_precedence() {
+        let repository = Repository::new(&[
+            (".gitignore", "*.log\n"),
+            ("site/sub/.gitignore", "!keep.log\n"),
+        ]);
+        assert!(!repository.is_ignored("site/sub/keep.log"));
+        assert!(repository.is_ignored("site/sub/other.log"));
+        assert!(repository.is_ignored("site/keep.log"));
+    }
+
+    #[test]
+    fn solrignore_takes_precedence_over_gitignore() {
+        let repository = Repository::new(&[
+            ("site/.gitignore", "generated/\n"),
+            ("site/.solrignore", "drafts/\n!generated/sitemap.xml\n"),
+        ]);
+        assert!(repository.is_ignored("site/drafts/page.html"));
+        assert!(repository.is_ignored("site/generated/data.json"));
+        assert!(!repository.is_ignored("site/generated/sitemap.xml"));
+    }
+
+    #[test]
+    fn ignore_files_above_the_repository_are_not_read() {
+        let repository = Repository::new(&[(".gitignore", "*.html\n")]);
+        let nested = Repository(repository.0.join("nested"));
+        fs::create_dir_all(nested.0.join(".git")).unwrap();
+        assert!(!nested.is_ignored("page.html"));
+    }
+}
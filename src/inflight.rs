@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// permits are counted in KiB so limits larger than u32::MAX bytes can be expressed
+const PERMIT_SIZE: u64 = 1024;
+
+/// Caps the sum of the body sizes of the requests in flight, a request waits until enough
+/// of the earlier requests have completed. A file larger than the limit is sent on its own
+pub(crate) struct InflightBytes {
+    semaphore: Arc<Semaphore>,
+
+    /// the total number of permits, the limit in KiB
+    permits: u32,
+}
+
+impl InflightBytes {
+    pub(crate) fn new(max_inflight_bytes: u64) -> Self {
+        let permits = max_inflight_bytes
+            .div_ceil(PERMIT_SIZE)
+            .clamp(1, u32::MAX as u64) as u32;
+
+        InflightBytes {
+            semaphore: Arc::new(Semaphore::new(permits as usize)),
+            permits,
+        }
+    }
+
+    /// wait until the body of this size fits in the limit, the bytes are released when the permit is dropped
+    pub(crate) async fn acquire(&self, body_len: u64) -> OwnedSemaphorePermit {
+        let permits = body_len.div_ceil(PERMIT_SIZE).clamp(1, self.permits as u64) as u32;
+
+        self.semaphore
+            .clone()
+            .acquire_many_owned(permits)
+            .await
+            .expect("the in flight bytes semaphore is never closed")
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::SolrPostError;
+
+/// The files indexed so far by a run, one absolute path per line, appended as each file is indexed
+/// so an interrupted run can be resumed without posting them again
+pub(crate) struct Journal {
+    file: File,
+    path: PathBuf,
+}
+
+impl Journal {
+    /// the files recorded by an interrupted run, a missing journal means nothing was indexed
+    pub(crate) fn completed(path: &Path) -> Result<HashSet<String>, SolrPostError> {
+        match fs::read_to_string(path) {
+            // a partially written last line doesn't match any file, that file is just posted again
+            Ok(content) => Ok(content.lines().map(String::from).collect()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HashSet::new()),
+            Err(source) => Err(SolrPostError::Io {
+                path: path.to_path_buf(),
+                source,
+            }),
+        }
+    }
+
+    /// open the journal, appending to it when resuming otherwise starting a new one
+    pub(crate) fn open(path: &Path, resume: bool) -> Result<Self, SolrPostError> {
+        let io_error = |source| SolrPostError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        let mut file = File::options()
+            .create(true)
+            .append(resume)
+            .read(true)
+            .write(true)
+            .truncate(!resume)
+            .open(path)
+            .map_err(io_error)?;
+
+        // end a partially written last line so the next file isn't appended to it
+        if resume && !ends_with_newline(&mut file).map_err(io_error)? {
+            writeln!(file).map_err(io_error)?;
+        }
+
+        Ok(Journal {
+            file,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// record an indexed file, written straight to the file so it survives the process being killed
+    pub(crate) fn record(&mut self, indexed: &Path) -> Result<(), SolrPostError> {
+        writeln!(self.file, "{}", indexed.display()).map_err(|source| SolrPostError::Io {
+            path: self.path.clone(),
+            source,
+        })
+    }
+
+    /// remove the journal once the run is complete, there is nothing left to resume
+    pub(crate) fn remove(self) -> Result<(), SolrPostError> {
+        fs::remove_file(&self.path).map_err(|source| SolrPostError::Io {
+            path: self.path,
+            source,
+        })
+    }
+}
+
+/// true if the file is empty or its last byte is a newline
+fn ends_with_newline(file: &mut File) -> io::Result<bool> {
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok(true);
+    }
+
+    let mut last = [0];
+    file.seek(SeekFrom::Start(len - 1))?;
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a journal path in a temporary directory, removed when dropped
+    struct JournalPath(PathBuf);
+
+    impl JournalPath {
+        fn new() -> Self {
+            let directory =
+                std::env::temp_dir().join(format!("solr_post_{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&directory).unwrap();
+            JournalPath(directory.join("journal"))
+        }
+    }
+
+    impl Drop for JournalPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(self.0.parent().unwrap());
+        }
+    }
+
+    fn record(path: &Path, resume: bool, files: &[&str]) {
+        let mut journal = Journal::open(path, resume).unwrap();
+        for file in files {
+            journal.record(Path::new(file)).unwrap();
+        }
+    }
+
+    #[test]
+    fn recorded_files_are_completed() {
+        let path = JournalPath::new();
+        assert!(Journal::completed(&path.0).unwrap().is_empty());
+
+        record(&path.0, false, &["/site/a.html", "/site/b.html"]);
+        assert_eq!(
+            Journal::completed(&path.0).unwrap(),
+            HashSet::from(["/site/a.html".to_string(), "/site/b.html".to_string()])
+        );
+    }
+
+    #[test]
+    fn resuming_appends_and_a_new_run_starts_over() {
+        let path = JournalPath::new();
+        record(&path.0, false, &["/site/a.html"]);
+        record(&path.0, true, &["/site/b.html"]);
+        assert_eq!(Journal::completed(&path.0).unwrap().len(), 2);
+
+        record(&path.0, false, &["/site/c.html"]);
+        assert_eq!(
+            Journal::completed(&path.0).unwrap(),
+            HashSet::from(["/site/c.html".to_string()])
+        );
+    }
+
+    #[test]
+    fn a_truncated_last_line_is_tolerated() {
+        let path = JournalPath::new();
+        fs::write(&path.0, "/site/a.html\n/site/b.ht").unwrap();
+
+        // the file of the partially written line is posted again
+        let completed = Journal::completed(&path.0).unwrap();
+        assert!(completed.contains("/site/a.html"));
+        assert!(!completed.contains("/site/b.html"));
+
+        // resuming appends after the partial line
+        record(&path.0, true, &["/site/c.html"]);
+        assert!(Journal::completed(&path.0)
+            .unwrap()
+            .contains("/site/a.html"));
+    }
+
+    #[test]
+    fn removed_once_the_run_is_complete() {
+        let path = JournalPath::new();
+        Journal::open(&path.0, false).unwrap().remove().unwrap();
+        assert!(!path.0.exists());
+    }
+}
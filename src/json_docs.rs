@@ -0,0 +1,26 @@
+use std::{fs::File, io::Read, path::Path};
+
+/// the top level keys of Solr's command-style JSON e.g. {"add": {"doc": {...}}, "commit": {}}
+const COMMANDS: &[&str] = &["add", "delete", "commit", "optimize", "rollback"];
+
+/// true if the file holds command-style JSON, which the plain /update handler takes, rather than documents.
+/// only the first key is read, an unreadable file counts as documents and fails when it is posted
+pub(crate) fn is_command_style(path: &Path) -> bool {
+    let mut prefix = Vec::with_capacity(64);
+    let read = File::open(path).and_then(|file| file.take(64).read_to_end(&mut prefix));
+    read.is_ok() && first_key(&prefix).is_some_and(|key| COMMANDS.contains(&key))
+}
+
+/// the first key of a JSON object e.g. "add" of `{ "add": ...`
+fn first_key(json: &[u8]) -> Option<&str> {
+    let json = std::str::from_utf8(json).ok().or_else(|| {
+        // the prefix may end inside a multibyte character
+        (1..4).find_map(|cut| std::str::from_utf8(&json[..json.len().saturating_sub(cut)]).ok())
+    })?;
+    let object = json
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .strip_prefix('{')?;
+    let key = object.trim_start().strip_prefix('"')?;
+    key.split_once('"').map(|(key, _)| key)
+}
@@ -0,0 +1,245 @@
+use std::ffi::c_void;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use base64::prelude::*;
+use libloading::Library;
+use log::{debug, warn};
+use reqwest::Url;
+
+/// the GSS-API library of MIT Kerberos or Heimdal, loaded on first use so building doesn't need the
+/// Kerberos development files
+const GSSAPI_LIBRARIES: &[&str] = &[
+    "libgssapi_krb5.so.2",
+    "libgssapi_krb5.so",
+    "libgssapi.so.3",
+    "/System/Library/Frameworks/GSS.framework/GSS",
+];
+
+/// GSS_C_NT_HOSTBASED_SERVICE 1.2.840.113554.1.2.1.4, the "HTTP@host" service name type
+const NT_HOSTBASED_SERVICE: &[u8] = b"\x2a\x86\x48\x86\xf7\x12\x01\x02\x01\x04";
+
+/// the SPNEGO mechanism 1.3.6.1.5.5.2
+const SPNEGO_MECHANISM: &[u8] = b"\x2b\x06\x01\x05\x05\x02";
+
+/// GSS_C_MUTUAL_FLAG
+const MUTUAL_FLAG: u32 = 2;
+
+/// GSS_C_GSS_CODE and GSS_C_MECH_CODE of gss_display_status
+const GSS_CODE: i32 = 1;
+const MECH_CODE: i32 = 2;
+
+/// the routine and calling errors of a major status, the low bits are supplementary info
+const ERROR_MASK: u32 = 0xffff_0000;
+
+#[repr(C)]
+struct Buffer {
+    length: usize,
+    value: *mut c_void,
+}
+
+#[repr(C)]
+struct Oid {
+    length: u32,
+    elements: *mut c_void,
+}
+
+type ImportName =
+    unsafe extern "C" fn(*mut u32, *const Buffer, *const Oid, *mut *mut c_void) -> u32;
+type InitSecContext = unsafe extern "C" fn(
+    *mut u32,
+    *mut c_void,
+    *mut *mut c_void,
+    *mut c_void,
+    *const Oid,
+    u32,
+    u32,
+    *const c_void,
+    *const Buffer,
+    *mut *const Oid,
+    *mut Buffer,
+    *mut u32,
+    *mut u32,
+) -> u32;
+type ReleaseBuffer = unsafe extern "C" fn(*mut u32, *mut Buffer) -> u32;
+type ReleaseName = unsafe extern "C" fn(*mut u32, *mut *mut c_void) -> u32;
+type DeleteSecContext = unsafe extern "C" fn(*mut u32, *mut *mut c_void, *mut Buffer) -> u32;
+type DisplayStatus =
+    unsafe extern "C" fn(*mut u32, u32, i32, *const Oid, *mut u32, *mut Buffer) -> u32;
+
+/// the GSS-API functions the SPNEGO token needs
+struct GssApi {
+    import_name: ImportName,
+    init_sec_context: InitSecContext,
+    release_buffer: ReleaseBuffer,
+    release_name: ReleaseName,
+    delete_sec_context: DeleteSecContext,
+    display_status: DisplayStatus,
+
+    /// keeps the functions loaded
+    _library: Library,
+}
+
+impl GssApi {
+    fn load() -> Result<Self, String> {
+        let library = GSSAPI_LIBRARIES
+            .iter()
+            // SAFETY: the GSS-API libraries have no initialization routines with preconditions
+            .find_map(|name| unsafe { Library::new(name) }.ok())
+            .ok_or_else(|| String::from("the GSS-API library (libgssapi_krb5) is not installed"))?;
+
+        // SAFETY: the signatures match RFC 2744
+        unsafe {
+            Ok(GssApi {
+                import_name: symbol(&library, b"gss_import_name\0")?,
+                init_sec_context: symbol(&library, b"gss_init_sec_context\0")?,
+                release_buffer: symbol(&library, b"gss_release_buffer\0")?,
+                release_name: symbol(&library, b"gss_release_name\0")?,
+                delete_sec_context: symbol(&library, b"gss_delete_sec_context\0")?,
+                display_status: symbol(&library, b"gss_display_status\0")?,
+                _library: library,
+            })
+        }
+    }
+
+    /// the first SPNEGO token for the HTTP service of the host, from the ticket cache of kinit
+    fn token(&self, host: &str) -> Result<Vec<u8>, String> {
+        let service = format!("HTTP@{}", host);
+        let mut minor = 0;
+
+        // SAFETY: every pointer outlives the call, the outputs are released below
+        unsafe {
+            let name_buffer = Buffer {
+                length: service.len(),
+                value: service.as_ptr() as *mut c_void,
+            };
+            let name_type = Oid {
+                length: NT_HOSTBASED_SERVICE.len() as u32,
+                elements: NT_HOSTBASED_SERVICE.as_ptr() as *mut c_void,
+            };
+            let mut name = ptr::null_mut();
+            let major = (self.import_name)(&mut minor, &name_buffer, &name_type, &mut name);
+            if major & ERROR_MASK != 0 {
+                return Err(self.status(major, minor));
+            }
+
+            let mechanism = Oid {
+                length: SPNEGO_MECHANISM.len() as u32,
+                elements: SPNEGO_MECHANISM.as_ptr() as *mut c_void,
+            };
+            let mut context = ptr::null_mut();
+            let mut output = Buffer {
+                length: 0,
+                value: ptr::null_mut(),
+            };
+            let major = (self.init_sec_context)(
+                &mut minor,
+                ptr::null_mut(),
+                &mut context,
+                name,
+                &mechanism,
+                MUTUAL_FLAG,
+                0,
+                ptr::null(),
+                ptr::null(),
+                ptr::null_mut(),
+                &mut output,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+            let result = match major & ERROR_MASK {
+                0 if output.value.is_null() => Err(String::from("empty SPNEGO token")),
+                0 => Ok(
+                    std::slice::from_raw_parts(output.value as *const u8, output.length).to_vec(),
+                ),
+                _ => Err(self.status(major, minor)),
+            };
+
+            // the server's reply token isn't checked, so the context ends with the first token
+            let mut ignored = 0;
+            (self.release_buffer)(&mut ignored, &mut output);
+            (self.delete_sec_context)(&mut ignored, &mut context, ptr::null_mut());
+            (self.release_name)(&mut ignored, &mut name);
+
+            result
+        }
+    }
+
+    /// the messages of a failed call e.g. "No Kerberos credentials available"
+    fn status(&self, major: u32, minor: u32) -> String {
+        let mut messages = Vec::new();
+        for (status, status_type) in [(major, GSS_CODE), (minor, MECH_CODE)] {
+            let mut message_context = 0;
+            loop {
+                let mut ignored = 0;
+                let mut message = Buffer {
+                    length: 0,
+                    value: ptr::null_mut(),
+                };
+                // SAFETY: the message buffer is released after it is copied
+                unsafe {
+                    let major = (self.display_status)(
+                        &mut ignored,
+                        status,
+                        status_type,
+                        ptr::null(),
+                        &mut message_context,
+                        &mut message,
+                    );
+                    if major & ERROR_MASK != 0 || message.value.is_null() {
+                        break;
+                    }
+                    let text =
+                        std::slice::from_raw_parts(message.value as *const u8, message.length);
+                    messages.push(String::from_utf8_lossy(text).into_owned());
+                    (self.release_buffer)(&mut ignored, &mut message);
+                }
+                if message_context == 0 {
+                    break;
+                }
+            }
+        }
+        messages.join(", ")
+    }
+}
+
+/// SAFETY: T must be the function pointer type of the symbol
+unsafe fn symbol<T: Copy>(library: &Library, name: &[u8]) -> Result<T, String> {
+    library
+        .get::<T>(name)
+        .map(|symbol| *symbol)
+        .map_err(|e| e.to_string())
+}
+
+/// the library is loaded once, an error loading it is kept to report it on every request
+fn gssapi() -> Result<&'static GssApi, &'static str> {
+    static GSSAPI: OnceLock<Result<GssApi, String>> = OnceLock::new();
+    GSSAPI
+        .get_or_init(GssApi::load)
+        .as_ref()
+        .map_err(String::as_str)
+}
+
+/// the Authorization header value with a fresh SPNEGO token for the host of the URL e.g. "Negotiate YIIC...".
+/// a token is only accepted once, so each request gets its own. None if there is no ticket, the request
+/// is sent without it and Solr answers 401
+pub(crate) fn negotiate_header(url: &str) -> Option<String> {
+    let host = Url::parse(url).ok()?.host_str()?.to_string();
+
+    let token = gssapi()
+        .map_err(str::to_string)
+        .and_then(|gssapi| gssapi.token(&host));
+    match token {
+        Ok(token) => Some(format!("Negotiate {}", BASE64_STANDARD.encode(token))),
+        Err(message) => {
+            // every request fails the same way, warn about the first one
+            static WARNED: AtomicBool = AtomicBool::new(false);
+            match WARNED.swap(true, Ordering::Relaxed) {
+                false => warn!("no SPNEGO token for HTTP@{}: {}, run kinit?", host, message),
+                true => debug!("no SPNEGO token for HTTP@{}: {}", host, message),
+            }
+            None
+        }
+    }
+}
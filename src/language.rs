@@ -0,0 +1,49 @@
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use tokio::io::AsyncReadExt;
+
+use crate::charset::{is_text, to_utf8};
+use crate::SolrPostError;
+
+/// the bytes read from the start of a file to detect its language, enough text without reading large files
+const SAMPLE_SIZE: u64 = 64 * 1024;
+
+/// the script and style elements and the tags of an HTML page, removed so only its text is detected
+static MARKUP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<script\b.*?</script\s*>|<style\b.*?</style\s*>|<!--.*?-->|<[^>]*>").unwrap()
+});
+
+/// the ISO 639-3 code of the language of a text file e.g. "eng", see PostConfig::detect_language.
+/// None for other files or when the language can't be detected reliably
+pub(crate) async fn detect_language(path: &Path) -> Result<Option<String>, SolrPostError> {
+    if !is_text(path) {
+        return Ok(None);
+    }
+
+    let io_error = |source| SolrPostError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let file = tokio::fs::File::open(path).await.map_err(io_error)?;
+    let mut sample = Vec::new();
+    file.take(SAMPLE_SIZE)
+        .read_to_end(&mut sample)
+        .await
+        .map_err(io_error)?;
+
+    // the sample can end in the middle of a UTF-8 character, other encodings are decoded
+    let text = match std::str::from_utf8(&sample) {
+        Ok(text) => text.to_string(),
+        Err(e) if e.error_len().is_none() => {
+            String::from_utf8_lossy(&sample[..e.valid_up_to()]).into_owned()
+        }
+        Err(_) => to_utf8(&sample),
+    };
+    let text = MARKUP.replace_all(&text, " ");
+
+    Ok(whatlang::detect(&text)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang().code().to_string()))
+}
@@ -1,21 +1,105 @@
 use std::{
-    collections::HashSet,
-    fs::File,
-    io::Read,
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
-    sync::{Arc, RwLock},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use base64::prelude::*;
-use futures::StreamExt;
-use log::info;
-use mime_guess::from_path;
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+mod adaptive_concurrency;
+mod batch;
+#[cfg(feature = "blocking")]
+mod blocking;
+mod builder;
+mod charset;
+mod circuit_breaker;
+mod cluster;
+mod commit_scheduler;
+mod credentials;
+mod dry_run;
+mod error;
+mod error_reporter;
+mod events;
+mod extract;
+mod failure_report;
+mod file_fields;
+mod html_meta;
+mod ignore_files;
+mod inflight;
+mod journal;
+mod json_docs;
+#[cfg(feature = "kerberos")]
+mod kerberos;
+#[cfg(feature = "language")]
+mod language;
+#[cfg(feature = "markdown")]
+mod markdown;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "mmap")]
+mod mmap_scan;
+mod observer;
+mod path_fields;
+mod rate_limit;
+mod scan;
+mod sidecar;
+mod solr_response;
+mod split;
+mod state;
+#[cfg(feature = "statsd")]
+mod statsd;
+mod stream;
+mod sync;
+mod tls;
+#[cfg(feature = "tracing")]
+mod trace;
+mod verify;
+mod version;
+#[cfg(feature = "watch")]
+mod watch;
+mod zookeeper;
+
+use futures::{future::LocalBoxFuture, StreamExt};
+use log::{info, warn};
 use regex::Regex;
-use reqwest::{header, Client};
-use wax::{Glob, WalkEntry, WalkError};
+use reqwest::{header::HeaderMap, Url};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+#[cfg(feature = "blocking")]
+pub use crate::blocking::solr_post_blocking;
+pub use crate::builder::PostConfigBuilder;
+pub use crate::dry_run::{solr_dry_run, DryRun, PlannedRequest};
+pub use crate::error::{CommitUrlError, ConfigError, SolrPostError};
+pub use crate::events::PostEvent;
+pub use crate::ignore_files::SOLRIGNORE_FILE;
+pub use crate::observer::ProgressObserver;
+pub use crate::sidecar::DEFAULT_SIDECAR_SUFFIX;
+pub use crate::solr_response::SolrError;
+pub use crate::split::MAX_SPLIT_SIZE;
+pub use crate::stream::{solr_post_stream, FileResult};
+#[cfg(feature = "watch")]
+pub use crate::watch::solr_watch;
+
+use crate::circuit_breaker::{BreakerAction, CircuitBreaker};
+use crate::commit_scheduler::CommitScheduler;
+use crate::error_reporter::ErrorReporter;
+use crate::events::{ChannelSink, EventSink};
+use crate::failure_report::FailureReport;
+use crate::journal::Journal;
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+use crate::observer::ObserverSink;
+use crate::scan::scan_files;
+use crate::solr_response::error_for_status;
+use crate::state::{absolute, FileState, IndexState};
+#[cfg(feature = "statsd")]
+use crate::statsd::Statsd;
+use crate::stream::{build_client, post_files, PostContext, SolrClient};
 
 /// Configuration for posting files to Solr server
+#[derive(Clone)]
 pub struct PostConfig {
     /// the number of concurrent requests to make to the solr server defaults to 8
     pub concurrency: usize,
@@ -44,8 +128,387 @@ pub struct PostConfig {
     /// include only files who's content contains this regex pattern e.g. "index_me"
     pub include_regex: Option<Regex>,
 
-    /// basic auth user credentials e.g. "user:pass"
-    pub basic_auth_creds: Option<String>,
+    /// the credentials sent in the Authorization header of every request e.g. AuthScheme::Basic("user:pass")
+    pub auth: Option<AuthScheme>,
+
+    /// explicit Solr commit URL e.g. "http://localhost:8983/solr/my_collection/update?commit=true"
+    /// when not set it is derived from the update endpoint
+    pub commit_url: Option<String>,
+
+    /// tag every document with the run id of this invocation e.g. "literal.run_id=<uuid>" defaults to false
+    pub index_run_id: bool,
+
+    /// the field name the run id is indexed into defaults to run_id
+    pub run_id_field: String,
+
+    /// the run id of this invocation, a new UUID is generated per solr_post call when not set
+    pub run_id: Option<String>,
+
+    /// commit after this many documents have been posted since the last commit e.g. 1000
+    /// when set together with commit_interval whichever is reached first triggers the commit
+    pub commit_every: Option<usize>,
+
+    /// commit when this much time has passed since the last commit e.g. 30 seconds
+    /// when set together with commit_every whichever is reached first triggers the commit
+    pub commit_interval: Option<Duration>,
+
+    /// memory map files for the exclude/include content regex check instead of reading them into memory
+    /// this reduces peak memory when scanning large files, files that can't be mapped are read normally.
+    /// files must not be modified while they are being scanned, truncating a mapped file can crash the process
+    #[cfg(feature = "mmap")]
+    pub mmap_scan: bool,
+
+    /// send the document's `_version_` so Solr rejects stale updates with a 409 conflict defaults to false
+    /// the version is read from a sidecar file e.g. "page.html.version", or the `_version_` field of a JSON document.
+    /// conflicts are counted separately from failures
+    pub optimistic_concurrency: bool,
+
+    /// cancel the run when this token is cancelled, the requests in flight are finished but no new files are posted
+    pub cancellation_token: Option<CancellationToken>,
+
+    /// commit the documents that were already indexed when the run is cancelled defaults to false
+    pub commit_on_cancel: bool,
+
+    /// cap the sum of the body sizes of the requests in flight e.g. 512 MiB
+    /// a file larger than the cap is sent on its own, unlimited when not set
+    pub max_inflight_bytes: Option<u64>,
+
+    /// gzip encode the request bodies with `Content-Encoding: gzip` defaults to false
+    /// this cuts transfer time for large text corpora over slow links
+    pub compress_requests: bool,
+
+    /// give up connecting to the solr server after this long e.g. 10 seconds, no timeout when not set
+    pub connect_timeout: Option<Duration>,
+
+    /// give up on a single request after this long, from connecting until the response body is read
+    /// e.g. 60 seconds, no timeout when not set
+    pub request_timeout: Option<Duration>,
+
+    /// abort the whole run if it takes longer than this, nothing is committed e.g. 1 hour, no deadline when not set
+    pub deadline: Option<Duration>,
+
+    /// trip the circuit breaker after this many consecutive server errors (5xx or connection errors)
+    /// e.g. 20, the breaker is disabled when not set
+    pub circuit_breaker_threshold: Option<usize>,
+
+    /// how long to pause posting when the circuit breaker trips before trying the server again defaults to 30 seconds
+    pub circuit_breaker_wait: Duration,
+
+    /// how many times the circuit breaker may trip and pause before the run is aborted defaults to 0
+    pub circuit_breaker_retries: usize,
+
+    /// send at most this many requests per second across all concurrent requests e.g. 50.0
+    /// not limited when not set
+    pub max_requests_per_second: Option<f64>,
+
+    /// start at a low concurrency and ramp up or down based on response times and server errors
+    /// concurrency is the upper limit when this is set defaults to false
+    pub adaptive_concurrency: bool,
+
+    /// post json, jsonl, xml, and csv files to the /update handler in batches of this many files instead of
+    /// one extract request per file e.g. 500, the documents must contain their own ids
+    pub batch_size: Option<usize>,
+
+    /// start another request for a batch once its body would grow past this many bytes e.g. 10_000_000
+    /// a file bigger than this is still posted in one request. not limited when not set
+    pub batch_max_bytes: Option<usize>,
+
+    /// how the posted documents are committed defaults to CommitPolicy::Hard
+    pub commit: CommitPolicy,
+
+    /// optimize the collection with `optimize=true` once posting is complete defaults to false
+    pub optimize_after: bool,
+
+    /// merge down to at most this many segments when optimizing, Solr's default of 1 when not set
+    pub optimize_max_segments: Option<u32>,
+
+    /// once this many files have failed, stop posting and roll back the uncommitted documents instead of
+    /// committing them e.g. 1 rolls back on the first failure. commits made by commit_every /
+    /// commit_interval can't be rolled back
+    pub rollback_on_failures: Option<usize>,
+
+    /// after indexing, delete the documents under the directories whose file no longer exists defaults to false
+    pub sync: bool,
+
+    /// delete every document in the collection, or every one with the id_prefix, before posting. the delete
+    /// becomes visible with the final commit so searches see the old documents until then defaults to false
+    pub clean: bool,
+
+    /// record the mtime and content hash of the indexed files in this JSON file after each run
+    pub state_file: Option<PathBuf>,
+
+    /// skip the files whose contents haven't changed since they were recorded in state_file defaults to false
+    pub incremental: bool,
+
+    /// append each indexed file to this journal during the run, it is removed once the run completes
+    pub journal_file: Option<PathBuf>,
+
+    /// skip the files recorded in journal_file by an interrupted run, their documents are committed by
+    /// this run's commit defaults to false
+    pub resume: bool,
+
+    /// write the failed files with their errors to this JSON lines file, replacing the report of a previous run
+    pub error_report: Option<PathBuf>,
+
+    /// post only the files listed in this error report instead of scanning directory_path, e.g. to retry the
+    /// files that failed in the previous run
+    pub from_report: Option<PathBuf>,
+
+    /// abort the run without committing once this many files have failed, e.g. when the schema is wrong.
+    /// like rollback_on_failures, 1 aborts on the first failure. the requests in flight are finished and
+    /// PostEvent::Finished is emitted before SolrPostError::TooManyErrors is returned
+    pub max_errors: Option<usize>,
+
+    /// push the metrics of the run to this Prometheus Pushgateway when it finishes e.g. http://pushgateway:9091
+    #[cfg(feature = "metrics")]
+    pub metrics_push_url: Option<String>,
+
+    /// serve the metrics of the run for Prometheus to scrape on this address while the run lasts e.g. 0.0.0.0:9898
+    #[cfg(feature = "metrics")]
+    pub metrics_listen: Option<SocketAddr>,
+
+    /// send StatsD counters and timers for every document to this host:port e.g. localhost:8125
+    #[cfg(feature = "statsd")]
+    pub statsd_addr: Option<String>,
+
+    /// check the collection's admin/ping handler before scanning, to fail fast on a wrong collection,
+    /// bad credentials, or a Solr server that isn't running defaults to true
+    pub ping: bool,
+
+    /// poll the ping handler with backoff for up to this long before posting, until Solr is ready
+    /// e.g. when it is started at the same time as the indexer. implies ping
+    pub wait_for_solr: Option<Duration>,
+
+    /// after the final commit, count the documents in Solr and fail with SolrPostError::VerifyMismatch if
+    /// there are fewer than the indexed files. the count is limited to this run with index_run_id, otherwise
+    /// it is the whole collection. requires CommitPolicy::Hard or CommitPolicy::Soft defaults to false
+    pub verify: bool,
+
+    /// the ZooKeeper connection string of a SolrCloud cluster e.g. "zk1:2181,zk2:2181,zk3:2181/solr".
+    /// the live nodes hosting the collection are read from the cluster state and the posts are spread
+    /// across them, a node that refuses connections is skipped and its files are posted to the next node.
+    /// replaces the host and port of the update URL, the path is kept
+    pub zk_hosts: Option<String>,
+
+    /// the update URLs of the same collection on other Solr nodes e.g. ["http://solr2:8983/solr/my_collection/update"].
+    /// the requests are spread across these and update_url (or host and port), only the scheme, host and port of
+    /// these URLs are used. ignored when zk_hosts is set
+    pub update_urls: Vec<String>,
+
+    /// more collections every file is also indexed into in the same pass e.g. a staging collection next to the
+    /// live one. the commands (commit, clean, sync, ...) are sent to each of them and a file is only indexed once
+    /// every collection accepted it. the collection follows the /solr/ segment of the update URL
+    pub extra_collections: Vec<String>,
+
+    /// the `_route_` param sent with each document, for SolrCloud collections using the implicit router
+    /// where Solr can't pick the shard itself. DocumentRoute::PathComponent can't be combined with batch_size
+    pub route: Option<DocumentRoute>,
+
+    /// build the URLs from host, port, and collection with the v2 API paths e.g.
+    /// "http://localhost:8983/api/collections/my_collection/update/extract" instead of "/solr/my_collection/...",
+    /// for gateways that only expose the v2 API. an update_url in either form is used as is defaults to false
+    pub v2_api: bool,
+
+    /// a PEM file with the certificates of the CA that signed Solr's certificate e.g. an internal CA,
+    /// trusted next to the system's root certificates for https URLs
+    pub ca_cert: Option<PathBuf>,
+
+    /// DANGEROUS: accept any TLS certificate, including self-signed, expired, and ones for other hosts.
+    /// anyone on the network path can read and change the requests, only for dev clusters defaults to false
+    pub accept_invalid_certs: bool,
+
+    /// the client certificate presented for mutual TLS, a PEM certificate with client_key or a PKCS#12
+    /// archive (.p12 or .pfx) holding the certificate and the key
+    pub client_cert: Option<PathBuf>,
+
+    /// the PKCS#8 PEM private key of a PEM client_cert, read from client_cert itself when not set
+    pub client_key: Option<PathBuf>,
+
+    /// the password of a PKCS#12 client_cert, empty when not set
+    pub client_cert_password: Option<String>,
+
+    /// headers sent with every request e.g. an "X-Api-Key" an API gateway in front of Solr requires,
+    /// they replace the Authorization header of auth
+    pub extra_headers: HeaderMap,
+
+    /// the proxy every request goes through e.g. "http://proxy.corp:3128", credentials can be given in the URL.
+    /// when not set HTTP_PROXY, HTTPS_PROXY and NO_PROXY of the environment are used
+    pub proxy: Option<String>,
+
+    /// a login form posted before the first request, the session cookie it sets is sent with every request
+    /// e.g. for Solr behind an SSO proxy
+    pub login: Option<Login>,
+
+    /// how redirects of Solr or a load balancer in front of it are handled, follows up to 10 by default
+    pub redirect: RedirectPolicy,
+
+    /// connect to these addresses instead of resolving the host names with DNS, like curl --resolve
+    /// e.g. ("solr-0.solr.internal", 10.0.0.5:8983). the port of the address is ignored, the port of the URL is used
+    pub resolve: Vec<(String, SocketAddr)>,
+
+    /// the local address the connections to Solr are made from e.g. the address of the storage network
+    /// interface of a multi-homed host, the OS picks it when not set
+    pub local_address: Option<IpAddr>,
+
+    /// fields and values added to every document e.g. [("site", "docs"), ("locale", "en")], sent as
+    /// literal.<field>=<value> params to the extract handler and added to the documents of a batch
+    pub literals: Vec<(String, String)>,
+
+    /// params added to the query string of every update request like the -params of bin/post
+    /// e.g. [("uprefix", "attr_"), ("fmap.content", "text"), ("lowernames", "true")]
+    pub params: Vec<(String, String)>,
+
+    /// send extractOnly=true so Tika's extracted content and metadata come back in each
+    /// FileResult::response_body without indexing anything, e.g. to debug why a PDF indexes as garbage.
+    /// nothing is committed
+    pub extract_only: bool,
+
+    /// write the extracted content and metadata of each file of an extract_only run to
+    /// <extract_output>/<path below directory_path>.json
+    pub extract_output: Option<PathBuf>,
+
+    /// XHTML elements Tika extracts into a separate field named after the element, sent as the extract
+    /// handler's capture param e.g. ["div"] with the param fmap.div=content
+    pub capture: Vec<String>,
+
+    /// index the attributes of the XHTML elements into separate fields e.g. the href of links, captureAttr=true
+    pub capture_attr: bool,
+
+    /// only index the XHTML content matching the XPath expression e.g. "/xhtml:html/xhtml:body/xhtml:div//node()"
+    pub xpath: Option<String>,
+
+    /// post json, jsonl, xml, and csv files to the /update handler as Solr documents, one request per file,
+    /// instead of extracting them with Tika. the other files still go through the extract handler
+    pub structured_update: bool,
+
+    /// how the files are posted, through the extract handler by default
+    pub mode: PostMode,
+
+    /// the path the JSON of PostMode::JsonDocs is split into documents at, the split param e.g. "/exams"
+    pub json_split: Option<String>,
+
+    /// the fields PostMode::JsonDocs takes from the JSON, the f params e.g. ["id:/isbn", "title:/book/title"]
+    pub json_fields: Vec<String>,
+
+    /// the character the values of PostMode::Csv are separated by, the separator param. defaults to a tab
+    /// for .tsv files, and Solr's ',' for the others
+    pub csv_separator: Option<char>,
+
+    /// false if the first line of the CSV files is not a header with the field names, the header param
+    pub csv_header: bool,
+
+    /// the field names of the CSV columns, the fieldnames param. required without a header, and replaces
+    /// the names of the header otherwise. an empty name skips the column e.g. ["id", "", "title"]
+    pub csv_fieldnames: Vec<String>,
+
+    /// the number of lines skipped at the start of the CSV files before the header, the skipLines param
+    pub csv_skip_lines: Option<usize>,
+
+    /// the character that quotes the CSV values holding the separator, the encapsulator param e.g. '"'
+    pub csv_encapsulator: Option<char>,
+
+    /// post .jsonl and .ndjson files in chunks of this many documents, each chunk a JSON array posted to
+    /// the /update handler, or /update/json/docs in PostMode::JsonDocs. the file is read a line at a time
+    /// instead of being sent as one body, so files of any size can be posted e.g. 10000
+    pub jsonl_chunk_size: Option<usize>,
+
+    /// plain text files e.g. .txt and .log files larger than this many bytes are posted as several documents
+    /// of at most this many bytes, cut at line ends. the id of each part is the file's id with "#part" and
+    /// the part number, counting from 1, which is also indexed as the part field e.g. "/logs/app.log#part2"
+    pub split_large_files: Option<u64>,
+
+    /// index the YAML (---) or TOML (+++) front matter of .md and .markdown files as literal fields, one per
+    /// top level key e.g. title, tags, and date, and post the body after it as Markdown text or rendered to HTML
+    #[cfg(feature = "markdown")]
+    pub markdown: Option<MarkdownBody>,
+
+    /// parse the head of .html, .htm, and .xhtml files and send its metadata as literal fields: title,
+    /// description, canonical_url, og_<property> for the og: properties e.g. og_image, and lang.
+    /// the literals replace the values Tika extracts for the same fields
+    pub html_metadata: bool,
+
+    /// add the key/value pairs of the JSON object in a sidecar file next to each document as literal fields,
+    /// the sidecar is the document's path with this suffix e.g. ".meta.json" for "page.html.meta.json".
+    /// an array is a value per item. the sidecar files are not posted themselves, see DEFAULT_SIDECAR_SUFFIX
+    pub sidecar_suffix: Option<String>,
+
+    /// prepended to the id of every document posted through the extract handler, to keep the documents of
+    /// several sources apart in one collection e.g. "docs:" for "docs:/var/www/page.html".
+    /// clean, sync, and verify only touch the documents with the prefix
+    pub id_prefix: Option<String>,
+
+    /// the site the files are served from, the id of a file below strip_path is its URL under it and is also
+    /// indexed in the url field e.g. "https://example.com/" for "https://example.com/en/page.html"
+    pub base_url: Option<String>,
+
+    /// the directory the files are served from, removed from their paths before they are appended to the
+    /// base_url e.g. "./build" for "./build/en/page.html". defaults to directory_path
+    pub strip_path: Option<PathBuf>,
+
+    /// regexes matched against the path of each file below directory_path with / separators, the named
+    /// groups that match are sent as literal fields e.g. "^docs/(?P<lang>[a-z]{2})/" for lang=en
+    pub path_fields: Vec<Regex>,
+
+    /// the field names of the directories of each file below directory_path by depth, sent as literal fields.
+    /// an empty name skips the directory e.g. ["category", "subcategory"] for category=docs and
+    /// subcategory=en with "docs/en/guide.html"
+    pub directory_fields: Vec<String>,
+
+    /// send the modification time of each file as the last_modified field and its creation time, where the
+    /// file system records it, as the created field. ISO 8601 dates e.g. "2024-06-01T12:30:00Z"
+    pub file_dates: bool,
+
+    /// send the size of each file in bytes as the file_size_bytes field, the size on disk before compress.
+    /// a split file's parts all get the size of the whole file
+    pub file_size: bool,
+
+    /// send the hex sha256 of the contents of each file as the content_hash field, the same hash as the
+    /// state file, e.g. to find duplicates or check what is indexed
+    pub content_hash: bool,
+
+    /// detect the language of the text, HTML, and Markdown files from their first 64 KiB and send its ISO 639-3
+    /// code e.g. "eng" or "deu" as the language field, without the langid update processor in Solr.
+    /// nothing is sent when it can't be detected reliably
+    #[cfg(feature = "language")]
+    pub detect_language: bool,
+
+    /// the Content-Type of the files with these extensions instead of the one guessed from the extension,
+    /// keyed by the lowercase extension without the dot e.g. "log" -> "text/plain". Tika picks its parser by it
+    pub mime_overrides: HashMap<String, String>,
+
+    /// detect the encoding of the text files that aren't UTF-8 e.g. ISO-8859-1, Windows-1252, or Shift_JIS,
+    /// and post them transcoded to UTF-8. HTML pages declaring their charset are posted as they are, Tika
+    /// reads the declaration. the parts of split files are posted as they are
+    pub transcode: bool,
+
+    /// the globs of the files to post below directory_path instead of the file_extensions, a file matched by
+    /// any of them is posted e.g. ["content/**/{posts,pages}/*.html"]
+    pub globs: Vec<String>,
+
+    /// more directories to post along with directory_path in the same run with one commit, a file under
+    /// several of them is posted once. its path is relative to the first directory it is under
+    pub directory_paths: Vec<PathBuf>,
+
+    /// the globs of the paths below the directories to skip, matched while walking so an excluded directory
+    /// isn't descended into e.g. ["**/node_modules/**", "**/*.min.js"]
+    pub exclude_globs: Vec<String>,
+
+    /// skip the files whose path below the directories matches this regex without reading them, this takes
+    /// precedence over include_path_regex e.g. "-draft\.html$" or "^archive/"
+    pub exclude_path_regex: Option<Regex>,
+
+    /// include only the files whose path below the directories matches this regex e.g. "^(docs|blog)/"
+    pub include_path_regex: Option<Regex>,
+
+    /// skip the files ignored by the .gitignore and .solrignore files of their directories and the directories
+    /// above them up to the root of their git repository, with the .gitignore syntax. a .solrignore takes
+    /// precedence over the .gitignore next to it
+    pub respect_ignore_files: bool,
+
+    /// post exactly the files listed in this file, one path per line, instead of walking the directories e.g.
+    /// the files changed by a publish. a relative path is below directory_path, blank lines are skipped. the
+    /// extension, glob, path, ignore file, and content filters aren't applied
+    pub file_list: Option<PathBuf>,
 }
 
 // defaults for PostConfig
@@ -84,202 +547,1514 @@ impl Default for PostConfig {
             update_url: None,
             exclued_regex: None,
             include_regex: None,
-            basic_auth_creds: None,
+            auth: None,
+            commit_url: None,
+            index_run_id: false,
+            run_id_field: String::from("run_id"),
+            run_id: None,
+            commit_every: None,
+            commit_interval: None,
+            #[cfg(feature = "mmap")]
+            mmap_scan: false,
+            optimistic_concurrency: false,
+            cancellation_token: None,
+            commit_on_cancel: false,
+            max_inflight_bytes: None,
+            compress_requests: false,
+            connect_timeout: None,
+            request_timeout: None,
+            deadline: None,
+            circuit_breaker_threshold: None,
+            circuit_breaker_wait: Duration::from_secs(30),
+            circuit_breaker_retries: 0,
+            max_requests_per_second: None,
+            adaptive_concurrency: false,
+            batch_size: None,
+            batch_max_bytes: None,
+            commit: CommitPolicy::Hard,
+            optimize_after: false,
+            optimize_max_segments: None,
+            rollback_on_failures: None,
+            sync: false,
+            clean: false,
+            state_file: None,
+            incremental: false,
+            journal_file: None,
+            resume: false,
+            error_report: None,
+            from_report: None,
+            max_errors: None,
+            #[cfg(feature = "metrics")]
+            metrics_push_url: None,
+            #[cfg(feature = "metrics")]
+            metrics_listen: None,
+            #[cfg(feature = "statsd")]
+            statsd_addr: None,
+            ping: true,
+            wait_for_solr: None,
+            verify: false,
+            zk_hosts: None,
+            update_urls: Vec::new(),
+            extra_collections: Vec::new(),
+            route: None,
+            v2_api: false,
+            ca_cert: None,
+            accept_invalid_certs: false,
+            client_cert: None,
+            client_key: None,
+            client_cert_password: None,
+            extra_headers: HeaderMap::new(),
+            proxy: None,
+            login: None,
+            redirect: RedirectPolicy::default(),
+            resolve: Vec::new(),
+            local_address: None,
+            literals: Vec::new(),
+            params: Vec::new(),
+            extract_only: false,
+            extract_output: None,
+            capture: Vec::new(),
+            capture_attr: false,
+            xpath: None,
+            structured_update: false,
+            mode: PostMode::default(),
+            json_split: None,
+            json_fields: Vec::new(),
+            csv_separator: None,
+            csv_header: true,
+            csv_fieldnames: Vec::new(),
+            csv_skip_lines: None,
+            csv_encapsulator: None,
+            jsonl_chunk_size: None,
+            split_large_files: None,
+            #[cfg(feature = "markdown")]
+            markdown: None,
+            html_metadata: false,
+            sidecar_suffix: None,
+            id_prefix: None,
+            base_url: None,
+            strip_path: None,
+            path_fields: Vec::new(),
+            directory_fields: Vec::new(),
+            file_dates: false,
+            file_size: false,
+            content_hash: false,
+            #[cfg(feature = "language")]
+            detect_language: false,
+            mime_overrides: HashMap::new(),
+            transcode: false,
+            globs: Vec::new(),
+            directory_paths: Vec::new(),
+            exclude_globs: Vec::new(),
+            exclude_path_regex: None,
+            include_path_regex: None,
+            respect_ignore_files: false,
+            file_list: None,
+        }
+    }
+}
+
+impl PostConfig {
+    /// the directories the files are posted from, directory_path and the directory_paths
+    pub(crate) fn directories(&self) -> impl Iterator<Item = &PathBuf> {
+        std::iter::once(&self.directory_path).chain(&self.directory_paths)
+    }
+
+    /// the number of structured files posted to the /update handler in one request, None when they go
+    /// through the extract handler
+    pub(crate) fn update_batch_size(&self) -> Option<usize> {
+        self.batch_size.or(self.structured_update.then_some(1))
+    }
+
+    /// the Solr update endpoint files are posted to, update_url if set otherwise built from host, port, and collection
+    pub fn update_endpoint(&self) -> String {
+        match &self.update_url {
+            Some(url) => url.clone(),
+            None if self.v2_api => format!(
+                "http://{0}:{1}/api/collections/{2}/update/extract",
+                self.host, self.port, self.collection
+            ),
+            None => format!(
+                "http://{0}:{1}/solr/{2}/update/extract",
+                self.host, self.port, self.collection
+            ),
+        }
+    }
+
+    /// the URL used to commit the indexed documents.
+    /// if commit_url is set it is used as is, otherwise the sibling `update?commit=true`
+    /// (`update?softCommit=true` for CommitPolicy::Soft) of the
+    /// update endpoint is derived by keeping the scheme, host, port, and base path of the update URL
+    /// e.g. "http://localhost:8983/solr/my_collection/update/extract" -> "http://localhost:8983/solr/my_collection/update?commit=true"
+    pub fn commit_url(&self) -> Result<String, CommitUrlError> {
+        if let Some(commit_url) = &self.commit_url {
+            return Url::parse(commit_url)
+                .map(|url| url.to_string())
+                .map_err(|_| CommitUrlError::InvalidUrl(commit_url.clone()));
+        }
+
+        let mut url = self.update_handler_url()?;
+        match self.commit {
+            CommitPolicy::Soft => url.set_query(Some("softCommit=true")),
+            _ => url.set_query(Some("commit=true")),
+        }
+
+        Ok(url.to_string())
+    }
+
+    /// the URL used to optimize the collection, the update handler of the commit URL with `optimize=true`
+    /// e.g. "http://localhost:8983/solr/my_collection/update?optimize=true&maxSegments=4"
+    pub fn optimize_url(&self) -> Result<String, CommitUrlError> {
+        match self.optimize_max_segments {
+            Some(max_segments) => {
+                self.update_command_url(&format!("optimize=true&maxSegments={}", max_segments))
+            }
+            None => self.update_command_url("optimize=true"),
+        }
+    }
+
+    /// the URL used to roll back the uncommitted documents, the update handler of the commit URL with `rollback=true`
+    pub fn rollback_url(&self) -> Result<String, CommitUrlError> {
+        self.update_command_url("rollback=true")
+    }
+
+    /// the URL deletes are posted to, the update handler of the commit URL with the commit policy as the query
+    /// e.g. "http://localhost:8983/solr/my_collection/update?commit=true"
+    pub fn delete_url(&self) -> Result<String, CommitUrlError> {
+        match self.commit {
+            CommitPolicy::None => self.update_command_url(""),
+            CommitPolicy::Hard => self.update_command_url("commit=true"),
+            CommitPolicy::Soft => self.update_command_url("softCommit=true"),
+            CommitPolicy::Within(ms) => self.update_command_url(&format!("commitWithin={}", ms)),
+        }
+    }
+
+    /// the URL the clean delete is posted to, the update handler of the commit URL without committing
+    pub(crate) fn clean_url(&self) -> Result<String, CommitUrlError> {
+        self.update_command_url("")
+    }
+
+    /// the /select handler next to the update handler, used to find the indexed documents
+    /// e.g. "http://localhost:8983/solr/my_collection/update/extract" -> "http://localhost:8983/solr/my_collection/select"
+    pub(crate) fn select_url(&self) -> Result<String, CommitUrlError> {
+        let mut url = self.update_handler_url()?;
+        let path = url.path().trim_end_matches("update").to_string();
+        url.set_path(&format!("{}select", path));
+
+        Ok(url.to_string())
+    }
+
+    /// the admin/ping handler of the collection next to the update handler
+    /// e.g. "http://localhost:8983/solr/my_collection/update/extract" -> "http://localhost:8983/solr/my_collection/admin/ping"
+    pub(crate) fn ping_url(&self) -> Result<String, CommitUrlError> {
+        let mut url = self.update_handler_url()?;
+        let path = url.path().trim_end_matches("update").to_string();
+        url.set_path(&format!("{}admin/ping", path));
+
+        Ok(url.to_string())
+    }
+
+    /// the update handler of the commit URL with the query replaced by the command, no query if it is empty
+    fn update_command_url(&self, query: &str) -> Result<String, CommitUrlError> {
+        let mut url = match &self.commit_url {
+            Some(commit_url) => Url::parse(commit_url)
+                .map_err(|_| CommitUrlError::InvalidUrl(commit_url.clone()))?,
+            None => self.update_handler_url()?,
+        };
+        url.set_query(Some(query).filter(|query| !query.is_empty()));
+
+        Ok(url.to_string())
+    }
+
+    /// the query matching the documents this configuration posts, the ids starting with the id_prefix and
+    /// base_url or every document
+    pub(crate) fn documents_query(&self) -> String {
+        match self.id_prefix_for() {
+            Some(prefix) => format!("{{!prefix f=id}}{}", prefix),
+            None => String::from("*:*"),
+        }
+    }
+
+    /// the start of the ids of the documents this configuration posts, the id_prefix followed by the base_url,
+    /// None without either e.g. "docs:https://example.com/". clean, sync, and verify only touch these documents
+    pub fn id_prefix_for(&self) -> Option<String> {
+        let mut prefix = self.id_prefix.clone().unwrap_or_default();
+        if let Some(base_url) = &self.base_url {
+            prefix.push_str(base_url.trim_end_matches('/'));
+            prefix.push('/');
+        }
+
+        (!prefix.is_empty()).then_some(prefix)
+    }
+
+    /// the /update handler next to the update endpoint, structured documents are posted here
+    /// e.g. "http://localhost:8983/solr/my_collection/update/extract" -> "http://localhost:8983/solr/my_collection/update"
+    pub(crate) fn update_handler_url(&self) -> Result<Url, CommitUrlError> {
+        let update_endpoint = self.update_endpoint();
+        let mut url = Url::parse(&update_endpoint)
+            .map_err(|_| CommitUrlError::InvalidUrl(update_endpoint.clone()))?;
+
+        let segments: Vec<String> = url
+            .path_segments()
+            .map(|segments| {
+                segments
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // keep everything up to the last "update" segment e.g. /solr/coll/update/json/docs -> /solr/coll/update
+        // otherwise fall back to the collection path e.g. /solr/coll/my_handler -> /solr/coll/update
+        let base_segments = match segments.iter().rposition(|s| s == "update") {
+            Some(update_index) => &segments[..update_index],
+            None => match collection_index(&segments) {
+                Some(collection_index) => &segments[..collection_index + 1],
+                None => return Err(CommitUrlError::CannotInfer(update_endpoint)),
+            },
+        };
+
+        let mut path = String::new();
+        for segment in base_segments {
+            path.push('/');
+            path.push_str(segment);
+        }
+        path.push_str("/update");
+
+        url.set_path(&path);
+        url.set_query(None);
+        url.set_fragment(None);
+
+        Ok(url)
+    }
+}
+
+/// the index of the collection segment of a URL path, after "solr" or after "api/collections" (or its "api/c" alias)
+/// of the v2 API e.g. ["solr", "coll", "update"] -> 1 and ["api", "collections", "coll", "update"] -> 2
+fn collection_index(segments: &[String]) -> Option<usize> {
+    let index = match segments.iter().position(|s| s == "solr") {
+        Some(solr_index) => solr_index + 1,
+        None => {
+            let api_index = segments.iter().position(|s| s == "api")?;
+            match segments.get(api_index + 1).map(String::as_str) {
+                Some("collections" | "c") => api_index + 2,
+                _ => return None,
+            }
+        }
+    };
+    (index < segments.len()).then_some(index)
+}
+
+/// the URL with the collection after the /solr/ (or v2 /api/collections/) segment replaced
+/// e.g. "http://localhost:8983/solr/live/update?commit=true" -> "http://localhost:8983/solr/staging/update?commit=true"
+pub(crate) fn collection_url(url: &str, collection: &str) -> Result<String, CommitUrlError> {
+    let mut parsed = Url::parse(url).map_err(|_| CommitUrlError::InvalidUrl(url.to_string()))?;
+
+    let mut segments: Vec<String> = parsed
+        .path_segments()
+        .map(|segments| segments.map(|s| s.to_string()).collect())
+        .unwrap_or_default();
+    let Some(collection_index) = collection_index(&segments) else {
+        return Err(CommitUrlError::NoCollection(url.to_string()));
+    };
+    segments[collection_index] = collection.to_string();
+
+    parsed
+        .path_segments_mut()
+        .map_err(|_| CommitUrlError::InvalidUrl(url.to_string()))?
+        .clear()
+        .extend(&segments);
+    Ok(parsed.to_string())
+}
+
+/// Generate a new unique run id, useful to know the run id before calling solr_post
+pub fn new_run_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// Callback invoked with the path and error of a file that failed to post
+pub type ErrorCallback = Box<dyn FnMut(&Path, &SolrPostError)>;
+
+/// Async callback invoked with the total number of files to index, or the number of files indexed
+pub type AsyncCountCallback = Box<dyn FnMut(u64) -> LocalBoxFuture<'static, ()>>;
+
+/// Async callback invoked with the path and error of a file that failed to post
+pub type AsyncErrorCallback = Box<dyn FnMut(PathBuf, SolrPostError) -> LocalBoxFuture<'static, ()>>;
+
+/// Async callback invoked when the indexing is complete
+pub type AsyncFinishCallback = Box<dyn FnMut() -> LocalBoxFuture<'static, ()>>;
+
+/// How the posted documents are committed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommitPolicy {
+    /// never commit, for collections that rely on autoCommit or forbid client commits
+    None,
+
+    /// hard commit with `commit=true` after posting and on the commit_every / commit_interval schedule
+    #[default]
+    Hard,
+
+    /// soft commit with `softCommit=true`, the documents become searchable without flushing to disk
+    Soft,
+
+    /// send `commitWithin` with each document so Solr commits them within this many milliseconds
+    Within(u64),
+}
+
+impl CommitPolicy {
+    /// true if the poster sends commit requests itself
+    pub fn is_explicit(self) -> bool {
+        matches!(self, CommitPolicy::Hard | CommitPolicy::Soft)
+    }
+}
+
+/// How the files are posted, see PostConfig::mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PostMode {
+    /// every file goes through the extract handler, /update/extract, where Tika extracts its content
+    #[default]
+    Extract,
+
+    /// .json, .jsonl, and .ndjson files are posted as they are to /update/json/docs, which indexes any JSON as
+    /// documents with the json_split and json_fields params, or to /update when they hold command-style JSON
+    /// e.g. {"add": {"doc": ...}}. the other files still go through the extract handler
+    JsonDocs,
+
+    /// .xml files hold Solr XML update commands e.g. <add><doc>...</doc><doc>...</doc></add> or <delete>, and are
+    /// posted as they are to /update. the other files still go through the extract handler
+    XmlUpdate,
+
+    /// .csv and .tsv files are posted as they are to /update with the csv_* params of the config,
+    /// the values of .tsv files are separated by tabs unless csv_separator is set. the other files still
+    /// go through the extract handler
+    Csv,
+}
+
+impl PostMode {
+    /// the name of the mode in the CLI e.g. "json-docs"
+    pub fn name(self) -> &'static str {
+        match self {
+            PostMode::Extract => "extract",
+            PostMode::JsonDocs => "json-docs",
+            PostMode::XmlUpdate => "xml",
+            PostMode::Csv => "csv",
+        }
+    }
+
+    /// true if the file is posted as it is to an update handler in this mode instead of being extracted
+    pub(crate) fn posts_as_is(self, path: &Path) -> bool {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match self {
+            PostMode::Extract => false,
+            PostMode::JsonDocs => matches!(extension.as_deref(), Some("json" | "jsonl" | "ndjson")),
+            PostMode::XmlUpdate => extension.as_deref() == Some("xml"),
+            PostMode::Csv => matches!(extension.as_deref(), Some("csv" | "tsv")),
+        }
+    }
+}
+
+/// What is posted to the extract handler after the front matter of a Markdown file, see PostConfig::markdown
+#[cfg(feature = "markdown")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkdownBody {
+    /// the Markdown text as it is
+    Text,
+
+    /// the Markdown rendered to HTML, so Tika indexes the text without the Markdown syntax
+    Html,
+}
+
+/// How redirects are handled, see PostConfig::redirect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectPolicy {
+    /// follow up to this many redirects. the Authorization header and cookies are dropped when a redirect
+    /// leaves the host, and a streamed file body can't be sent again so its 307 or 308 fails the file
+    Follow(usize),
+
+    /// never follow a redirect, a 3xx response fails the request
+    Never,
+}
+
+impl Default for RedirectPolicy {
+    fn default() -> Self {
+        RedirectPolicy::Follow(10)
+    }
+}
+
+/// How the requests are authenticated, see PostConfig::auth
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthScheme {
+    /// basic auth with user credentials e.g. "user:pass"
+    Basic(String),
+
+    /// a bearer token e.g. a JWT for Solr's JWT authentication plugin or an API gateway
+    Bearer(String),
+
+    /// Kerberos with a SPNEGO token for each request, from the ticket cache of `kinit`
+    #[cfg(feature = "kerberos")]
+    Negotiate,
+
+    /// basic auth with the login and password of the Solr host in a .netrc file, $NETRC or ~/.netrc when None.
+    /// the requests are sent without credentials if the file has no entry for the host
+    Netrc(Option<PathBuf>),
+
+    /// basic auth with the password of the user stored in the OS keyring for the Solr host
+    /// e.g. the service "solr.example.com" and the account "user"
+    #[cfg(feature = "keyring")]
+    Keyring { user: String },
+}
+
+/// A login form posted once before the first request, see PostConfig::login
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Login {
+    /// the URL the form is posted to e.g. "https://sso.example.com/login"
+    pub url: String,
+
+    /// the form fields e.g. [("username", "indexer"), ("password", "secret")]
+    pub form: Vec<(String, String)>,
+}
+
+/// The shard a document is routed to with the `_route_` param, see PostConfig::route
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DocumentRoute {
+    /// every document is routed to this shard e.g. "tenant_a"
+    Fixed(String),
+
+    /// the directory at this depth below the directory being posted names the shard, 0 is the first directory
+    /// e.g. with 0 "docs/tenant_a/2024/report.pdf" is routed to "tenant_a" when posting "docs".
+    /// files without a directory at that depth are posted without a route
+    PathComponent(usize),
+}
+
+impl DocumentRoute {
+    /// the route of the file from its path below the directory it is in
+    pub(crate) fn route_for(&self, relative_path: &Path) -> Option<String> {
+        match self {
+            DocumentRoute::Fixed(route) => Some(route.clone()),
+            DocumentRoute::PathComponent(depth) => {
+                let directories = relative_path.parent()?;
+                directories
+                    .iter()
+                    .nth(*depth)
+                    .map(|component| component.to_string_lossy().into_owned())
+            }
+        }
+    }
+}
+
+/// Summary of a solr_post run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PostSummary {
+    /// the number of files found to index after applying the filters
+    pub total: usize,
+
+    /// the number of files successfully indexed
+    pub indexed: usize,
+
+    /// the number of files that failed to be read or posted
+    pub failed: usize,
+
+    /// the number of files rejected by Solr because of a version conflict
+    pub conflicts: usize,
+
+    /// the number of files skipped by the exclude and include regex filters
+    pub skipped: usize,
+
+    /// the total number of body bytes sent to Solr
+    pub bytes_sent: u64,
+
+    /// how long the run took from scanning to the final commit
+    pub elapsed: Duration,
+
+    /// true if the run was cancelled before all the files were posted
+    pub cancelled: bool,
+
+    /// true if more files failed than rollback_on_failures allows and the documents were rolled back
+    pub rolled_back: bool,
+
+    /// the number of documents deleted by sync because their file no longer exists
+    pub deleted: usize,
+
+    /// the number of files skipped by incremental because they haven't changed since the last run
+    pub unchanged: usize,
+
+    /// the number of files skipped by resume because the interrupted run already indexed them
+    pub resumed: usize,
+
+    /// the number of documents Solr found after the commit, see PostConfig::verify
+    pub verified: Option<usize>,
+}
+
+impl PostSummary {
+    /// the average number of documents indexed per second
+    pub fn docs_per_sec(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.indexed as f64 / secs
+        } else {
+            0.0
         }
     }
 }
 
 /// Post files to Solr server concurrently based on the configuration
-/// optionally you can provide callbacks for on_start, on_next, and on_finish
+/// optionally you can provide callbacks for on_start, on_next, on_error, and on_finish
 /// on_start will be called with the total number of files to index
 /// on_next will be called with the number of files indexed for tracking progress
+/// on_error will be called with the file path and error when a file fails to post or Solr responds with a non 2xx status,
+/// when it is not set the errors are printed to stderr
 /// on_finish will be called when the indexing is complete
-/// returns the total number of files indexed
-#[allow(clippy::redundant_clone)]
+/// returns a summary of the run, files that fail to post are counted in the summary,
+/// an error is returned if posting can't start or the commit fails
 pub async fn solr_post(
     config: PostConfig,
-    mut on_start: Option<Box<dyn FnMut(u64)>>,
-    mut on_next: Option<Box<dyn FnMut(u64)>>,
-    mut on_finish: Option<Box<dyn FnMut()>>,
-) -> usize {
-    let file_extensions_joined = config.file_extensions.join(",");
-    let glob_expression = format!("**/*.{{{}}}", file_extensions_joined);
-    let glob = Glob::new(glob_expression.as_str()).unwrap();
-    let files: Vec<Result<WalkEntry, WalkError>> = glob.walk(config.directory_path).collect();
-    let files_to_index_set: HashSet<String>;
-    let mut default_headers = header::HeaderMap::new();
-
-    // insert basic auth header if basic_auth_creds is set
-    if let Some(creds) = &config.basic_auth_creds {
-        // encode the username and password to base64
-        let auth_value = BASE64_STANDARD.encode(creds);
-        default_headers.insert(
-            header::AUTHORIZATION,
-            header::HeaderValue::from_str(&format!("Basic {}", auth_value)).unwrap(),
-        );
-    }
+    on_start: Option<Box<dyn FnMut(u64)>>,
+    on_next: Option<Box<dyn FnMut(u64)>>,
+    on_error: Option<ErrorCallback>,
+    on_finish: Option<Box<dyn FnMut()>>,
+) -> Result<PostSummary, SolrPostError> {
+    // wrap the callbacks so they can be awaited like async callbacks
+    let on_start = on_start.map(|mut on_start| -> AsyncCountCallback {
+        Box::new(move |total| {
+            on_start(total);
+            Box::pin(std::future::ready(()))
+        })
+    });
+    let on_next = on_next.map(|mut on_next| -> AsyncCountCallback {
+        Box::new(move |indexed_count| {
+            on_next(indexed_count);
+            Box::pin(std::future::ready(()))
+        })
+    });
+    let on_error = on_error.map(|mut on_error| -> AsyncErrorCallback {
+        Box::new(move |file_path, error| {
+            on_error(&file_path, &error);
+            Box::pin(std::future::ready(()))
+        })
+    });
+    let on_finish = on_finish.map(|mut on_finish| -> AsyncFinishCallback {
+        Box::new(move || {
+            on_finish();
+            Box::pin(std::future::ready(()))
+        })
+    });
 
-    // build the client with default_headers
-    let client = Client::builder()
-        .default_headers(default_headers)
-        .build()
-        .unwrap();
+    solr_post_async(config, on_start, on_next, on_error, on_finish).await
+}
 
-    // build the solr post url from the config. If the update_url is set, use that, otherwise build the url
-    let solr_collection_update_endpoint = match &config.update_url {
-        Some(url) => url.clone(),
-        None => format!(
-            "http://{0}:{1}/solr/{2}/update/extract",
-            config.host, config.port, config.collection
-        ),
+/// Post files to Solr server concurrently based on the configuration, same as solr_post
+/// but the callbacks return futures that are awaited before posting continues
+/// e.g. `Box::new(|indexed_count| Box::pin(async move { update_progress_row(indexed_count).await }))`
+pub async fn solr_post_async(
+    config: PostConfig,
+    on_start: Option<AsyncCountCallback>,
+    on_next: Option<AsyncCountCallback>,
+    on_error: Option<AsyncErrorCallback>,
+    on_finish: Option<AsyncFinishCallback>,
+) -> Result<PostSummary, SolrPostError> {
+    let mut sink = CallbackSink {
+        on_start,
+        on_next,
+        on_error,
+        on_finish,
+        indexed_count: 0,
+        error_reporter: ErrorReporter::default(),
     };
 
-    // scope for the MutexGuard accross async/await
-    // see: https://rust-lang.github.io/rust-clippy/master/index.html#await_holding_lock
-    {
-        // files to index
-        let files_to_index = Arc::new(RwLock::new(HashSet::<String>::new()));
+    post_with_events(config, &mut sink).await
+}
+
+/// Post files to Solr server concurrently based on the configuration, delivering a PostEvent
+/// for each step of the run over the channel instead of calling callbacks.
+/// posting continues if the receiver is dropped, returns the same summary as solr_post
+pub async fn solr_post_with_events(
+    config: PostConfig,
+    sender: mpsc::Sender<PostEvent>,
+) -> Result<PostSummary, SolrPostError> {
+    post_with_events(config, &mut ChannelSink { sender }).await
+}
 
-        // this clone is just so the main thread can hold onto a reference, to then print out later
-        let files_to_index_ref = files_to_index.clone();
+/// Post files to Solr server concurrently based on the configuration, notifying the observer
+/// of the progress instead of calling boxed callbacks. returns the same summary as solr_post
+pub async fn solr_post_with_observer(
+    config: PostConfig,
+    observer: Arc<dyn ProgressObserver>,
+) -> Result<PostSummary, SolrPostError> {
+    let mut sink = ObserverSink {
+        observer,
+        indexed_count: 0,
+    };
+
+    post_with_events(config, &mut sink).await
+}
 
-        // Scan for .html files that need indexing and store them in a vector
-        files.par_iter().for_each(|file| match file {
-            Ok(entry) => {
-                let path = entry.path();
-                let path_str = path.to_str().unwrap();
+/// Adapts the callbacks of solr_post and solr_post_async to the events of a run
+struct CallbackSink {
+    on_start: Option<AsyncCountCallback>,
+    on_next: Option<AsyncCountCallback>,
+    on_error: Option<AsyncErrorCallback>,
+    on_finish: Option<AsyncFinishCallback>,
+
+    /// the number of files Solr has responded to, passed to on_next
+    indexed_count: u64,
+
+    /// prints the errors to stderr when there is no on_error callback
+    error_reporter: ErrorReporter,
+}
 
-                // read the file content
-                let mut file = File::open(path_str).unwrap();
-                let mut contents = String::new();
-                file.read_to_string(&mut contents).unwrap();
+impl CallbackSink {
+    /// count a file Solr responded to and call the progress callback
+    async fn next(&mut self) {
+        self.indexed_count += 1;
 
-                // exclude and include rules. Note if exclude takes precedence over include
+        if let Some(ref mut on_next) = self.on_next {
+            // call the progress callback with the indexed_count
+            on_next(self.indexed_count).await;
+        }
+    }
+}
 
-                if let Some(exclude_regex) = config.exclued_regex.as_ref() {
-                    if exclude_regex.is_match(&contents) {
-                        // this file should be excluded, skip it and continue to the next file
-                        return;
+impl EventSink for CallbackSink {
+    fn emit(&mut self, event: PostEvent) -> LocalBoxFuture<'_, ()> {
+        Box::pin(async move {
+            match event {
+                PostEvent::ScanFinished { total, .. } => {
+                    if let Some(ref mut on_start) = self.on_start {
+                        // call the start callback with the total_files_to_index
+                        on_start(total as u64).await;
                     }
                 }
+                PostEvent::FileIndexed(_) => self.next().await,
+                PostEvent::FileFailed(mut file_result) => {
+                    // a status means Solr responded, count it towards the progress
+                    let responded = file_result.status.is_some();
 
-                if let Some(include_regex) = config.include_regex.as_ref() {
-                    if !include_regex.is_match(&contents) {
-                        // this file should not be included, skip it and continue to the next file
-                        return;
+                    if let Some(error) = file_result.error.take() {
+                        match self.on_error {
+                            // let the caller handle the error instead of printing it
+                            Some(ref mut on_error) => on_error(file_result.path, error).await,
+                            None if file_result.is_conflict() => {}
+                            None => {
+                                report_error(&mut self.error_reporter, &file_result.path, error)
+                            }
+                        }
+                    }
+
+                    if responded {
+                        self.next().await;
                     }
                 }
+                PostEvent::Finished(summary) => {
+                    self.error_reporter.finish();
+
+                    if summary.conflicts > 0 {
+                        eprintln!(
+                            "{} documents were not updated because of version conflicts",
+                            summary.conflicts
+                        );
+                    }
 
-                let mut files_to_index_set = files_to_index.write().expect("rwlock poisoned");
-                files_to_index_set.insert(path_str.to_string());
+                    if let Some(ref mut on_finish) = self.on_finish {
+                        // call the finish callback
+                        on_finish().await;
+                    }
+                }
+                _ => {}
             }
-            Err(e) => println!("error: {:?}", e),
+        })
+    }
+}
+
+/// scan and post the files, emitting the events of the run to the sink.
+/// the run is aborted with SolrPostError::DeadlineExceeded if it takes longer than the deadline
+async fn post_with_events(
+    config: PostConfig,
+    sink: &mut dyn EventSink,
+) -> Result<PostSummary, SolrPostError> {
+    #[cfg(feature = "metrics")]
+    let metrics = Metrics::start(&config).await?;
+    #[cfg(feature = "metrics")]
+    let mut metrics_sink;
+    #[cfg(feature = "metrics")]
+    let sink: &mut dyn EventSink = match &metrics {
+        Some(metrics) => {
+            metrics_sink = metrics.sink(sink);
+            &mut metrics_sink
+        }
+        None => sink,
+    };
+
+    #[cfg(feature = "statsd")]
+    let statsd = config
+        .statsd_addr
+        .as_deref()
+        .map(Statsd::connect)
+        .transpose()?;
+    #[cfg(feature = "statsd")]
+    let mut statsd_sink;
+    #[cfg(feature = "statsd")]
+    let sink: &mut dyn EventSink = match &statsd {
+        Some(statsd) => {
+            statsd_sink = statsd.sink(sink);
+            &mut statsd_sink
+        }
+        None => sink,
+    };
+
+    let result = match config.deadline {
+        Some(deadline) => tokio::time::timeout(deadline, run(config, sink))
+            .await
+            .map_err(|_| SolrPostError::DeadlineExceeded(deadline))
+            .and_then(|result| result),
+        None => run(config, sink).await,
+    };
+
+    #[cfg(feature = "metrics")]
+    if let Some(metrics) = metrics {
+        metrics.finish().await;
+    }
+
+    #[cfg(feature = "statsd")]
+    if let Some(statsd) = statsd {
+        statsd.finish();
+    }
+
+    result
+}
+
+/// The state of the previous runs used to pick the scanned files that need posting
+pub(crate) struct Selection {
+    pub(crate) index_state: Option<IndexState>,
+
+    /// the current state of the scanned files, empty without a state file
+    pub(crate) current_state: HashMap<String, FileState>,
+
+    /// the number of files skipped by incremental
+    pub(crate) unchanged: usize,
+
+    /// the number of files skipped by resume
+    pub(crate) resumed: usize,
+}
+
+/// remove the files that don't need posting from the scanned files, the unchanged files when
+/// incremental and the files an interrupted run already indexed when resuming
+pub(crate) fn select_files(
+    config: &PostConfig,
+    files: &mut HashSet<String>,
+) -> Result<Selection, SolrPostError> {
+    // compare the scanned files with the state of the previous run
+    let index_state = match &config.state_file {
+        Some(state_file) => Some(IndexState::load(state_file)?),
+        None => None,
+    };
+    let current_state = match &index_state {
+        Some(index_state) => index_state.current(files),
+        None => HashMap::new(),
+    };
+
+    let mut unchanged = 0;
+    if let (Some(index_state), true) = (&index_state, config.incremental) {
+        files.retain(|file| {
+            let is_unchanged = index_state.is_unchanged(file, &current_state);
+            unchanged += is_unchanged as usize;
+            !is_unchanged
         });
+    }
+
+    // skip what the interrupted run already indexed
+    let completed = match (&config.journal_file, config.resume) {
+        (Some(journal_file), true) => Journal::completed(journal_file)?,
+        _ => HashSet::new(),
+    };
+    let mut resumed = 0;
+    if !completed.is_empty() {
+        files.retain(|file| {
+            let is_completed = completed.contains(&absolute(file));
+            resumed += is_completed as usize;
+            !is_completed
+        });
+    }
+
+    Ok(Selection {
+        index_state,
+        current_state,
+        unchanged,
+        resumed,
+    })
+}
 
-        let rw_lock_files_set = files_to_index_ref.read().expect("rwlock poisoned");
-        files_to_index_set = rw_lock_files_set.clone();
-    } // MutexGuard is dropped here
+/// scan and post the files, emitting the events of the run to the sink
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "run",
+        skip_all,
+        fields(directory = %config.directory_path.display(), run_id = tracing::field::Empty)
+    )
+)]
+async fn run(config: PostConfig, sink: &mut dyn EventSink) -> Result<PostSummary, SolrPostError> {
+    let started = Instant::now();
 
-    let total_files_to_index = files_to_index_set.len();
+    let context = Arc::new(PostContext::new(config)?);
+    info!("run id {}", context.run_id);
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("run_id", context.run_id.as_str());
 
-    let mut posts = futures::stream::iter(files_to_index_set.into_iter().map(|file| async {
-        // get the absolute path of file
-        let file_path = Path::new(&file);
-        let file_path_absolute = file_path.canonicalize().unwrap();
+    // read the SolrCloud nodes up front so an unreachable ZooKeeper fails the run instead of every file
+    context.nodes().await?;
+    context.client.login().await?;
+    ping_solr(&context).await?;
 
-        // url encode the file path string
-        let file_path_encoded = urlencoding::encode(file_path_absolute.to_str().unwrap());
+    for directory_path in context.config.directories() {
+        sink.emit(PostEvent::ScanStarted {
+            directory_path: directory_path.clone(),
+        })
+        .await;
+    }
 
-        // read the file into a String
-        let mut file = File::open(file).unwrap();
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).unwrap();
+    let mut scanned = scan_files(&context.config)?;
+    let Selection {
+        mut index_state,
+        current_state,
+        unchanged,
+        resumed,
+    } = select_files(&context.config, &mut scanned.files)?;
 
-        // format the solr post url using file_path_encoded as the resource.name & literal.id
-        let solr_post_url = format!(
-            "{0}?resource.name={1}&literal.id={1}",
-            solr_collection_update_endpoint, file_path_encoded
+    let total_files_to_index = scanned.files.len();
+
+    let mut summary = PostSummary {
+        total: total_files_to_index,
+        failed: scanned.failed,
+        skipped: scanned.skipped,
+        unchanged,
+        resumed,
+        ..Default::default()
+    };
+
+    sink.emit(PostEvent::ScanFinished {
+        total: total_files_to_index,
+        skipped: scanned.skipped,
+    })
+    .await;
+
+    // wipe the collection only once the scan succeeded, so a bad directory doesn't leave it empty.
+    // a resumed run was already cleaned by the interrupted one
+    if context.config.clean && resumed == 0 {
+        for clean_url in context.command_urls(&context.config.clean_url()?).await? {
+            delete_by_query(
+                &context.client,
+                &clean_url,
+                &context.config.documents_query(),
+            )
+            .await
+            .map_err(|e| SolrPostError::Clean(Box::new(e)))?;
+        }
+        info!(
+            "deleted the documents matching {} before posting",
+            context.config.documents_query()
         );
+        sink.emit(PostEvent::Cleaned).await;
+    }
 
-        // guess the mime type of the file from the file path e.g. "text/html"
-        let mime_type = from_path(&file_path_absolute).first_or_octet_stream();
-
-        // post the file to solr using the Apache Tika update/extract handler
-        (
-            client
-                .post(solr_post_url)
-                .header(header::CONTENT_TYPE, mime_type.to_string())
-                .body(contents)
-                .send()
-                .await,
-            file_path_absolute,
-        )
-    }))
-    .buffer_unordered(config.concurrency);
+    for file in scanned.files.iter() {
+        sink.emit(PostEvent::FileQueued {
+            path: PathBuf::from(file),
+        })
+        .await;
+    }
+
+    let mut journal = match &context.config.journal_file {
+        Some(journal_file) => Some(Journal::open(journal_file, context.config.resume)?),
+        None => None,
+    };
+
+    let mut failure_report = match &context.config.error_report {
+        Some(error_report) => Some(FailureReport::create(error_report)?),
+        None => None,
+    };
+
+    let mut posts = post_files(context.clone(), scanned.files);
 
     info!("indexing {} files", total_files_to_index);
-    let mut indexed_count = 0;
 
-    if let Some(ref mut on_start) = on_start {
-        // call the start callback with the total_files_to_index
-        on_start(total_files_to_index as u64);
-    }
+    let client = &context.client;
+    let commit_urls = &context.command_urls(&context.commit_url).await?;
+    let explicit_commits = context.config.commit.is_explicit() && !context.config.extract_only;
+    let mut commit_scheduler = match explicit_commits {
+        true => CommitScheduler::new(context.config.commit_every, context.config.commit_interval),
+        false => CommitScheduler::new(None, None),
+    };
+    let mut circuit_breaker = context.config.circuit_breaker_threshold.map(|threshold| {
+        CircuitBreaker::new(
+            threshold,
+            context.config.circuit_breaker_wait,
+            context.config.circuit_breaker_retries,
+        )
+    });
+
+    let mut rolling_back = false;
+    let mut aborting = false;
 
     // loop through the stream of futures solr POST requests and increment the progress bar
-    while let Some((res, file_path)) = posts.next().await {
-        match res {
-            Ok(response) => {
-                if response.status().is_success() {
-                    info!("indexed: {}", file_path.to_str().unwrap());
-                } else {
-                    eprintln!(
-                        "POST {} {}\nIs collection correct?\nfailed to index file: {}",
-                        response.url(),
-                        response.status(),
-                        file_path.to_str().unwrap(),
-                    );
+    loop {
+        // wait for the next response, or for the commit interval to elapse
+        let next = match commit_scheduler.deadline() {
+            Some(deadline) => tokio::select! {
+                next = posts.next() => Some(next),
+                _ = tokio::time::sleep_until(deadline.into()) => None,
+            },
+            None => Some(posts.next().await),
+        };
+
+        let file_result = match next {
+            Some(Some(file_result)) => file_result,
+            Some(None) => break,
+            None => {
+                // the commit interval elapsed while waiting for a response
+                if commit_scheduler.is_due() && !rolling_back && !aborting {
+                    commit_all(client, commit_urls).await?;
+                    sink.emit(PostEvent::Committed).await;
                 }
+                commit_scheduler.reset();
+                continue;
+            }
+        };
 
-                indexed_count += 1;
+        summary.bytes_sent += file_result.bytes_sent;
+
+        if let Some(circuit_breaker) = circuit_breaker.as_mut() {
+            let action = if file_result.is_server_error() {
+                circuit_breaker.record_failure()
+            } else {
+                circuit_breaker.record_success();
+                BreakerAction::Continue
+            };
 
-                if let Some(ref mut on_next) = on_next {
-                    // call the progress callback with the indexed_count
-                    on_next(indexed_count as u64);
+            match action {
+                BreakerAction::Continue => {}
+                BreakerAction::Pause(wait) => {
+                    warn!(
+                        "too many consecutive server errors, pausing for {:?} before trying again",
+                        wait
+                    );
+                    // not polling the stream pauses posting
+                    tokio::time::sleep(wait).await;
+                }
+                BreakerAction::Abort => {
+                    let threshold = context.config.circuit_breaker_threshold.unwrap_or_default();
+                    sink.emit(PostEvent::FileFailed(file_result)).await;
+                    return Err(SolrPostError::CircuitOpen { threshold });
                 }
             }
-            Err(e) => {
-                eprintln!("{}\nIs Solr server running and collection available?", e)
+        }
+
+        if file_result.is_success() {
+            info!("indexed: {}", file_result.path.display());
+            summary.indexed += 1;
+            commit_scheduler.record();
+            if let Some(index_state) = index_state.as_mut() {
+                index_state.record(&file_result.path, &current_state);
+            }
+            if let Some(journal) = journal.as_mut() {
+                journal.record(&file_result.path)?;
             }
+            if let Some(extract_output) = &context.config.extract_output {
+                extract::save_extracted(
+                    extract_output,
+                    context.relative_path(&file_result.path),
+                    &file_result,
+                )?;
+            }
+            sink.emit(PostEvent::FileIndexed(file_result)).await;
+        } else {
+            if file_result.is_conflict() {
+                // the indexed document has a different version, this is not a failure
+                info!("version conflict: {}", file_result.path.display());
+                summary.conflicts += 1;
+            } else {
+                summary.failed += 1;
+                if let Some(failure_report) = failure_report.as_mut() {
+                    failure_report.record(&file_result)?;
+                }
+            }
+            sink.emit(PostEvent::FileFailed(file_result)).await;
+        }
+
+        // too many failures, finish the requests in flight and roll back instead of committing
+        if !rolling_back
+            && context
+                .config
+                .rollback_on_failures
+                .is_some_and(|max| summary.failed >= max)
+        {
+            warn!(
+                "{} files failed, rolling back",
+                context.config.rollback_on_failures.unwrap_or_default()
+            );
+            rolling_back = true;
+            context.stop.cancel();
+        }
+
+        // too many failures, finish the requests in flight and abort without committing
+        if !aborting
+            && context
+                .config
+                .max_errors
+                .is_some_and(|max| summary.failed >= max)
+        {
+            aborting = true;
+            context.stop.cancel();
+        }
+
+        if commit_scheduler.is_due() && !rolling_back && !aborting {
+            commit_all(client, commit_urls).await?;
+            commit_scheduler.reset();
+            sink.emit(PostEvent::Committed).await;
         }
     }
 
-    // send GET request to solr to commit the changes
-    let response = client
-        .get("http://localhost:8983/solr/portal/update?commit=true")
-        .send()
+    summary.cancelled = context
+        .config
+        .cancellation_token
+        .as_ref()
+        .is_some_and(|token| token.is_cancelled());
+
+    // an aborted run is reported like a finished one, the documents it posted are left uncommitted
+    if aborting && !rolling_back {
+        summary.elapsed = started.elapsed();
+        sink.emit(PostEvent::Finished(summary.clone())).await;
+        return Err(SolrPostError::TooManyErrors {
+            max_errors: context.config.max_errors.unwrap_or_default(),
+        });
+    }
+
+    // remove the documents of deleted files, only a complete run knows which files still exist
+    if context.config.sync && !summary.cancelled && !rolling_back {
+        summary.deleted = sync::delete_missing(&context)
+            .await
+            .map_err(|e| SolrPostError::Sync(Box::new(e)))?;
+        sink.emit(PostEvent::Synced {
+            deleted: summary.deleted,
+        })
         .await;
+    }
 
-    // check if the commit was successful
-    match response {
-        Ok(response) => {
-            if response.status().is_success() {
-                info!("commit successful");
-            } else {
-                info!("commit failed");
+    // commit the remaining changes, unless the run was cancelled and shouldn't commit
+    if rolling_back {
+        for rollback_url in context
+            .command_urls(&context.config.rollback_url()?)
+            .await?
+        {
+            rollback(client, &rollback_url).await?;
+        }
+        summary.rolled_back = true;
+        sink.emit(PostEvent::RolledBack).await;
+    } else if context.config.extract_only {
+        info!("extract only, skipping commit");
+    } else if !explicit_commits {
+        info!("commit policy {:?}, skipping commit", context.config.commit);
+    } else if !summary.cancelled || context.config.commit_on_cancel {
+        commit_all(client, commit_urls).await?;
+        sink.emit(PostEvent::Committed).await;
+    } else {
+        info!("run cancelled, skipping commit");
+    }
+
+    // catch documents that were accepted but never made it into the index
+    if context.config.verify && !summary.cancelled && !summary.rolled_back {
+        let found = verify::verify_count(&context, summary.indexed).await?;
+        summary.verified = Some(found);
+        sink.emit(PostEvent::Verified { found }).await;
+    }
+
+    // record what was indexed, a rolled back run indexed nothing
+    if let (Some(index_state), Some(state_file)) =
+        (index_state.as_mut(), &context.config.state_file)
+    {
+        if !summary.rolled_back {
+            index_state.update_scanned(&current_state);
+            index_state.save(state_file)?;
+        }
+    }
+
+    // keep the journal of a cancelled run to resume it, a rolled back run has nothing to resume
+    if let Some(journal) = journal {
+        if !summary.cancelled || summary.rolled_back {
+            journal.remove()?;
+        }
+    }
+
+    // optimizing rewrites the index so it is only worth it after a complete run
+    if let Some(optimize_url) = &context.optimize_url {
+        if !summary.cancelled && !summary.rolled_back {
+            for optimize_url in context.command_urls(optimize_url).await? {
+                optimize(client, &optimize_url).await?;
             }
+            sink.emit(PostEvent::Optimized).await;
+        }
+    }
+
+    // output time
+    summary.elapsed = started.elapsed();
+    info!(
+        "indexing complete run id {} in {:.2}s",
+        context.run_id,
+        summary.elapsed.as_secs_f64()
+    );
+
+    sink.emit(PostEvent::Finished(summary.clone())).await;
+
+    // max_errors was reached while rolling back
+    if aborting {
+        return Err(SolrPostError::TooManyErrors {
+            max_errors: context.config.max_errors.unwrap_or_default(),
+        });
+    }
+
+    Ok(summary)
+}
+
+/// print the error of a file that failed to post to stderr, throttling repeated errors
+fn report_error(error_reporter: &mut ErrorReporter, file_path: &Path, error: SolrPostError) {
+    match error {
+        SolrPostError::Response {
+            url,
+            status,
+            solr_error: None,
+        } => {
+            error_reporter.report(
+                &format!("POST {}", status),
+                &format!(
+                    "POST {} {}\nIs collection correct?\nfailed to index file: {}",
+                    url,
+                    status,
+                    file_path.display(),
+                ),
+            );
+        }
+        SolrPostError::Response {
+            url,
+            status,
+            solr_error: Some(solr_error),
+        } => {
+            // the reason Solr gives replaces the guess, the message often names the document so group by status
+            error_reporter.report(
+                &format!("POST {}", status),
+                &format!(
+                    "POST {} {}: {}\nfailed to index file: {}",
+                    url,
+                    status,
+                    solr_error.message,
+                    file_path.display(),
+                ),
+            );
+        }
+        SolrPostError::Request(e) => {
+            let message = format!("{}\nIs Solr server running and collection available?", e);
+
+            // the error message contains the url of the file, so group the errors without it
+            error_reporter.report(&e.without_url().to_string(), &message);
         }
+        SolrPostError::BatchRequest(e) => {
+            // the batch url doesn't contain the file so the message groups as is
+            let message = format!("{}\nIs Solr server running and collection available?", e);
+            error_reporter.report(&e.to_string(), &message);
+        }
+        e => eprintln!("{}", e),
+    }
+}
+
+/// Commit the documents posted to the collection of the configuration, e.g. after draining solr_post_stream
+pub async fn solr_commit(config: &PostConfig) -> Result<(), SolrPostError> {
+    let commit_url = config.commit_url()?;
+    let client = build_client(config)?;
+    client.login().await?;
+
+    commit(&client, &commit_url).await
+}
+
+/// Optimize the collection of the configuration, e.g. after a bulk load with solr_post_stream
+pub async fn solr_optimize(config: &PostConfig) -> Result<(), SolrPostError> {
+    let optimize_url = config.optimize_url()?;
+    let client = build_client(config)?;
+    client.login().await?;
+
+    optimize(&client, &optimize_url).await
+}
+
+/// Delete the documents matching the query from the collection of the configuration, e.g. to clean up before a
+/// reindex. The delete is committed according to PostConfig::commit and uses the same credentials as posting
+pub async fn solr_delete_by_query(config: &PostConfig, query: &str) -> Result<(), SolrPostError> {
+    let delete_url = config.delete_url()?;
+    let client = build_client(config)?;
+    client.login().await?;
+
+    delete_by_query(&client, &delete_url, query)
+        .await
+        .map_err(|e| SolrPostError::Delete(Box::new(e)))?;
+
+    info!("deleted documents matching {}", query);
+    Ok(())
+}
+
+/// List the files a run would post after the extension and content regex filters, as sorted absolute
+/// paths which are also the document ids. Nothing is sent to Solr
+pub fn solr_list_files(config: &PostConfig) -> Result<Vec<PathBuf>, SolrPostError> {
+    let scanned = scan_files(config)?;
+
+    let mut files: Vec<PathBuf> = scanned
+        .files
+        .iter()
+        .map(|file| PathBuf::from(absolute(file)))
+        .collect();
+    files.sort();
+
+    Ok(files)
+}
+
+/// post a delete by query to the update handler, a non success status is an error
+pub(crate) async fn delete_by_query(
+    client: &SolrClient,
+    url: &str,
+    query: &str,
+) -> Result<(), SolrPostError> {
+    let body = serde_json::json!({ "delete": { "query": query } });
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string())
+        .send()
+        .await?;
+
+    error_for_status(response).await?;
+
+    Ok(())
+}
+
+/// the first and the longest wait between the pings of PostConfig::wait_for_solr
+const PING_BACKOFF_MIN: Duration = Duration::from_millis(250);
+const PING_BACKOFF_MAX: Duration = Duration::from_secs(5);
+
+/// send GET request to the collection's ping handler when PostConfig::ping is set, retrying for up to
+/// PostConfig::wait_for_solr. skipped when the collection can't be inferred from a custom update URL
+pub(crate) async fn ping_solr(context: &PostContext) -> Result<(), SolrPostError> {
+    if !context.config.ping && context.config.wait_for_solr.is_none() {
+        return Ok(());
+    }
+
+    let ping_urls = match context.config.ping_url() {
+        Ok(ping_url) => context.command_urls(&ping_url).await?,
         Err(e) => {
-            eprintln!("{}\nIs Solr server running and collection available?", e);
+            info!("skipping the ping: {}", e);
+            return Ok(());
+        }
+    };
+
+    let give_up = Instant::now() + context.config.wait_for_solr.unwrap_or_default();
+    let mut backoff = PING_BACKOFF_MIN;
+    for ping_url in ping_urls {
+        loop {
+            match update_command(&context.client, &ping_url).await {
+                Ok(()) => break,
+                Err(e) if Instant::now() + backoff < give_up => {
+                    info!("waiting for Solr, retrying in {:?}: {}", backoff, e);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(PING_BACKOFF_MAX);
+                }
+                Err(e) => return Err(SolrPostError::Ping(Box::new(e))),
+            }
         }
     }
 
-    // output time
-    info!("indexing complete");
+    info!("ping successful");
+    Ok(())
+}
+
+/// commit every collection, see PostConfig::extra_collections
+async fn commit_all(client: &SolrClient, commit_urls: &[String]) -> Result<(), SolrPostError> {
+    for commit_url in commit_urls {
+        commit(client, commit_url).await?;
+    }
+    Ok(())
+}
+
+/// send GET request to solr to commit the changes
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "commit", skip(client), fields(status = tracing::field::Empty))
+)]
+async fn commit(client: &SolrClient, commit_url: &str) -> Result<(), SolrPostError> {
+    update_command(client, commit_url)
+        .await
+        .map_err(|e| SolrPostError::Commit(Box::new(e)))?;
+
+    info!("commit successful");
+    Ok(())
+}
+
+/// send GET request to solr to roll back the uncommitted changes
+async fn rollback(client: &SolrClient, rollback_url: &str) -> Result<(), SolrPostError> {
+    update_command(client, rollback_url)
+        .await
+        .map_err(|e| SolrPostError::Rollback(Box::new(e)))?;
+
+    info!("rollback successful");
+    Ok(())
+}
+
+/// send GET request to solr to optimize the collection
+async fn optimize(client: &SolrClient, optimize_url: &str) -> Result<(), SolrPostError> {
+    update_command(client, optimize_url)
+        .await
+        .map_err(|e| SolrPostError::Optimize(Box::new(e)))?;
+
+    info!("optimize successful");
+    Ok(())
+}
+
+/// send an update handler command e.g. commit=true, a non success status is an error
+async fn update_command(client: &SolrClient, url: &str) -> Result<(), SolrPostError> {
+    let response = client.get(url).send().await?;
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("status", response.status().as_u16());
+
+    error_for_status(response).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(update_url: &str) -> PostConfig {
+        PostConfig {
+            update_url: Some(update_url.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn commit_url_of_the_default_endpoint() {
+        assert_eq!(
+            PostConfig::default().commit_url().unwrap(),
+            "http://localhost:8983/solr/collection1/update?commit=true"
+        );
+    }
+
+    #[test]
+    fn commit_url_of_a_custom_handler() {
+        assert_eq!(
+            config("http://localhost:8983/solr/c/myhandler")
+                .commit_url()
+                .unwrap(),
+            "http://localhost:8983/solr/c/update?commit=true"
+        );
+    }
+
+    #[test]
+    fn commit_url_of_json_docs() {
+        assert_eq!(
+            config("http://localhost:8983/solr/c/update/json/docs")
+                .commit_url()
+                .unwrap(),
+            "http://localhost:8983/solr/c/update?commit=true"
+        );
+    }
+
+    #[test]
+    fn commit_url_without_a_solr_path() {
+        assert_eq!(
+            config("http://localhost:8983/indexer/post").commit_url(),
+            Err(CommitUrlError::CannotInfer(
+                "http://localhost:8983/indexer/post".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn commit_url_of_the_v2_api() {
+        assert_eq!(
+            config("http://localhost:8983/api/collections/c/update")
+                .commit_url()
+                .unwrap(),
+            "http://localhost:8983/api/collections/c/update?commit=true"
+        );
+    }
+
+    #[test]
+    fn commit_url_drops_the_query() {
+        assert_eq!(
+            config("https://solr.example.com:8443/solr/c/update/extract?literal.source=web#top")
+                .commit_url()
+                .unwrap(),
+            "https://solr.example.com:8443/solr/c/update?commit=true"
+        );
+    }
 
-    if let Some(ref mut on_finish) = on_finish {
-        // call the finish callback
-        on_finish();
+    #[test]
+    fn soft_commit_url() {
+        let config = PostConfig {
+            commit: CommitPolicy::Soft,
+            ..config("http://localhost:8983/solr/c/update/extract")
+        };
+        assert_eq!(
+            config.commit_url().unwrap(),
+            "http://localhost:8983/solr/c/update?softCommit=true"
+        );
+    }
+
+    #[test]
+    fn explicit_commit_url_is_used_as_is() {
+        let config = PostConfig {
+            commit_url: Some("http://localhost:8983/indexer/commit?now=1".to_string()),
+            ..config("http://localhost:8983/indexer/post")
+        };
+        assert_eq!(
+            config.commit_url().unwrap(),
+            "http://localhost:8983/indexer/commit?now=1"
+        );
+    }
+
+    #[test]
+    fn invalid_update_url() {
+        assert_eq!(
+            config("not a url").commit_url(),
+            Err(CommitUrlError::InvalidUrl("not a url".to_string()))
+        );
     }
 
-    total_files_to_index
+    #[test]
+    fn documents_query_of_the_id_prefix_and_base_url() {
+        assert_eq!(PostConfig::default().id_prefix_for(), None);
+        assert_eq!(PostConfig::default().documents_query(), "*:*");
+
+        let config = PostConfig {
+            id_prefix: Some("docs:".to_string()),
+            base_url: Some("https://example.com/".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.id_prefix_for().as_deref(),
+            Some("docs:https://example.com/")
+        );
+        assert_eq!(
+            config.documents_query(),
+            "{!prefix f=id}docs:https://example.com/"
+        );
+    }
+
+    #[test]
+    fn update_handler_url_of_a_custom_handler() {
+        assert_eq!(
+            config("http://localhost:8983/solr/c/myhandler?wt=json")
+                .update_handler_url()
+                .unwrap()
+                .as_str(),
+            "http://localhost:8983/solr/c/update"
+        );
+    }
 }
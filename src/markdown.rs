@@ -0,0 +1,263 @@
+use std::path::Path;
+
+use pulldown_cmark::{html, Options, Parser};
+
+use crate::charset::to_utf8;
+use crate::{MarkdownBody, SolrPostError};
+
+/// A Markdown file split into the fields of its front matter and the body posted to the extract handler
+pub(crate) struct MarkdownDocument {
+    /// the literal fields of the front matter, a list is a field per value e.g. ("tags", "rust")
+    pub(crate) fields: Vec<(String, String)>,
+
+    /// the Markdown after the front matter, or the HTML rendered from it
+    pub(crate) body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrontMatter {
+    /// between --- lines, the closing line can also be ...
+    Yaml,
+
+    /// between +++ lines
+    Toml,
+}
+
+/// true for the Markdown files e.g. "index.md" or "post.markdown"
+pub(crate) fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("md") || extension.eq_ignore_ascii_case("markdown")
+        })
+}
+
+/// read the Markdown file and split off its front matter, a file that isn't UTF-8 is transcoded with
+/// PostConfig::transcode and fails otherwise
+pub(crate) async fn read_markdown(
+    path: &Path,
+    body: MarkdownBody,
+    transcode: bool,
+) -> Result<MarkdownDocument, SolrPostError> {
+    let content = tokio::fs::read(path)
+        .await
+        .map_err(|source| SolrPostError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    let content = match String::from_utf8(content) {
+        Ok(content) => content,
+        Err(e) if transcode => to_utf8(e.as_bytes()),
+        Err(e) => {
+            return Err(SolrPostError::InvalidDocument {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })
+        }
+    };
+
+    markdown_document(&content, body).map_err(|message| SolrPostError::InvalidDocument {
+        path: path.to_path_buf(),
+        message,
+    })
+}
+
+fn markdown_document(content: &str, body: MarkdownBody) -> Result<MarkdownDocument, String> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+
+    let (fields, markdown) = match split_front_matter(content) {
+        Some((FrontMatter::Yaml, front_matter, markdown)) => (yaml_fields(front_matter)?, markdown),
+        Some((FrontMatter::Toml, front_matter, markdown)) => (toml_fields(front_matter)?, markdown),
+        None => (Vec::new(), content),
+    };
+
+    let body = match body {
+        MarkdownBody::Text => markdown.as_bytes().to_vec(),
+        MarkdownBody::Html => render_html(markdown).into_bytes(),
+    };
+
+    Ok(MarkdownDocument { fields, body })
+}
+
+/// the format, the front matter, and the Markdown after it. None when the file doesn't start with a
+/// --- or +++ line, or the front matter isn't closed
+fn split_front_matter(content: &str) -> Option<(FrontMatter, &str, &str)> {
+    let mut lines = content.split_inclusive('\n');
+    let first = lines.next()?;
+    let format = match first.trim_end() {
+        "---" => FrontMatter::Yaml,
+        "+++" => FrontMatter::Toml,
+        _ => return None,
+    };
+
+    let mut offset = first.len();
+    for line in lines {
+        let end = offset + line.len();
+        let is_closing = match format {
+            FrontMatter::Yaml => matches!(line.trim_end(), "---" | "..."),
+            FrontMatter::Toml => line.trim_end() == "+++",
+        };
+        if is_closing {
+            return Some((format, &content[first.len()..offset], &content[end..]));
+        }
+        offset = end;
+    }
+
+    None
+}
+
+/// the top level keys of the YAML front matter with their scalar values, nested mappings are skipped
+fn yaml_fields(front_matter: &str) -> Result<Vec<(String, String)>, String> {
+    let mapping = match serde_yaml::from_str(front_matter) {
+        Ok(serde_yaml::Value::Mapping(mapping)) => mapping,
+        Ok(serde_yaml::Value::Null) => return Ok(Vec::new()),
+        Ok(_) => return Err("the YAML front matter must be a mapping".to_string()),
+        Err(e) => return Err(format!("invalid YAML front matter: {}", e)),
+    };
+
+    let mut fields = Vec::new();
+    for (key, value) in mapping {
+        let Some(key) = yaml_scalar(key) else {
+            continue;
+        };
+        match value {
+            serde_yaml::Value::Sequence(values) => fields.extend(
+                values
+                    .into_iter()
+                    .filter_map(yaml_scalar)
+                    .map(|value| (key.clone(), value)),
+            ),
+            value => fields.extend(yaml_scalar(value).map(|value| (key, value))),
+        }
+    }
+    Ok(fields)
+}
+
+fn yaml_scalar(value: serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(value) => Some(value),
+        serde_yaml::Value::Number(value) => Some(value.to_string()),
+        serde_yaml::Value::Bool(value) => Some(value.to_string()),
+        serde_yaml::Value::Tagged(tagged) => yaml_scalar(tagged.value),
+        _ => None,
+    }
+}
+
+/// the top level keys of the TOML front matter with their scalar values, tables are skipped
+fn toml_fields(front_matter: &str) -> Result<Vec<(String, String)>, String> {
+    let table: toml::Table = front_matter
+        .parse()
+        .map_err(|e| format!("invalid TOML front matter: {}", e))?;
+
+    let mut fields = Vec::new();
+    for (key, value) in table {
+        match value {
+            toml::Value::Array(values) => fields.extend(
+                values
+                    .into_iter()
+                    .filter_map(toml_scalar)
+                    .map(|value| (key.clone(), value)),
+            ),
+            value => fields.extend(toml_scalar(value).map(|value| (key, value))),
+        }
+    }
+    Ok(fields)
+}
+
+fn toml_scalar(value: toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(value) => Some(value),
+        toml::Value::Integer(value) => Some(value.to_string()),
+        toml::Value::Float(value) => Some(value.to_string()),
+        toml::Value::Boolean(value) => Some(value.to_string()),
+        toml::Value::Datetime(value) => Some(value.to_string()),
+        toml::Value::Array(_) | toml::Value::Table(_) => None,
+    }
+}
+
+/// render the Markdown to an HTML page, declaring the charset so Tika reads it as UTF-8
+fn render_html(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS;
+
+    let mut page = String::from("<!DOCTYPE html><html><head><meta charset=\"utf-8\"></head><body>");
+    html::push_html(&mut page, Parser::new_ext(markdown, options));
+    page.push_str("</body></html>");
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(document: &MarkdownDocument) -> Vec<(&str, &str)> {
+        document
+            .fields
+            .iter()
+            .map(|(field, value)| (field.as_str(), value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn yaml_front_matter_is_split_off() {
+        let content = "---\ntitle: Home\ntags: [rust, solr]\ndraft: false\nauthor:\n  name: Ann\n---\n# Home\n";
+        let (format, front_matter, markdown) = split_front_matter(content).unwrap();
+        assert_eq!(format, FrontMatter::Yaml);
+        assert!(front_matter.starts_with("title: Home\n"));
+        assert_eq!(markdown, "# Home\n");
+
+        let document = markdown_document(content, MarkdownBody::Text).unwrap();
+        assert_eq!(
+            fields(&document),
+            [
+                ("title", "Home"),
+                ("tags", "rust"),
+                ("tags", "solr"),
+                ("draft", "false")
+            ]
+        );
+        assert_eq!(document.body, b"# Home\n");
+    }
+
+    #[test]
+    fn toml_front_matter_is_split_off() {
+        let content = "\u{feff}+++\r\ndate = 2024-05-01\r\nweight = 3\r\n+++\r\nBody";
+        let document = markdown_document(content, MarkdownBody::Text).unwrap();
+        assert_eq!(fields(&document), [("date", "2024-05-01"), ("weight", "3")]);
+        assert_eq!(document.body, b"Body");
+    }
+
+    #[test]
+    fn yaml_front_matter_can_end_with_dots() {
+        let (_, front_matter, markdown) = split_front_matter("---\na: 1\n...\nBody").unwrap();
+        assert_eq!(front_matter, "a: 1\n");
+        assert_eq!(markdown, "Body");
+    }
+
+    #[test]
+    fn without_closed_front_matter_the_file_is_the_body() {
+        assert!(split_front_matter("# Title\n---\n").is_none());
+        assert!(split_front_matter("---\ntitle: Home\n").is_none());
+
+        let document = markdown_document("---\ntitle: Home\n", MarkdownBody::Text).unwrap();
+        assert!(document.fields.is_empty());
+        assert_eq!(document.body, b"---\ntitle: Home\n");
+    }
+
+    #[test]
+    fn invalid_front_matter_is_an_error() {
+        assert!(markdown_document("---\n- a\n- b\n---\n", MarkdownBody::Text).is_err());
+        assert!(markdown_document("+++\ntitle = \n+++\n", MarkdownBody::Text).is_err());
+    }
+
+    #[test]
+    fn body_rendered_to_html() {
+        let document =
+            markdown_document("---\ntitle: Home\n---\n# Home\n", MarkdownBody::Html).unwrap();
+        let page = String::from_utf8(document.body).unwrap();
+        assert!(page.contains("<meta charset=\"utf-8\">"));
+        assert!(page.contains("<h1>Home</h1>"));
+    }
+}
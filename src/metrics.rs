@@ -0,0 +1,212 @@
+use std::net::SocketAddr;
+
+use futures::future::LocalBoxFuture;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use reqwest::{header, Client};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use crate::events::{EventSink, PostEvent};
+use crate::{PostConfig, SolrPostError};
+
+/// the content type of the Prometheus text exposition format
+const TEXT_FORMAT: &str = "text/plain; version=0.0.4";
+
+/// Prometheus metrics of a run, served for scraping while the run lasts and/or pushed to a Pushgateway
+/// when it finishes, see PostConfig::metrics_listen and PostConfig::metrics_push_url
+pub(crate) struct Metrics {
+    registry: Registry,
+
+    /// the number of files to post, set once the scan finishes
+    files: IntGauge,
+
+    /// the files Solr responded to or that failed, labeled by outcome indexed, failed, or conflict
+    documents: IntCounterVec,
+
+    bytes_sent: IntCounter,
+
+    /// how long reading and posting each file took
+    request_duration: Histogram,
+
+    commits: IntCounter,
+
+    push_url: Option<String>,
+
+    /// the scrape endpoint, stopped when the run finishes
+    server: Option<JoinHandle<()>>,
+}
+
+impl Metrics {
+    /// register the metrics and start the scrape endpoint, None if the configuration doesn't ask for metrics
+    pub(crate) async fn start(config: &PostConfig) -> Result<Option<Self>, SolrPostError> {
+        if config.metrics_push_url.is_none() && config.metrics_listen.is_none() {
+            return Ok(None);
+        }
+
+        let registry = Registry::new();
+        let metrics_error = |e: prometheus::Error| SolrPostError::Metrics(e.to_string());
+
+        let files = IntGauge::new("solr_post_files", "the number of files to post")
+            .map_err(metrics_error)?;
+        let documents = IntCounterVec::new(
+            Opts::new("solr_post_documents_total", "the posted files by outcome"),
+            &["outcome"],
+        )
+        .map_err(metrics_error)?;
+        let bytes_sent =
+            IntCounter::new("solr_post_bytes_sent_total", "the body bytes sent to Solr")
+                .map_err(metrics_error)?;
+        let request_duration = Histogram::with_opts(HistogramOpts::new(
+            "solr_post_request_duration_seconds",
+            "how long reading and posting a file took",
+        ))
+        .map_err(metrics_error)?;
+        let commits = IntCounter::new("solr_post_commits_total", "the commits sent to Solr")
+            .map_err(metrics_error)?;
+
+        registry
+            .register(Box::new(files.clone()))
+            .map_err(metrics_error)?;
+        registry
+            .register(Box::new(documents.clone()))
+            .map_err(metrics_error)?;
+        registry
+            .register(Box::new(bytes_sent.clone()))
+            .map_err(metrics_error)?;
+        registry
+            .register(Box::new(request_duration.clone()))
+            .map_err(metrics_error)?;
+        registry
+            .register(Box::new(commits.clone()))
+            .map_err(metrics_error)?;
+
+        let server = match config.metrics_listen {
+            Some(addr) => Some(serve(addr, registry.clone()).await?),
+            None => None,
+        };
+
+        Ok(Some(Metrics {
+            registry,
+            files,
+            documents,
+            bytes_sent,
+            request_duration,
+            commits,
+            push_url: config.metrics_push_url.clone(),
+            server,
+        }))
+    }
+
+    fn record(&self, event: &PostEvent) {
+        match event {
+            PostEvent::ScanFinished { total, .. } => self.files.set(*total as i64),
+            PostEvent::FileIndexed(result) | PostEvent::FileFailed(result) => {
+                let outcome = match (result.is_success(), result.is_conflict()) {
+                    (true, _) => "indexed",
+                    (false, true) => "conflict",
+                    (false, false) => "failed",
+                };
+                self.documents.with_label_values(&[outcome]).inc();
+                self.bytes_sent.inc_by(result.bytes_sent);
+                self.request_duration.observe(result.elapsed.as_secs_f64());
+            }
+            PostEvent::Committed => self.commits.inc(),
+            _ => {}
+        }
+    }
+
+    /// stop the scrape endpoint and push the final values to the Pushgateway. a failed push is only
+    /// reported, the documents were posted regardless
+    pub(crate) async fn finish(self) {
+        if let Some(server) = self.server {
+            server.abort();
+        }
+
+        if let Some(push_url) = &self.push_url {
+            if let Err(e) = push(push_url, &self.registry).await {
+                eprintln!("failed to push the metrics to {}: {}", push_url, e);
+            }
+        }
+    }
+
+    /// record the events passing through to the sink
+    pub(crate) fn sink<'a>(&'a self, inner: &'a mut dyn EventSink) -> MetricsSink<'a> {
+        MetricsSink {
+            metrics: self,
+            inner,
+        }
+    }
+}
+
+/// Records the events of a run in the metrics before passing them on
+pub(crate) struct MetricsSink<'a> {
+    metrics: &'a Metrics,
+    inner: &'a mut dyn EventSink,
+}
+
+impl EventSink for MetricsSink<'_> {
+    fn emit(&mut self, event: PostEvent) -> LocalBoxFuture<'_, ()> {
+        self.metrics.record(&event);
+        self.inner.emit(event)
+    }
+}
+
+/// the metrics in the Prometheus text format
+fn encode(registry: &Registry) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&registry.gather(), &mut buffer)
+        .expect("encoding to memory can't fail");
+    buffer
+}
+
+/// serve the metrics to every request on addr, any path works e.g. /metrics
+async fn serve(addr: SocketAddr, registry: Registry) -> Result<JoinHandle<()>, SolrPostError> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| SolrPostError::Metrics(format!("failed to listen on {}: {}", addr, e)))?;
+
+    Ok(tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let body = encode(&registry);
+            tokio::spawn(async move {
+                // the request doesn't matter, read it so the client sees a clean response
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request).await;
+
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    TEXT_FORMAT,
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes()).await;
+                let _ = stream.write_all(&body).await;
+            });
+        }
+    }))
+}
+
+/// push the metrics to the solr_post job on the Pushgateway, replacing the values of the previous run
+async fn push(push_url: &str, registry: &Registry) -> Result<(), SolrPostError> {
+    let url = format!("{}/metrics/job/solr_post", push_url.trim_end_matches('/'));
+    let response = Client::new()
+        .post(&url)
+        .header(header::CONTENT_TYPE, TEXT_FORMAT)
+        .body(encode(registry))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(SolrPostError::Response {
+            url,
+            status: response.status(),
+            solr_error: None,
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use std::{fs::File, path::Path};
+
+use memmap2::Mmap;
+use regex::{bytes, Regex};
+
+/// The content regex filters compiled to match against the bytes of memory mapped files,
+/// so large files don't have to be read into a heap String to be scanned
+pub(crate) struct MmapFilters {
+    exclude_regex: Option<bytes::Regex>,
+    include_regex: Option<bytes::Regex>,
+}
+
+impl MmapFilters {
+    /// compile the exclude and include regexes for matching bytes
+    pub(crate) fn new(exclude_regex: Option<&Regex>, include_regex: Option<&Regex>) -> Self {
+        MmapFilters {
+            exclude_regex: exclude_regex.and_then(|re| bytes::Regex::new(re.as_str()).ok()),
+            include_regex: include_regex.and_then(|re| bytes::Regex::new(re.as_str()).ok()),
+        }
+    }
+
+    /// memory map the file and apply the exclude and include rules to its content.
+    /// returns None if the file could not be mapped (e.g. empty files on some platforms)
+    /// so the caller can fall back to reading the file normally
+    pub(crate) fn is_match(&self, path: &Path) -> Option<bool> {
+        let file = File::open(path).ok()?;
+
+        // Safety: the mapping is only read for the duration of this call. If another process
+        // truncates the file while it is mapped, reading it may crash the process (SIGBUS),
+        // so mmap_scan should only be used on files that aren't modified during the scan
+        let mmap = unsafe { Mmap::map(&file) }.ok()?;
+
+        // exclude takes precedence over include
+        if let Some(exclude_regex) = self.exclude_regex.as_ref() {
+            if exclude_regex.is_match(&mmap) {
+                return Some(false);
+            }
+        }
+
+        if let Some(include_regex) = self.include_regex.as_ref() {
+            if !include_regex.is_match(&mmap) {
+                return Some(false);
+            }
+        }
+
+        Some(true)
+    }
+}
@@ -0,0 +1,62 @@
+use std::{path::Path, sync::Arc};
+
+use futures::future::LocalBoxFuture;
+
+use crate::events::{EventSink, PostEvent};
+use crate::SolrPostError;
+
+/// Observes the progress of solr_post_with_observer, every method defaults to a no-op so only
+/// the interesting ones need to be implemented. Methods take &self so the observer can be shared
+/// as an Arc, use atomics or a Mutex for state
+pub trait ProgressObserver: Send + Sync {
+    /// called with the total number of files to index
+    fn on_start(&self, _total: u64) {}
+
+    /// called with the number of files indexed so far
+    fn on_next(&self, _indexed_count: u64) {}
+
+    /// called with the path and error of a file that failed to post or got a non 2xx response
+    fn on_error(&self, _file_path: &Path, _error: &SolrPostError) {}
+
+    /// called when the indexing is complete
+    fn on_finish(&self) {}
+}
+
+/// Adapts a ProgressObserver to the events of a run
+pub(crate) struct ObserverSink {
+    pub(crate) observer: Arc<dyn ProgressObserver>,
+
+    /// the number of files Solr has responded to, passed to on_next
+    pub(crate) indexed_count: u64,
+}
+
+impl ObserverSink {
+    /// count a file Solr responded to and notify the observer
+    fn next(&mut self) {
+        self.indexed_count += 1;
+        self.observer.on_next(self.indexed_count);
+    }
+}
+
+impl EventSink for ObserverSink {
+    fn emit(&mut self, event: PostEvent) -> LocalBoxFuture<'_, ()> {
+        match event {
+            PostEvent::ScanFinished { total, .. } => self.observer.on_start(total as u64),
+            PostEvent::FileIndexed(_) => self.next(),
+            PostEvent::FileFailed(file_result) => {
+                if let Some(error) = &file_result.error {
+                    self.observer.on_error(&file_result.path, error);
+                }
+
+                // a status means Solr responded, count it towards the progress
+                if file_result.status.is_some() {
+                    self.next();
+                }
+            }
+            PostEvent::Finished(_) => self.observer.on_finish(),
+            _ => {}
+        }
+
+        Box::pin(std::future::ready(()))
+    }
+}
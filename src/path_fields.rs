@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use regex::Regex;
+
+/// the path of the file below directory_path with / separators on every platform e.g. "docs/en/guide.html"
+pub(crate) fn slash_path(relative_path: &Path) -> String {
+    relative_path
+        .iter()
+        .map(|component| component.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// the named groups of the regexes matching the path of the file below directory_path as fields,
+/// see PostConfig::path_fields. e.g. ("lang", "en") for "docs/en/guide.html" and "^docs/(?P<lang>[a-z]{2})/"
+pub(crate) fn path_fields(regexes: &[Regex], relative_path: &Path) -> Vec<(String, String)> {
+    let path = slash_path(relative_path);
+
+    let mut fields = Vec::new();
+    for regex in regexes {
+        let Some(captures) = regex.captures(&path) else {
+            continue;
+        };
+        for name in regex.capture_names().flatten() {
+            if let Some(value) = captures.name(name).filter(|value| !value.is_empty()) {
+                fields.push((name.to_string(), value.as_str().to_string()));
+            }
+        }
+    }
+    fields
+}
+
+/// the directories of the path of the file below directory_path as the fields named for their depth,
+/// see PostConfig::directory_fields. e.g. ("category", "docs"), ("subcategory", "en") for "docs/en/guide.html"
+pub(crate) fn directory_fields(names: &[String], relative_path: &Path) -> Vec<(String, String)> {
+    let Some(directories) = relative_path.parent() else {
+        return Vec::new();
+    };
+
+    names
+        .iter()
+        .zip(directories.iter())
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(name, directory)| (name.clone(), directory.to_string_lossy().into_owned()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn pairs(fields: &[(String, String)]) -> Vec<(&str, &str)> {
+        fields
+            .iter()
+            .map(|(field, value)| (field.as_str(), value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn named_groups_are_fields() {
+        let regexes = [
+            Regex::new(r"^docs/(?P<lang>[a-z]{2})/").unwrap(),
+            Regex::new(r"(?P<year>\d{4})/(?P<month>\d{2})?/?[^/]+$").unwrap(),
+            Regex::new(r"^blog/(?P<never>.*)").unwrap(),
+        ];
+        let fields = path_fields(&regexes, Path::new("docs/en/2024/guide.html"));
+        assert_eq!(pairs(&fields), [("lang", "en"), ("year", "2024")]);
+    }
+
+    #[test]
+    fn unnamed_groups_are_not_fields() {
+        let regexes = [Regex::new(r"^(docs)/(?P<section>[^/]+)/").unwrap()];
+        let fields = path_fields(&regexes, Path::new("docs/api/index.html"));
+        assert_eq!(pairs(&fields), [("section", "api")]);
+    }
+
+    #[test]
+    fn directories_are_fields_named_for_their_depth() {
+        let names = [
+            "category".to_string(),
+            String::new(),
+            "topic".to_string(),
+            "unused".to_string(),
+        ];
+        let fields = directory_fields(&names, Path::new("docs/en/setup/guide.html"));
+        assert_eq!(pairs(&fields), [("category", "docs"), ("topic", "setup")]);
+        assert!(directory_fields(&names, Path::new("index.html")).is_empty());
+    }
+
+    #[test]
+    fn slash_paths() {
+        let path: PathBuf = ["docs", "en", "guide.html"].iter().collect();
+        assert_eq!(slash_path(&path), "docs/en/guide.html");
+    }
+}
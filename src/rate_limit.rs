@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Spaces requests evenly so no more than max_requests_per_second are sent, concurrency alone
+/// doesn't cap the rate when the documents are tiny
+pub(crate) struct RateLimiter {
+    /// the time between two requests
+    interval: Duration,
+
+    /// the earliest time the next request may be sent
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(max_requests_per_second: f64) -> Self {
+        RateLimiter {
+            // a rate that isn't a positive number doesn't limit anything, the builder rejects those
+            interval: Duration::try_from_secs_f64(1.0 / max_requests_per_second)
+                .unwrap_or_default(),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// wait for the next free slot, slots are handed out in the order they are requested
+    pub(crate) async fn acquire(&self) {
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            // an idle limiter doesn't save up slots for a burst
+            let slot = (*next_slot).max(Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn requests_are_spaced_by_the_interval() {
+        let limiter = RateLimiter::new(100.0);
+        let started = Instant::now();
+        for _ in 0..5 {
+            limiter.acquire().await;
+        }
+        // the first slot is free, the next four are 10ms apart
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn an_idle_limiter_does_not_save_up_a_burst() {
+        let limiter = RateLimiter::new(50.0);
+        limiter.acquire().await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // the slots refill one at a time, the idle time only frees the next one
+        let started = Instant::now();
+        limiter.acquire().await;
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn a_rate_that_is_not_positive_does_not_wait() {
+        let limiter = RateLimiter::new(0.0);
+        let started = Instant::now();
+        for _ in 0..100 {
+            limiter.acquire().await;
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
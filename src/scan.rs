@@ -0,0 +1,442 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+#[cfg(feature = "watch")]
+use wax::Pattern;
+use wax::{Glob, WalkEntry, WalkError};
+
+use crate::failure_report::FailureReport;
+use crate::ignore_files::IgnoreFiles;
+#[cfg(feature = "mmap")]
+use crate::mmap_scan::MmapFilters;
+use crate::path_fields::slash_path;
+use crate::{PostConfig, SolrPostError};
+
+/// The files found to index after applying the extension and content filters
+pub(crate) struct ScannedFiles {
+    /// the paths of the files to index
+    pub(crate) files: HashSet<String>,
+
+    /// the number of files skipped by the content regex filters
+    pub(crate) skipped: usize,
+
+    /// the number of files that could not be read
+    pub(crate) failed: usize,
+}
+
+/// walk the directories for files with the configured extensions or globs and apply the content regex filters,
+/// or take the files of PostConfig::from_report or PostConfig::file_list as they are
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "scan",
+        skip_all,
+        fields(
+            directory = %config.directory_path.display(),
+            files = tracing::field::Empty,
+            skipped = tracing::field::Empty,
+            failed = tracing::field::Empty,
+        )
+    )
+)]
+pub(crate) fn scan_files(config: &PostConfig) -> Result<ScannedFiles, SolrPostError> {
+    if let Some(report) = &config.from_report {
+        return Ok(ScannedFiles {
+            files: FailureReport::files(report)?,
+            skipped: 0,
+            failed: 0,
+        });
+    }
+    if let Some(file_list) = &config.file_list {
+        return Ok(ScannedFiles {
+            files: listed_files(config, file_list)?,
+            skipped: 0,
+            failed: 0,
+        });
+    }
+
+    let glob_expressions = glob_expressions(config);
+    let globs = glob_expressions
+        .iter()
+        .map(|glob_expression| Glob::new(glob_expression))
+        .collect::<Result<Vec<_>, _>>()?;
+    // the excluded subtrees aren't descended into, and the files excluded by their path aren't read
+    let mut ignore_files = config.respect_ignore_files.then(IgnoreFiles::default);
+    let mut files: Vec<Result<WalkEntry, WalkError>> = Vec::new();
+    for directory in config.directories() {
+        for glob in &globs {
+            files.extend(
+                glob.walk(directory)
+                    .not(config.exclude_globs.iter().map(String::as_str))?
+                    .filter(|file| match file {
+                        Ok(entry) => {
+                            entry
+                                .path()
+                                .strip_prefix(directory)
+                                .map_or(true, |relative_path| {
+                                    passes_path_filters(config, relative_path)
+                                })
+                                && !ignore_files.as_mut().is_some_and(|ignore_files| {
+                                    ignore_files.is_ignored(entry.path())
+                                })
+                        }
+                        Err(_) => true,
+                    }),
+            );
+        }
+    }
+
+    // a file matched by several globs or under overlapping directories is only scanned once
+    let mut walked = HashSet::new();
+    files.retain(|file| match file {
+        Ok(entry) => walked.insert(
+            entry
+                .path()
+                .canonicalize()
+                .unwrap_or_else(|_| entry.path().to_path_buf()),
+        ),
+        Err(_) => true,
+    });
+
+    // files that could not be read while scanning
+    let failed_count = AtomicUsize::new(0);
+
+    // files skipped by the content regex filters
+    let skipped_count = AtomicUsize::new(0);
+
+    // files to index
+    let files_to_index = Arc::new(RwLock::new(HashSet::<String>::new()));
+
+    // this clone is just so the main thread can hold onto a reference, to then print out later
+    let files_to_index_ref = files_to_index.clone();
+
+    // compile the content regexes for matching memory mapped files
+    #[cfg(feature = "mmap")]
+    let mmap_filters = config
+        .mmap_scan
+        .then(|| MmapFilters::new(config.exclued_regex.as_ref(), config.include_regex.as_ref()));
+
+    // Scan for .html files that need indexing and store them in a vector
+    files.par_iter().for_each(|file| match file {
+        Ok(entry) => {
+            let path = entry.path();
+            let path_str = path.to_string_lossy();
+
+            // the sidecar files are read along with their documents, see PostConfig::sidecar_suffix
+            if is_sidecar(config, &path_str) {
+                return;
+            }
+
+            // memory map the file for the content check when mmap_scan is set
+            #[cfg(feature = "mmap")]
+            let mapped_match = mmap_filters
+                .as_ref()
+                .and_then(|mmap_filters| mmap_filters.is_match(path));
+            #[cfg(not(feature = "mmap"))]
+            let mapped_match: Option<bool> = None;
+
+            let should_index = match mapped_match {
+                Some(should_index) => should_index,
+                None => match read_file(path) {
+                    // binary files e.g. pdf are matched against their lossy utf-8 conversion
+                    Ok(contents) => {
+                        passes_content_filters(config, &String::from_utf8_lossy(&contents))
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        failed_count.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                },
+            };
+
+            if !should_index {
+                // this file should not be indexed, skip it and continue to the next file
+                skipped_count.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+
+            let mut files_to_index_set = files_to_index.write().expect("rwlock poisoned");
+            files_to_index_set.insert(path_str.to_string());
+        }
+        Err(e) => eprintln!("error: {}", e),
+    });
+
+    let files = files_to_index_ref.read().expect("rwlock poisoned").clone();
+
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::Span::current();
+        span.record("files", files.len());
+        span.record("skipped", skipped_count.load(Ordering::Relaxed));
+        span.record("failed", failed_count.load(Ordering::Relaxed));
+    }
+
+    Ok(ScannedFiles {
+        files,
+        skipped: skipped_count.into_inner(),
+        failed: failed_count.into_inner(),
+    })
+}
+
+/// the paths of the files in the file list, a relative path is below directory_path
+fn listed_files(config: &PostConfig, file_list: &Path) -> Result<HashSet<String>, SolrPostError> {
+    let content = fs::read_to_string(file_list).map_err(|source| SolrPostError::Io {
+        path: file_list.to_path_buf(),
+        source,
+    })?;
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            config
+                .directory_path
+                .join(line)
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect())
+}
+
+/// the globs the directories are walked with, PostConfig::globs or else one matching the file extensions
+/// e.g. "**/*.{html,txt}"
+fn glob_expressions(config: &PostConfig) -> Vec<String> {
+    match config.globs.is_empty() {
+        true => vec![format!("**/*.{{{}}}", config.file_extensions.join(","))],
+        false => config.globs.clone(),
+    }
+}
+
+/// true if the path of a file below its directory is matched by the globs the directories are walked with,
+/// by none of the exclude_globs, passes the path regex rules, and isn't ignored by the ignore files
+#[cfg(feature = "watch")]
+pub(crate) fn is_selected(config: &PostConfig, relative_path: &Path, path: &Path) -> bool {
+    let is_match = |glob_expression: &String| {
+        Glob::new(glob_expression).is_ok_and(|glob| glob.is_match(relative_path))
+    };
+    glob_expressions(config).iter().any(is_match)
+        && !config.exclude_globs.iter().any(is_match)
+        && passes_path_filters(config, relative_path)
+        && !(config.respect_ignore_files && IgnoreFiles::default().is_ignored(path))
+}
+
+/// apply the exclude and include path regex rules to the path of a file below its directory, returns true
+/// if the file should be indexed. exclude takes precedence over include
+fn passes_path_filters(config: &PostConfig, relative_path: &Path) -> bool {
+    if config.exclude_path_regex.is_none() && config.include_path_regex.is_none() {
+        return true;
+    }
+
+    let path = slash_path(relative_path);
+    !config
+        .exclude_path_regex
+        .as_ref()
+        .is_some_and(|exclude_path_regex| exclude_path_regex.is_match(&path))
+        && config
+            .include_path_regex
+            .as_ref()
+            .is_none_or(|include_path_regex| include_path_regex.is_match(&path))
+}
+
+/// true if a single file is matched by the globs and passes the content regex rules, e.g. a file that just changed
+#[cfg(feature = "watch")]
+pub(crate) fn should_index(
+    config: &PostConfig,
+    relative_path: &Path,
+    path: &Path,
+) -> Result<bool, SolrPostError> {
+    if !is_selected(config, relative_path, path)
+        || !path.is_file()
+        || is_sidecar(config, &path.to_string_lossy())
+    {
+        return Ok(false);
+    }
+
+    let contents = read_file(path)?;
+    Ok(passes_content_filters(
+        config,
+        &String::from_utf8_lossy(&contents),
+    ))
+}
+
+/// true for the metadata sidecar files, see PostConfig::sidecar_suffix
+fn is_sidecar(config: &PostConfig, path: &str) -> bool {
+    config
+        .sidecar_suffix
+        .as_deref()
+        .is_some_and(|suffix| path.ends_with(suffix))
+}
+
+/// apply the exclude and include content regex rules, returns true if the file should be indexed
+fn passes_content_filters(config: &PostConfig, contents: &str) -> bool {
+    // exclude and include rules. Note if exclude takes precedence over include
+
+    if let Some(exclude_regex) = config.exclued_regex.as_ref() {
+        if exclude_regex.is_match(contents) {
+            // this file should be excluded
+            return false;
+        }
+    }
+
+    if let Some(include_regex) = config.include_regex.as_ref() {
+        if !include_regex.is_match(contents) {
+            // this file should not be included
+            return false;
+        }
+    }
+
+    true
+}
+
+/// read the content of a file as bytes, so binary documents e.g. pdf, docx, xlsx can be posted
+pub(crate) fn read_file(path: &Path) -> Result<Vec<u8>, SolrPostError> {
+    fs::read(path).map_err(|source| SolrPostError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use regex::Regex;
+
+    use super::*;
+
+    fn config(exclude_path_regex: Option<&str>, include_path_regex: Option<&str>) -> PostConfig {
+        PostConfig {
+            exclude_path_regex: exclude_path_regex.map(|regex| Regex::new(regex).unwrap()),
+            include_path_regex: include_path_regex.map(|regex| Regex::new(regex).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn path_filters_pass_without_regexes() {
+        assert!(passes_path_filters(
+            &config(None, None),
+            Path::new("a.html")
+        ));
+    }
+
+    #[test]
+    fn exclude_path_regex() {
+        let config = config(Some(r"(-draft\.html$|^archive/)"), None);
+        assert!(passes_path_filters(&config, Path::new("docs/page.html")));
+        assert!(!passes_path_filters(
+            &config,
+            Path::new("docs/page-draft.html")
+        ));
+        assert!(!passes_path_filters(
+            &config,
+            Path::new("archive/2019/page.html")
+        ));
+        assert!(passes_path_filters(
+            &config,
+            Path::new("docs/archive/page.html")
+        ));
+    }
+
+    #[test]
+    fn include_path_regex() {
+        let config = config(None, Some("^(docs|blog)/"));
+        assert!(passes_path_filters(&config, Path::new("docs/page.html")));
+        assert!(!passes_path_filters(&config, Path::new("about.html")));
+    }
+
+    #[test]
+    fn exclude_path_regex_takes_precedence() {
+        let config = config(Some("-draft"), Some("^docs/"));
+        assert!(!passes_path_filters(
+            &config,
+            Path::new("docs/page-draft.html")
+        ));
+    }
+
+    #[test]
+    fn path_regexes_are_case_sensitive() {
+        let config = config(Some("^Archive/"), None);
+        assert!(passes_path_filters(&config, Path::new("archive/page.html")));
+    }
+
+    #[test]
+    fn content_filters() {
+        let config = PostConfig {
+            exclued_regex: Some(Regex::new("no_index").unwrap()),
+            include_regex: Some(Regex::new("index_me").unwrap()),
+            ..Default::default()
+        };
+        assert!(passes_content_filters(&config, "please index_me"));
+        assert!(!passes_content_filters(&config, "index_me no_index"));
+        assert!(!passes_content_filters(&config, "anything else"));
+    }
+
+    #[test]
+    fn glob_expressions_of_the_extensions() {
+        let config = PostConfig {
+            file_extensions: vec!["html".to_string(), "txt".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(glob_expressions(&config), ["**/*.{html,txt}"]);
+
+        let config = PostConfig {
+            globs: vec!["docs/**/*.pdf".to_string()],
+            ..config
+        };
+        assert_eq!(glob_expressions(&config), ["docs/**/*.pdf"]);
+    }
+
+    #[test]
+    fn listed_files_are_below_the_directory() {
+        let file_list =
+            std::env::temp_dir().join(format!("solr_post_{}.txt", uuid::Uuid::new_v4()));
+        fs::write(
+            &file_list,
+            "docs/a.html\n\n  blog/b.html  \r\n/srv/c.html\n",
+        )
+        .unwrap();
+        let config = PostConfig {
+            directory_path: PathBuf::from("/var/www"),
+            ..Default::default()
+        };
+
+        let files = listed_files(&config, &file_list).unwrap();
+        fs::remove_file(&file_list).unwrap();
+        assert_eq!(
+            files,
+            HashSet::from([
+                "/var/www/docs/a.html".to_string(),
+                "/var/www/blog/b.html".to_string(),
+                "/srv/c.html".to_string(),
+            ])
+        );
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn selected_files() {
+        let config = PostConfig {
+            file_extensions: vec!["html".to_string()],
+            exclude_globs: vec!["**/node_modules/**".to_string()],
+            ..config(Some("-draft"), None)
+        };
+        let is_selected = |relative_path: &str| {
+            is_selected(&config, Path::new(relative_path), Path::new(relative_path))
+        };
+        assert!(is_selected("docs/a.html"));
+        assert!(!is_selected("docs/a.txt"));
+        assert!(!is_selected("lib/node_modules/x/a.html"));
+        assert!(!is_selected("docs/a-draft.html"));
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::SolrPostError;
+
+/// the suffix of the metadata sidecar files when none is configured e.g. "page.html.meta.json"
+pub const DEFAULT_SIDECAR_SUFFIX: &str = ".meta.json";
+
+/// the path of the sidecar file of a document e.g. "page.html.meta.json" for "page.html"
+fn sidecar_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut sidecar_path = path.as_os_str().to_owned();
+    sidecar_path.push(suffix);
+    PathBuf::from(sidecar_path)
+}
+
+/// read the key/value pairs of the document's sidecar file as fields, none when it has no sidecar.
+/// an array is a field per value, nested objects are skipped
+pub(crate) async fn read_sidecar_fields(
+    path: &Path,
+    suffix: &str,
+) -> Result<Vec<(String, String)>, SolrPostError> {
+    let sidecar_path = sidecar_path(path, suffix);
+    let content = match tokio::fs::read(&sidecar_path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(source) => {
+            return Err(SolrPostError::Io {
+                path: sidecar_path,
+                source,
+            })
+        }
+    };
+
+    let invalid = |message: String| SolrPostError::InvalidDocument {
+        path: sidecar_path.clone(),
+        message,
+    };
+    let object = match serde_json::from_slice(&content) {
+        Ok(Value::Object(object)) => object,
+        Ok(_) => return Err(invalid("expected a JSON object".to_string())),
+        Err(e) => return Err(invalid(e.to_string())),
+    };
+
+    let mut fields = Vec::new();
+    for (key, value) in object {
+        match value {
+            Value::Array(values) => fields.extend(
+                values
+                    .into_iter()
+                    .filter_map(scalar)
+                    .map(|value| (key.clone(), value)),
+            ),
+            value => fields.extend(scalar(value).map(|value| (key, value))),
+        }
+    }
+    Ok(fields)
+}
+
+fn scalar(value: Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// a document next to its sidecar file in a temporary directory, removed when dropped
+    struct Document(PathBuf);
+
+    impl Document {
+        fn new(sidecar: Option<&str>) -> Self {
+            let directory =
+                std::env::temp_dir().join(format!("solr_post_{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&directory).unwrap();
+            let path = directory.join("page.html");
+            fs::write(&path, "<p>page</p>").unwrap();
+            if let Some(sidecar) = sidecar {
+                fs::write(sidecar_path(&path, DEFAULT_SIDECAR_SUFFIX), sidecar).unwrap();
+            }
+            Document(path)
+        }
+
+        async fn fields(&self) -> Result<Vec<(String, String)>, SolrPostError> {
+            read_sidecar_fields(&self.0, DEFAULT_SIDECAR_SUFFIX).await
+        }
+    }
+
+    impl Drop for Document {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(self.0.parent().unwrap());
+        }
+    }
+
+    #[test]
+    fn sidecar_next_to_the_document() {
+        assert_eq!(
+            sidecar_path(Path::new("/site/page.html"), ".meta.json"),
+            Path::new("/site/page.html.meta.json")
+        );
+    }
+
+    #[tokio::test]
+    async fn sidecar_values_are_fields() {
+        let document = Document::new(Some(
+            r#"{"author": "Ann", "tags": ["rust", 2, {"nested": true}], "public": true, "meta": {"a": 1}, "none": null}"#,
+        ));
+        let fields = document.fields().await.unwrap();
+        let fields: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|(field, value)| (field.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("author", "Ann"),
+                ("public", "true"),
+                ("tags", "rust"),
+                ("tags", "2")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_document_without_a_sidecar_has_no_fields() {
+        assert!(Document::new(None).fields().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_sidecar_that_is_not_an_object_is_invalid() {
+        for sidecar in ["[1, 2]", "{"] {
+            let fields = Document::new(Some(sidecar)).fields().await;
+            assert!(matches!(fields, Err(SolrPostError::InvalidDocument { .. })));
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use regex::Regex;
+use reqwest::Response;
+use serde_json::Value;
+
+use crate::SolrPostError;
+
+/// The error Solr describes in the body of a non success response
+#[derive(Debug, Clone)]
+pub struct SolrError {
+    /// the reason Solr gives e.g. "ERROR: [doc=/docs/a.json] unknown field 'titel'"
+    pub message: String,
+
+    /// the error code in the body, usually the HTTP status
+    pub code: Option<u16>,
+
+    /// the Java stack trace of the server error, only sent for 5xx errors
+    pub trace: Option<String>,
+}
+
+/// the <lst name="error"> section of an XML response
+static XML_ERROR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?s)<lst name="error">(.*?)</lst>"#).unwrap());
+
+/// a named value of an XML response e.g. <int name="QTime">12</int>
+static XML_VALUE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)<(?:str|int|long) name="(msg|code|trace|QTime)">(.*?)</(?:str|int|long)>"#)
+        .unwrap()
+});
+
+/// the response if its status is a success, otherwise SolrPostError::Response with the error Solr describes in the body
+pub(crate) async fn error_for_status(response: Response) -> Result<Response, SolrPostError> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+
+    let url = response.url().to_string();
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+
+    Err(SolrPostError::Response {
+        url,
+        status,
+        solr_error: parse_error(&body),
+    })
+}
+
+/// the error of a JSON (wt=json, the default since Solr 7) or XML Solr response, None if the body has no error
+pub(crate) fn parse_error(body: &str) -> Option<SolrError> {
+    if let Ok(response) = serde_json::from_str::<Value>(body) {
+        let error = &response["error"];
+        let message = error["msg"].as_str()?;
+
+        return Some(SolrError {
+            message: message.to_string(),
+            code: error["code"].as_u64().map(|code| code as u16),
+            trace: error["trace"].as_str().map(str::to_string),
+        });
+    }
+
+    let error = XML_ERROR.captures(body)?.get(1)?.as_str();
+    let value = |name: &str| {
+        XML_VALUE
+            .captures_iter(error)
+            .find(|captures| &captures[1] == name)
+            .map(|captures| unescape_xml(&captures[2]))
+    };
+
+    Some(SolrError {
+        message: value("msg")?,
+        code: value("code").and_then(|code| code.parse().ok()),
+        trace: value("trace"),
+    })
+}
+
+/// how long Solr took to handle the request according to the QTime of the response header
+pub(crate) fn parse_qtime(body: &str) -> Option<Duration> {
+    let qtime = match serde_json::from_str::<Value>(body) {
+        Ok(response) => response["responseHeader"]["QTime"].as_u64()?,
+        Err(_) => XML_VALUE
+            .captures_iter(body)
+            .find(|captures| &captures[1] == "QTime")?[2]
+            .parse()
+            .ok()?,
+    };
+
+    Some(Duration::from_millis(qtime))
+}
+
+fn unescape_xml(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_error() {
+        let body = r#"{
+            "responseHeader": {"status": 400, "QTime": 7},
+            "error": {
+                "metadata": ["error-class", "org.apache.solr.common.SolrException"],
+                "msg": "ERROR: [doc=/docs/a.json] unknown field 'titel'",
+                "code": 400
+            }
+        }"#;
+        let error = parse_error(body).unwrap();
+        assert_eq!(
+            error.message,
+            "ERROR: [doc=/docs/a.json] unknown field 'titel'"
+        );
+        assert_eq!(error.code, Some(400));
+        assert_eq!(error.trace, None);
+        assert_eq!(parse_qtime(body), Some(Duration::from_millis(7)));
+    }
+
+    #[test]
+    fn json_without_an_error() {
+        let body = r#"{"responseHeader": {"status": 0, "QTime": 3}}"#;
+        assert!(parse_error(body).is_none());
+    }
+
+    #[test]
+    fn xml_error() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<response>
+<lst name="responseHeader"><int name="status">500</int><int name="QTime">12</int></lst>
+<lst name="error">
+  <str name="msg">can&apos;t index &lt;doc&gt; &amp; more</str>
+  <str name="trace">java.lang.NullPointerException
+	at org.apache.solr.Handler</str>
+  <int name="code">500</int>
+</lst>
+</response>"#;
+        let error = parse_error(body).unwrap();
+        assert_eq!(error.message, "can't index <doc> & more");
+        assert_eq!(error.code, Some(500));
+        assert!(error
+            .trace
+            .unwrap()
+            .starts_with("java.lang.NullPointerException"));
+        assert_eq!(parse_qtime(body), Some(Duration::from_millis(12)));
+    }
+
+    #[test]
+    fn html_error_pages_have_no_solr_error() {
+        let body = "<html><head><title>Error 404 Not Found</title></head>\
+            <body><h2>HTTP ERROR 404 Not Found</h2></body></html>";
+        assert!(parse_error(body).is_none());
+        assert_eq!(parse_qtime(body), None);
+    }
+
+    #[test]
+    fn missing_response_header_has_no_qtime() {
+        assert_eq!(parse_qtime(r#"{"error": {"msg": "bad"}}"#), None);
+        assert_eq!(parse_qtime(""), None);
+        assert_eq!(parse_error("").map(|error| error.message), None);
+    }
+}
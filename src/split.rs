@@ -0,0 +1,112 @@
+use std::path::Path;
+
+use mime_guess::from_path;
+
+/// separates the id of a file from the part number in the ids of its parts e.g. "/logs/app.log#part2"
+const PART_ID_SEPARATOR: &str = "#part";
+
+/// the largest split_large_files, each file being split holds a part of this size in memory
+pub const MAX_SPLIT_SIZE: u64 = 256 * 1024 * 1024;
+
+/// true for the plain text files split_large_files applies to e.g. "app.log" or "notes.txt"
+pub(crate) fn is_plain_text(path: &Path) -> bool {
+    from_path(path)
+        .first()
+        .is_some_and(|mime| mime == mime_guess::mime::TEXT_PLAIN)
+}
+
+/// the id of a part of a file, the parts are numbered from 1 so the ids stay the same between runs
+pub(crate) fn part_id(id: &str, part: usize) -> String {
+    format!("{}{}{}", id, PART_ID_SEPARATOR, part)
+}
+
+/// the queries matching the document of a whole file, posted before it grew past the split size, and the
+/// documents of every part of it e.g. "{!prefix f=id}/logs/app.log#part"
+pub(crate) fn file_queries(id: &str) -> [String; 2] {
+    [
+        format!("{{!term f=id}}{}", id),
+        format!("{{!prefix f=id}}{}{}", id, PART_ID_SEPARATOR),
+    ]
+}
+
+/// the id of the file a part belongs to, or the id itself when it isn't the id of a part
+pub(crate) fn part_file(id: &str) -> &str {
+    match id.rsplit_once(PART_ID_SEPARATOR) {
+        Some((file, part)) if !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()) => file,
+        _ => id,
+    }
+}
+
+/// where a full buffer is cut into a part: after its last line end, or when a line is longer than
+/// a part, before a UTF-8 character cut off by the end of the buffer
+pub(crate) fn part_end(buffer: &[u8]) -> usize {
+    if let Some(newline) = buffer.iter().rposition(|b| *b == b'\n') {
+        return newline + 1;
+    }
+
+    match std::str::from_utf8(buffer) {
+        Err(e) if e.error_len().is_none() && e.valid_up_to() > 0 => e.valid_up_to(),
+        _ => buffer.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn part_ids_round_trip() {
+        let id = part_id("/logs/app.log", 2);
+        assert_eq!(id, "/logs/app.log#part2");
+        assert_eq!(part_file(&id), "/logs/app.log");
+    }
+
+    #[test]
+    fn file_queries_match_the_file_and_part_ids() {
+        assert_eq!(
+            file_queries("/logs/app.log"),
+            [
+                "{!term f=id}/logs/app.log",
+                "{!prefix f=id}/logs/app.log#part"
+            ]
+        );
+        assert!(part_id("/logs/app.log", 12).starts_with("/logs/app.log#part"));
+    }
+
+    #[test]
+    fn part_file_of_other_ids() {
+        assert_eq!(part_file("/logs/app.log"), "/logs/app.log");
+        assert_eq!(part_file("/logs/app.log#part"), "/logs/app.log#part");
+        assert_eq!(part_file("/logs/app.log#partial"), "/logs/app.log#partial");
+        assert_eq!(part_file("/docs/a#part1/b.txt"), "/docs/a#part1/b.txt");
+    }
+
+    #[test]
+    fn part_end_after_the_last_line() {
+        assert_eq!(part_end(b"one\ntwo\nthr"), 8);
+        assert_eq!(part_end(b"one\n"), 4);
+    }
+
+    #[test]
+    fn part_end_of_a_long_line() {
+        assert_eq!(part_end(b"no line end"), 11);
+
+        // "é" is cut off after its first byte
+        let buffer = "abcé".as_bytes();
+        assert_eq!(part_end(&buffer[..4]), 3);
+        assert_eq!(part_end(buffer), 5);
+    }
+
+    #[test]
+    fn part_end_of_invalid_utf8() {
+        assert_eq!(part_end(&[b'a', 0xff, b'b']), 3);
+        assert_eq!(part_end(&[0xc3]), 1);
+    }
+
+    #[test]
+    fn plain_text_files() {
+        assert!(is_plain_text(Path::new("app.log")));
+        assert!(is_plain_text(Path::new("notes.txt")));
+        assert!(!is_plain_text(Path::new("index.html")));
+    }
+}
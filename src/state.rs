@@ -0,0 +1,308 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::SolrPostError;
+
+/// bumped when the layout of the state file changes, older state files are ignored
+const STATE_VERSION: u64 = 1;
+
+/// What was indexed for a file, used to tell if it changed since the last run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FileState {
+    /// modification time in nanoseconds since the unix epoch
+    mtime: u64,
+    size: u64,
+
+    /// hex sha256 of the contents
+    hash: String,
+}
+
+/// The files indexed by previous runs, persisted as JSON in the state file
+pub(crate) struct IndexState {
+    /// keyed by the absolute path of the file, the id of its document
+    files: HashMap<String, FileState>,
+}
+
+impl IndexState {
+    /// load the state file, a missing file is an empty state
+    pub(crate) fn load(path: &Path) -> Result<Self, SolrPostError> {
+        let content = match fs::read(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(IndexState {
+                    files: HashMap::new(),
+                })
+            }
+            Err(source) => {
+                return Err(SolrPostError::Io {
+                    path: path.to_path_buf(),
+                    source,
+                })
+            }
+        };
+
+        let invalid = |message: String| SolrPostError::StateFile {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let state: Value = serde_json::from_slice(&content).map_err(|e| invalid(e.to_string()))?;
+        if state["version"].as_u64() != Some(STATE_VERSION) {
+            return Ok(IndexState {
+                files: HashMap::new(),
+            });
+        }
+
+        let files = state["files"]
+            .as_object()
+            .ok_or_else(|| invalid("missing files".to_string()))?
+            .iter()
+            .filter_map(|(path, file)| {
+                Some((
+                    path.clone(),
+                    FileState {
+                        mtime: file["mtime"].as_u64()?,
+                        size: file["size"].as_u64()?,
+                        hash: file["sha256"].as_str()?.to_string(),
+                    },
+                ))
+            })
+            .collect();
+
+        Ok(IndexState { files })
+    }
+
+    /// write the state file, through a temporary file so an interrupted write doesn't lose the previous state
+    pub(crate) fn save(&self, path: &Path) -> Result<(), SolrPostError> {
+        let files: Map<String, Value> = self
+            .files
+            .iter()
+            .map(|(path, file)| {
+                (
+                    path.clone(),
+                    json!({ "mtime": file.mtime, "size": file.size, "sha256": file.hash }),
+                )
+            })
+            .collect();
+        let state = json!({ "version": STATE_VERSION, "files": files });
+
+        let io_error = |source| SolrPostError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let temp_path = PathBuf::from(temp_path);
+
+        fs::write(&temp_path, state.to_string()).map_err(io_error)?;
+        fs::rename(&temp_path, path).map_err(io_error)
+    }
+
+    /// the current state of the scanned files keyed by absolute path, files that can't be read are left out.
+    /// the recorded hash is reused when the mtime and size haven't changed so unchanged files aren't read
+    pub(crate) fn current(&self, files: &HashSet<String>) -> HashMap<String, FileState> {
+        files
+            .par_iter()
+            .filter_map(|file| {
+                let path = absolute(file);
+                let metadata = fs::metadata(&path).ok()?;
+                let mtime = metadata
+                    .modified()
+                    .ok()?
+                    .duration_since(UNIX_EPOCH)
+                    .ok()?
+                    .as_nanos() as u64;
+                let size = metadata.len();
+
+                let hash = match self.files.get(&path) {
+                    Some(previous) if previous.mtime == mtime && previous.size == size => {
+                        previous.hash.clone()
+                    }
+                    _ => hash_file(Path::new(&path)).ok()?,
+                };
+
+                Some((path, FileState { mtime, size, hash }))
+            })
+            .collect()
+    }
+
+    /// true if the file has the same contents as when it was last indexed, a touched file is unchanged
+    pub(crate) fn is_unchanged(&self, file: &str, current: &HashMap<String, FileState>) -> bool {
+        let path = absolute(file);
+        match (self.files.get(&path), current.get(&path)) {
+            (Some(previous), Some(current)) => previous.hash == current.hash,
+            _ => false,
+        }
+    }
+
+    /// record the state of a file that was indexed
+    pub(crate) fn record(&mut self, path: &Path, current: &HashMap<String, FileState>) {
+        let path = path.to_string_lossy();
+        if let Some(file) = current.get(path.as_ref()) {
+            self.files.insert(path.into_owned(), file.clone());
+        }
+    }
+
+    /// forget the files that are no longer scanned e.g. deleted files, and refresh the mtime of
+    /// touched files with unchanged contents so they aren't hashed again next run
+    pub(crate) fn update_scanned(&mut self, current: &HashMap<String, FileState>) {
+        self.files.retain(|path, file| match current.get(path) {
+            Some(current) => {
+                if current.hash == file.hash {
+                    *file = current.clone();
+                }
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+/// the absolute path of a scanned file as a string, the same as the document id and FileResult::path
+pub(crate) fn absolute(file: &str) -> String {
+    let path = PathBuf::from(file);
+    path.canonicalize()
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// hex sha256 of the file contents, streamed so large files aren't read into memory
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+
+    /// a directory of files, removed when dropped
+    struct Directory(PathBuf);
+
+    impl Directory {
+        fn new() -> Self {
+            let root = std::env::temp_dir().join(format!("solr_post_{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&root).unwrap();
+            Directory(root.canonicalize().unwrap())
+        }
+
+        /// write the file with the modification time, returns its path
+        fn write(&self, name: &str, content: &str, mtime: u64) -> String {
+            let path = self.0.join(name);
+            fs::write(&path, content).unwrap();
+            let file = fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(mtime))
+                .unwrap();
+            path.to_string_lossy().into_owned()
+        }
+    }
+
+    impl Drop for Directory {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// the state after indexing the files
+    fn indexed(files: &[&String]) -> (IndexState, HashMap<String, FileState>) {
+        let mut state = IndexState {
+            files: HashMap::new(),
+        };
+        let current = state.current(&files.iter().map(|file| file.to_string()).collect());
+        for file in files {
+            state.record(Path::new(file), &current);
+        }
+        (state, current)
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let directory = Directory::new();
+        let page = directory.write("page.html", "<p>one</p>", 1_000);
+        let (state, _) = indexed(&[&page]);
+
+        let state_file = directory.0.join("state.json");
+        state.save(&state_file).unwrap();
+        let loaded = IndexState::load(&state_file).unwrap();
+        assert_eq!(loaded.files, state.files);
+        assert_eq!(loaded.files[&page].mtime, 1_000_000_000_000);
+    }
+
+    #[test]
+    fn missing_or_older_state_files_are_empty() {
+        let directory = Directory::new();
+        let state_file = directory.0.join("state.json");
+        assert!(IndexState::load(&state_file).unwrap().files.is_empty());
+
+        fs::write(&state_file, r#"{"version":0,"files":{"/a":{}}}"#).unwrap();
+        assert!(IndexState::load(&state_file).unwrap().files.is_empty());
+
+        fs::write(&state_file, "{").unwrap();
+        assert!(IndexState::load(&state_file).is_err());
+    }
+
+    #[test]
+    fn unchanged_mtime_and_size_reuse_the_recorded_hash() {
+        let directory = Directory::new();
+        let page = directory.write("page.html", "<p>one</p>", 1_000);
+        let (mut state, _) = indexed(&[&page]);
+        state.files.get_mut(&page).unwrap().hash = "recorded".to_string();
+
+        // the file isn't read again while its mtime and size are the same
+        let current = state.current(&HashSet::from([page.clone()]));
+        assert_eq!(current[&page].hash, "recorded");
+        assert!(state.is_unchanged(&page, &current));
+
+        // a different mtime hashes the contents again
+        directory.write("page.html", "<p>one</p>", 2_000);
+        let current = state.current(&HashSet::from([page.clone()]));
+        assert_ne!(current[&page].hash, "recorded");
+        assert!(!state.is_unchanged(&page, &current));
+    }
+
+    #[test]
+    fn touched_files_with_the_same_contents_are_unchanged() {
+        let directory = Directory::new();
+        let page = directory.write("page.html", "<p>one</p>", 1_000);
+        let other = directory.write("other.html", "<p>two</p>", 1_000);
+        let (mut state, _) = indexed(&[&page, &other]);
+
+        directory.write("page.html", "<p>one</p>", 2_000);
+        directory.write("other.html", "<p>2</p>", 1_000);
+        let current = state.current(&HashSet::from([page.clone(), other.clone()]));
+        assert!(state.is_unchanged(&page, &current));
+        assert!(!state.is_unchanged(&other, &current));
+
+        // the new mtime is kept so the touched file isn't hashed again next run
+        state.update_scanned(&current);
+        assert_eq!(state.files[&page].mtime, 2_000_000_000_000);
+    }
+
+    #[test]
+    fn files_no_longer_scanned_are_forgotten() {
+        let directory = Directory::new();
+        let page = directory.write("page.html", "<p>one</p>", 1_000);
+        let other = directory.write("other.html", "<p>two</p>", 1_000);
+        let (mut state, _) = indexed(&[&page, &other]);
+
+        let current = state.current(&HashSet::from([page.clone()]));
+        state.update_scanned(&current);
+        assert!(state.files.contains_key(&page));
+        assert!(!state.files.contains_key(&other));
+    }
+}
@@ -0,0 +1,84 @@
+use std::net::UdpSocket;
+
+use cadence::{BufferedUdpMetricSink, Counted, CountedExt, StatsdClient, Timed};
+use futures::future::LocalBoxFuture;
+
+use crate::events::{EventSink, PostEvent};
+use crate::SolrPostError;
+
+/// Sends StatsD counters and timers for every posted document, see PostConfig::statsd_addr.
+/// outcomes are DogStatsD tags, which plain StatsD servers ignore
+pub(crate) struct Statsd {
+    client: StatsdClient,
+}
+
+impl Statsd {
+    /// resolve the StatsD server, host:port e.g. localhost:8125
+    pub(crate) fn connect(addr: &str) -> Result<Self, SolrPostError> {
+        let statsd_error = |e: &dyn std::fmt::Display| {
+            SolrPostError::Metrics(format!("failed to connect to StatsD at {}: {}", addr, e))
+        };
+
+        let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| statsd_error(&e))?;
+        socket.set_nonblocking(true).map_err(|e| statsd_error(&e))?;
+        let sink = BufferedUdpMetricSink::from(addr, socket).map_err(|e| statsd_error(&e))?;
+
+        Ok(Statsd {
+            client: StatsdClient::from_sink("solr_post", sink),
+        })
+    }
+
+    fn record(&self, event: &PostEvent) {
+        // metrics are best effort, a lost packet must never fail the run
+        match event {
+            PostEvent::FileIndexed(result) | PostEvent::FileFailed(result) => {
+                let outcome = match (result.is_success(), result.is_conflict()) {
+                    (true, _) => "indexed",
+                    (false, true) => "conflict",
+                    (false, false) => "failed",
+                };
+                let _ = self
+                    .client
+                    .incr_with_tags("documents")
+                    .with_tag("outcome", outcome)
+                    .try_send();
+                let _ = self.client.count("bytes_sent", result.bytes_sent);
+                let _ = self
+                    .client
+                    .time_with_tags("request", result.elapsed)
+                    .with_tag("outcome", outcome)
+                    .try_send();
+            }
+            PostEvent::Committed => {
+                let _ = self.client.incr("commits");
+            }
+            _ => {}
+        }
+    }
+
+    /// send the metrics still buffered
+    pub(crate) fn finish(self) {
+        let _ = self.client.flush();
+    }
+
+    /// record the events passing through to the sink
+    pub(crate) fn sink<'a>(&'a self, inner: &'a mut dyn EventSink) -> StatsdSink<'a> {
+        StatsdSink {
+            statsd: self,
+            inner,
+        }
+    }
+}
+
+/// Sends the events of a run to StatsD before passing them on
+pub(crate) struct StatsdSink<'a> {
+    statsd: &'a Statsd,
+    inner: &'a mut dyn EventSink,
+}
+
+impl EventSink for StatsdSink<'_> {
+    fn emit(&mut self, event: PostEvent) -> LocalBoxFuture<'_, ()> {
+        self.statsd.record(&event);
+        self.inner.emit(event)
+    }
+}
@@ -0,0 +1,1702 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use async_compression::tokio::bufread::GzipEncoder;
+use base64::prelude::*;
+use futures::{Stream, StreamExt};
+use mime_guess::from_path;
+use reqwest::{
+    header, redirect, Body, Client, Method, NoProxy, Proxy, RequestBuilder, Response, StatusCode,
+    Url,
+};
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::sync::{OnceCell, OwnedSemaphorePermit};
+use tokio_util::io::ReaderStream;
+use tokio_util::sync::CancellationToken;
+
+use crate::adaptive_concurrency::{AdaptiveConcurrency, ConcurrencyPermit};
+use crate::batch::{batch_bodies, BatchBody, BatchFormat, Batches, PostJob};
+use crate::charset::{is_text, read_transcoded};
+use crate::cluster::{NodeRef, SolrNodes};
+#[cfg(feature = "keyring")]
+use crate::credentials::keyring_credentials;
+use crate::credentials::netrc_credentials;
+use crate::file_fields::read_file_fields;
+use crate::html_meta::{is_html, read_html_metadata};
+use crate::inflight::InflightBytes;
+use crate::json_docs::is_command_style;
+#[cfg(feature = "language")]
+use crate::language::detect_language;
+#[cfg(feature = "markdown")]
+use crate::markdown::{is_markdown, read_markdown};
+use crate::path_fields::{directory_fields, path_fields};
+use crate::rate_limit::RateLimiter;
+use crate::scan::scan_files;
+use crate::sidecar::read_sidecar_fields;
+use crate::solr_response::{parse_error, parse_qtime};
+use crate::split::{file_queries, is_plain_text, part_end, part_id};
+use crate::tls;
+use crate::version::document_version;
+use crate::zookeeper::discover_nodes;
+use crate::{
+    collection_url, delete_by_query, new_run_id, AuthScheme, CommitPolicy, CommitUrlError,
+    DocumentRoute, Login, PostConfig, PostMode, RedirectPolicy, SolrPostError,
+};
+
+/// The outcome of posting a single file to Solr
+#[derive(Debug)]
+pub struct FileResult {
+    /// the absolute path of the file
+    pub path: PathBuf,
+
+    /// the HTTP status Solr responded with, None if the file could not be read or the request could not be sent
+    pub status: Option<StatusCode>,
+
+    /// the body of the Solr response, empty if there was no response
+    pub response_body: String,
+
+    /// the number of body bytes sent to Solr
+    pub bytes_sent: u64,
+
+    /// how long reading and posting the file took
+    pub elapsed: Duration,
+
+    /// how long Solr took to handle the request according to the QTime of the response, None without a response header
+    pub qtime: Option<Duration>,
+
+    /// why the file failed, None if it was indexed
+    /// a non success status is reported as SolrPostError::Response
+    pub error: Option<SolrPostError>,
+}
+
+impl FileResult {
+    /// true if Solr accepted the document
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// true if Solr rejected the document because of a version conflict
+    pub fn is_conflict(&self) -> bool {
+        self.status == Some(StatusCode::CONFLICT)
+    }
+
+    /// true if the server failed rather than the document, a 5xx status or the request could not be sent
+    pub fn is_server_error(&self) -> bool {
+        match (&self.status, &self.error) {
+            (Some(status), _) => status.is_server_error(),
+            (None, Some(error)) => matches!(
+                error,
+                SolrPostError::Request(_) | SolrPostError::BatchRequest(_)
+            ),
+            (None, None) => false,
+        }
+    }
+
+    /// an empty result for the file, the path is made absolute
+    fn new(file: String) -> Self {
+        let file_path = PathBuf::from(file);
+        let file_path_absolute = file_path.canonicalize().unwrap_or(file_path);
+
+        FileResult {
+            path: file_path_absolute,
+            status: None,
+            response_body: String::new(),
+            bytes_sent: 0,
+            elapsed: Duration::ZERO,
+            qtime: None,
+            error: None,
+        }
+    }
+}
+
+/// Everything needed to post files that is shared by all the requests of a run
+pub(crate) struct PostContext {
+    pub(crate) config: PostConfig,
+    pub(crate) client: SolrClient,
+
+    /// the Solr update endpoint files are posted to
+    pub(crate) update_endpoint: String,
+
+    /// the URL used to commit the posted documents
+    pub(crate) commit_url: String,
+
+    /// the URL used to optimize the collection when optimize_after is set
+    pub(crate) optimize_url: Option<String>,
+
+    /// stops handing out new files, cancelled with the cancellation_token or when the run rolls back
+    pub(crate) stop: CancellationToken,
+
+    /// the /select handler used to find the documents of deleted files when sync is set
+    pub(crate) select_url: Option<String>,
+
+    /// the run id shared by every document posted in this run
+    pub(crate) run_id: String,
+
+    /// literal params tagging the document with the run id and the literals of the config
+    /// e.g. "&literal.run_id=<uuid>&literal.site=docs"
+    literal_params: String,
+
+    /// the params of the config added to every update request e.g. "&uprefix=attr_&lowernames=true"
+    extra_params: String,
+
+    /// the capture, captureAttr, and xpath params of the extract handler e.g. "&capture=div&xpath=..."
+    extract_params: String,
+
+    /// param asking Solr to commit the document in time e.g. "&commitWithin=10000", empty unless CommitPolicy::Within
+    commit_within_param: String,
+
+    /// caps the bytes in flight when max_inflight_bytes is set
+    inflight_bytes: Option<InflightBytes>,
+
+    /// spaces the requests when max_requests_per_second is set
+    rate_limiter: Option<RateLimiter>,
+
+    /// tunes the number of requests in flight when adaptive_concurrency is set
+    adaptive_concurrency: Option<AdaptiveConcurrency>,
+
+    /// the /update handler structured documents are posted to when batching, chunking JSON Lines, or in
+    /// a mode other than Extract
+    update_handler_url: Option<String>,
+
+    /// the Solr nodes the requests are spread across, discovered on first use when zk_hosts is set
+    nodes: OnceCell<SolrNodes>,
+
+    /// the canonical directories, the path of a file is relative to the first one it is below e.g. for
+    /// DocumentRoute::PathComponent
+    roots: Vec<PathBuf>,
+
+    /// the canonical strip_path, the files below it are mapped to the base_url. without one the files below
+    /// each of the roots are
+    url_root: Option<PathBuf>,
+}
+
+impl PostContext {
+    pub(crate) fn new(config: PostConfig) -> Result<Self, SolrPostError> {
+        // resolve the commit url before posting anything so a bad url doesn't leave the documents uncommitted
+        let commit_url = config.commit_url()?;
+
+        // every document posted in this invocation shares the same run id
+        let run_id = config.run_id.clone().unwrap_or_else(new_run_id);
+
+        let literal_params = literal_params(document_fields(&config, &run_id));
+
+        let update_handler_url = match config.update_batch_size().is_some()
+            || config.mode != PostMode::Extract
+            || config.jsonl_chunk_size.is_some()
+        {
+            true => Some(config.update_handler_url()?.to_string()),
+            false => None,
+        };
+
+        Ok(PostContext {
+            update_handler_url,
+            client: build_client(&config)?,
+            // build the solr post url from the config. If the update_url is set, use that, otherwise build the url
+            update_endpoint: config.update_endpoint(),
+            commit_url,
+            optimize_url: match config.optimize_after {
+                true => Some(config.optimize_url()?),
+                false => None,
+            },
+            run_id,
+            literal_params,
+            extract_params: extract_params(&config),
+            extra_params: config
+                .params
+                .iter()
+                .map(|(name, value)| {
+                    format!(
+                        "&{}={}",
+                        urlencoding::encode(name),
+                        urlencoding::encode(value)
+                    )
+                })
+                .collect(),
+            commit_within_param: match config.commit {
+                CommitPolicy::Within(ms) => format!("&commitWithin={}", ms),
+                _ => String::new(),
+            },
+            inflight_bytes: config.max_inflight_bytes.map(InflightBytes::new),
+            rate_limiter: config.max_requests_per_second.map(RateLimiter::new),
+            adaptive_concurrency: config
+                .adaptive_concurrency
+                .then(|| AdaptiveConcurrency::new(config.concurrency)),
+            select_url: match config.sync {
+                true => Some(config.select_url()?),
+                false => None,
+            },
+            stop: match &config.cancellation_token {
+                Some(cancellation_token) => cancellation_token.child_token(),
+                None => CancellationToken::new(),
+            },
+            nodes: OnceCell::new(),
+            roots: config
+                .directories()
+                .map(|directory| {
+                    directory
+                        .canonicalize()
+                        .unwrap_or_else(|_| directory.clone())
+                })
+                .collect(),
+            url_root: config
+                .strip_path
+                .as_ref()
+                .map(|url_root| url_root.canonicalize().unwrap_or_else(|_| url_root.clone())),
+            config,
+        })
+    }
+
+    /// the Solr nodes the requests are spread across, the SolrCloud nodes hosting the collection read from
+    /// ZooKeeper the first time when zk_hosts is set, or the update URL and update_urls.
+    /// None when there is a single node
+    pub(crate) async fn nodes(&self) -> Result<Option<&SolrNodes>, SolrPostError> {
+        if let Some(zk_hosts) = &self.config.zk_hosts {
+            let nodes = self
+                .nodes
+                .get_or_try_init(|| async {
+                    discover_nodes(zk_hosts, &self.config.collection)
+                        .await
+                        .map(SolrNodes::new)
+                })
+                .await?;
+            return Ok(Some(nodes));
+        }
+
+        if self.config.update_urls.is_empty() {
+            return Ok(None);
+        }
+
+        // the update URLs were validated by the builder, an invalid one is left out
+        let nodes = self
+            .nodes
+            .get_or_init(|| async {
+                let base_urls = std::iter::once(&self.update_endpoint)
+                    .chain(&self.config.update_urls)
+                    .filter_map(|url| Url::parse(url).ok())
+                    .collect();
+                SolrNodes::new(base_urls)
+            })
+            .await;
+        Ok(Some(nodes))
+    }
+
+    /// the URLs of a command e.g. the commit, one for each collection, sent to the next node when there are several
+    pub(crate) async fn command_urls(&self, url: &str) -> Result<Vec<String>, SolrPostError> {
+        let urls = self.collection_urls(url)?;
+        Ok(match self.nodes().await? {
+            Some(nodes) => urls.iter().map(|url| nodes.pick().url(url)).collect(),
+            None => urls,
+        })
+    }
+
+    /// the path of the file below the first directory it is in e.g. "tenant_a/report.pdf", the file name if it
+    /// isn't below any
+    pub(crate) fn relative_path<'a>(&self, path: &'a Path) -> &'a Path {
+        self.roots
+            .iter()
+            .chain(self.config.directories())
+            .find_map(|root| path.strip_prefix(root).ok())
+            .unwrap_or_else(|| path.file_name().map(Path::new).unwrap_or(path))
+    }
+
+    /// the URL of the file under the base_url, its path below the strip_path or else the directory it is in
+    /// with each component percent encoded e.g. "https://example.com/en/page.html" for "./build/en/page.html".
+    /// None without a base_url or when the file isn't below them
+    pub(crate) fn document_url(&self, path: &Path) -> Option<String> {
+        let base_url = self.config.base_url.as_deref()?;
+        let relative_path = match &self.url_root {
+            Some(url_root) => path.strip_prefix(url_root).ok()?,
+            None => self
+                .roots
+                .iter()
+                .find_map(|root| path.strip_prefix(root).ok())?,
+        };
+        let segments: Vec<String> = relative_path
+            .components()
+            .map(|component| {
+                urlencoding::encode(&component.as_os_str().to_string_lossy()).into_owned()
+            })
+            .collect();
+
+        Some(format!(
+            "{}/{}",
+            base_url.trim_end_matches('/'),
+            segments.join("/")
+        ))
+    }
+
+    /// the id of the document of the file, its URL or else its path, with the id_prefix
+    /// e.g. "docs:/var/www/page.html"
+    pub(crate) fn document_id(&self, path: &Path) -> String {
+        let id = self
+            .document_url(path)
+            .unwrap_or_else(|| path.to_string_lossy().into_owned());
+        match &self.config.id_prefix {
+            Some(id_prefix) => format!("{}{}", id_prefix, id),
+            None => id,
+        }
+    }
+
+    /// the path of the file of a document id, the reverse of document_id. None if the id lacks the id_prefix
+    /// or is a URL that can't be decoded
+    pub(crate) fn document_path(&self, id: &str) -> Option<PathBuf> {
+        let id = match &self.config.id_prefix {
+            Some(id_prefix) => id.strip_prefix(id_prefix.as_str())?,
+            None => id,
+        };
+
+        let url_path = self.config.base_url.as_deref().and_then(|base_url| {
+            id.strip_prefix(base_url.trim_end_matches('/'))?
+                .strip_prefix('/')
+        });
+        let Some(url_path) = url_path else {
+            return Some(PathBuf::from(id));
+        };
+        let relative_path = url_path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                urlencoding::decode(segment)
+                    .ok()
+                    .map(|segment| segment.into_owned())
+            })
+            .collect::<Option<PathBuf>>()?;
+
+        // without a strip_path the URL can be of a file under any of the directories
+        let roots = match &self.url_root {
+            Some(url_root) => std::slice::from_ref(url_root),
+            None => &self.roots,
+        };
+        let mut paths = roots.iter().map(|root| root.join(&relative_path));
+        let first = paths.next()?;
+        match first.exists() {
+            true => Some(first),
+            false => Some(paths.find(|path| path.exists()).unwrap_or(first)),
+        }
+    }
+
+    /// the `_route_` param of the file e.g. "&_route_=tenant_a", empty when the file has no route
+    fn route_param(&self, path: &Path) -> String {
+        match self
+            .config
+            .route
+            .as_ref()
+            .and_then(|route| route.route_for(self.relative_path(path)))
+        {
+            Some(route) => format!("&_route_={}", urlencoding::encode(&route)),
+            None => String::new(),
+        }
+    }
+
+    /// the URL for the collection and each of the extra_collections, the collection of the URL is replaced
+    /// e.g. "http://localhost:8983/solr/live/update" -> ["http://localhost:8983/solr/live/update", "http://localhost:8983/solr/staging/update"]
+    pub(crate) fn collection_urls(&self, url: &str) -> Result<Vec<String>, CommitUrlError> {
+        let mut urls = vec![url.to_string()];
+        for collection in &self.config.extra_collections {
+            urls.push(collection_url(url, collection)?);
+        }
+        Ok(urls)
+    }
+
+    /// wait until a request with a body of this size may be sent under the adaptive concurrency,
+    /// in flight bytes, and request rate limits
+    async fn acquire_permits(&self, body_len: u64) -> RequestPermits<'_> {
+        // wait for room under the adaptive concurrency limit, the permit records how the request went
+        let concurrency = match &self.adaptive_concurrency {
+            Some(adaptive_concurrency) => Some(adaptive_concurrency.acquire().await),
+            None => None,
+        };
+
+        // wait for room under max_inflight_bytes, the permit is held until the response is read
+        let inflight = match &self.inflight_bytes {
+            Some(inflight_bytes) => Some(inflight_bytes.acquire(body_len).await),
+            None => None,
+        };
+
+        // wait for a slot under max_requests_per_second
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+
+        RequestPermits {
+            concurrency,
+            _inflight: inflight,
+            sent: Instant::now(),
+        }
+    }
+}
+
+/// The permits a request holds until its response has been read
+struct RequestPermits<'a> {
+    concurrency: Option<ConcurrencyPermit<'a>>,
+    _inflight: Option<OwnedSemaphorePermit>,
+
+    /// when the request was sent, after waiting for the limits
+    sent: Instant,
+}
+
+impl RequestPermits<'_> {
+    /// record the response status, a response without a server error counts as a success for adaptive concurrency
+    fn completed(&mut self, status: StatusCode) {
+        if let Some(concurrency) = self.concurrency.as_mut() {
+            if !status.is_server_error() {
+                concurrency.succeeded(self.sent.elapsed());
+            }
+        }
+    }
+}
+
+/// build the http client with the default headers e.g. basic auth
+pub(crate) fn build_client(config: &PostConfig) -> Result<SolrClient, SolrPostError> {
+    let mut default_headers = header::HeaderMap::new();
+
+    // encode the username and password to base64, base64 is always a valid header value
+    let basic = |creds: &str| format!("Basic {}", BASE64_STANDARD.encode(creds));
+
+    // insert the authorization header if auth is set, a SPNEGO token is made for each request
+    let auth_value = match &config.auth {
+        Some(AuthScheme::Basic(creds)) => Some(basic(creds)),
+        Some(AuthScheme::Bearer(token)) => Some(format!("Bearer {}", token)),
+        #[cfg(feature = "kerberos")]
+        Some(AuthScheme::Negotiate) => None,
+        Some(AuthScheme::Netrc(path)) => {
+            let host = solr_host(config);
+            let creds = netrc_credentials(path.as_deref(), &host)?;
+            if creds.is_none() {
+                log::warn!("no .netrc entry for {}, posting without credentials", host);
+            }
+            creds.as_deref().map(basic)
+        }
+        #[cfg(feature = "keyring")]
+        Some(AuthScheme::Keyring { user }) => {
+            Some(basic(&keyring_credentials(user, &solr_host(config))?))
+        }
+        None => None,
+    };
+    if let Some(auth_value) = auth_value {
+        let mut auth_value = header::HeaderValue::from_str(&auth_value).map_err(|_| {
+            SolrPostError::InvalidHeader {
+                name: header::AUTHORIZATION.to_string(),
+            }
+        })?;
+        // keep the credentials out of debug output
+        auth_value.set_sensitive(true);
+        default_headers.insert(header::AUTHORIZATION, auth_value);
+    }
+
+    // the extra headers often hold API keys, keep them out of debug output too
+    for name in config.extra_headers.keys() {
+        default_headers.remove(name);
+    }
+    for (name, value) in &config.extra_headers {
+        let mut value = value.clone();
+        value.set_sensitive(true);
+        default_headers.append(name, value);
+    }
+
+    // build the client with default_headers, the cookie store keeps the session of a login or a gateway
+    let mut client_builder = Client::builder()
+        .default_headers(default_headers)
+        .cookie_store(true);
+
+    if let Some(connect_timeout) = config.connect_timeout {
+        client_builder = client_builder.connect_timeout(connect_timeout);
+    }
+
+    if let Some(request_timeout) = config.request_timeout {
+        client_builder = client_builder.timeout(request_timeout);
+    }
+
+    client_builder = client_builder.redirect(match config.redirect {
+        RedirectPolicy::Follow(max) => redirect::Policy::limited(max),
+        RedirectPolicy::Never => redirect::Policy::none(),
+    });
+
+    if let Some(local_address) = config.local_address {
+        client_builder = client_builder.local_address(local_address);
+    }
+
+    for (host, addr) in &config.resolve {
+        client_builder = client_builder.resolve(host, *addr);
+    }
+
+    // an explicit proxy still skips the hosts of NO_PROXY, without one reqwest uses the proxies of the environment
+    if let Some(proxy) = &config.proxy {
+        client_builder = client_builder.proxy(Proxy::all(proxy)?.no_proxy(NoProxy::from_env()));
+    }
+
+    // the root certificates, client certificate, and verification of the TLS backend
+    client_builder = tls::configure(config, client_builder)?;
+
+    Ok(SolrClient {
+        client: client_builder.build()?,
+        #[cfg(feature = "kerberos")]
+        negotiate: config.auth == Some(AuthScheme::Negotiate),
+        login: config.login.clone().map(Arc::new),
+        logged_in: Arc::new(OnceCell::new()),
+    })
+}
+
+/// the host of the update endpoint the stored credentials are looked up for e.g. "solr.example.com"
+fn solr_host(config: &PostConfig) -> String {
+    Url::parse(&config.update_endpoint())
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| config.host.clone())
+}
+
+/// The HTTP client of a run, a reqwest Client that also adds the authentication made for each request
+#[derive(Clone)]
+pub(crate) struct SolrClient {
+    client: Client,
+
+    /// add a fresh SPNEGO token to every request, see AuthScheme::Negotiate
+    #[cfg(feature = "kerberos")]
+    negotiate: bool,
+
+    /// the login form posted before the first request, see PostConfig::login
+    login: Option<Arc<Login>>,
+
+    /// the outcome of the login, shared by the clones of the client
+    logged_in: Arc<OnceCell<Result<(), String>>>,
+}
+
+impl SolrClient {
+    pub(crate) fn get(&self, url: impl AsRef<str>) -> RequestBuilder {
+        self.request(Method::GET, url.as_ref())
+    }
+
+    pub(crate) fn post(&self, url: impl AsRef<str>) -> RequestBuilder {
+        self.request(Method::POST, url.as_ref())
+    }
+
+    /// post the login form once, the cookie store keeps the session for the following requests.
+    /// a failed login is kept so every request fails the same way instead of logging in again
+    pub(crate) async fn login(&self) -> Result<(), SolrPostError> {
+        let Some(login) = &self.login else {
+            return Ok(());
+        };
+
+        self.logged_in
+            .get_or_init(|| async {
+                let response = self
+                    .client
+                    .post(&login.url)
+                    .form(&login.form)
+                    .send()
+                    .await
+                    .map_err(|e| format!("{}: {}", login.url, e))?;
+                match response.status().is_success() {
+                    true => {
+                        log::info!("logged in at {}", login.url);
+                        Ok(())
+                    }
+                    false => Err(format!("{} answered {}", login.url, response.status())),
+                }
+            })
+            .await
+            .clone()
+            .map_err(SolrPostError::Login)
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let request = self.client.request(method, url);
+
+        #[cfg(feature = "kerberos")]
+        if self.negotiate {
+            if let Some(negotiate) = crate::kerberos::negotiate_header(url) {
+                return request.header(header::AUTHORIZATION, negotiate);
+            }
+        }
+
+        request
+    }
+}
+
+/// Scan for files to index based on the configuration and return a stream that posts them to Solr
+/// concurrently, yielding the outcome of each file as it completes.
+/// unlike solr_post no commit is issued, use solr_commit once the stream is drained
+pub fn solr_post_stream(
+    config: PostConfig,
+) -> Result<impl Stream<Item = FileResult>, SolrPostError> {
+    let context = Arc::new(PostContext::new(config)?);
+    let scanned = scan_files(&context.config)?;
+
+    Ok(post_files(context, scanned.files))
+}
+
+/// post the files concurrently, up to the configured concurrency at a time
+pub(crate) fn post_files(
+    context: Arc<PostContext>,
+    files: impl IntoIterator<Item = String>,
+) -> impl Stream<Item = FileResult> {
+    let concurrency = context.config.concurrency;
+    let stop = context.stop.clone();
+
+    let jobs = Batches::new(
+        files.into_iter(),
+        context.config.update_batch_size(),
+        context.config.jsonl_chunk_size.is_some(),
+    );
+
+    // stop handing out new jobs once cancelled, the requests already in flight are finished
+    let jobs = jobs.take_while(move |_| !stop.is_cancelled());
+
+    futures::stream::iter(jobs.map(move |job| {
+        let context = context.clone();
+        async move {
+            match job {
+                PostJob::File(file) => vec![post_file(&context, file).await],
+                PostJob::Batch(format, files) => post_batch(&context, format, files).await,
+                PostJob::JsonLines(file) => vec![post_json_lines(&context, file).await],
+            }
+        }
+    }))
+    .buffer_unordered(concurrency)
+    .flat_map(futures::stream::iter)
+}
+
+/// read a file and post it to Solr
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "post",
+        skip_all,
+        fields(
+            path = %file,
+            status = tracing::field::Empty,
+            bytes = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+            error = tracing::field::Empty,
+        )
+    )
+)]
+async fn post_file(context: &PostContext, file: String) -> FileResult {
+    let started = Instant::now();
+    let mut result = FileResult::new(file);
+
+    let sent = match file_literal_params(context, &result.path).await {
+        Ok(literals) => match split_size(&context.config, &result.path) {
+            Some(split_size) => post_parts(context, split_size, &literals, &mut result).await,
+            None => post_whole_file(context, &literals, &mut result).await,
+        },
+        Err(e) => Err(e),
+    };
+    if let Err(e) = sent {
+        result.error = Some(e);
+    }
+
+    result.elapsed = started.elapsed();
+    #[cfg(feature = "tracing")]
+    crate::trace::record_file_result(&result);
+    result
+}
+
+/// the literal params read for the file: the fields of its directories and captured from its path, its
+/// dates, size, hash, and language, the metadata of an HTML page's head, and the fields of the sidecar file. none for the files a
+/// mode posts as is
+async fn file_literal_params(context: &PostContext, path: &Path) -> Result<String, SolrPostError> {
+    let config = &context.config;
+    if config.mode.posts_as_is(path) {
+        return Ok(String::new());
+    }
+
+    let relative_path = context.relative_path(path);
+    let mut fields = directory_fields(&config.directory_fields, relative_path);
+    fields.extend(path_fields(&config.path_fields, relative_path));
+    fields.extend(read_file_fields(config, path).await?);
+    #[cfg(feature = "language")]
+    if config.detect_language {
+        if let Some(language) = detect_language(path).await? {
+            fields.push(("language".to_string(), language));
+        }
+    }
+    if config.html_metadata && is_html(path) {
+        fields.extend(read_html_metadata(path).await?);
+    }
+    if let Some(suffix) = &config.sidecar_suffix {
+        fields.extend(read_sidecar_fields(path, suffix).await?);
+    }
+
+    Ok(literal_params(
+        fields
+            .iter()
+            .map(|(field, value)| (field.as_str(), value.as_str())),
+    ))
+}
+
+/// post the file in one request with the literal params read for it. the body of a Markdown file is
+/// sent without its front matter, which is sent as literals instead, see PostConfig::markdown. a text
+/// file that isn't UTF-8 is sent transcoded with PostConfig::transcode
+async fn post_whole_file(
+    context: &PostContext,
+    literals: &str,
+    result: &mut FileResult,
+) -> Result<(), SolrPostError> {
+    let url = format!("{}{}", file_url(context, &result.path), literals);
+
+    #[cfg(feature = "markdown")]
+    if let Some(body) = context
+        .config
+        .markdown
+        .filter(|_| is_markdown(&result.path))
+    {
+        let document = read_markdown(&result.path, body, context.config.transcode).await?;
+        let fields = document
+            .fields
+            .iter()
+            .map(|(field, value)| (field.as_str(), value.as_str()));
+        let url = format!("{}{}", url, literal_params(fields));
+        return send_to_collections(context, &url, Some(&document.body), result).await;
+    }
+
+    if context.config.transcode
+        && is_text(&result.path)
+        && !context.config.mode.posts_as_is(&result.path)
+    {
+        if let Some(content) = read_transcoded(&result.path).await? {
+            return send_to_collections(context, &url, Some(&content), result).await;
+        }
+    }
+
+    send_to_collections(context, &url, None, result).await
+}
+
+/// the size of the parts the file is split into, None unless it is a plain text file larger than
+/// split_large_files. extract_only shows what Tika makes of the whole file
+fn split_size(config: &PostConfig, path: &Path) -> Option<u64> {
+    let split_size = config.split_large_files?;
+    if config.extract_only || config.mode.posts_as_is(path) || !is_plain_text(path) {
+        return None;
+    }
+    let file_len = std::fs::metadata(path).ok()?.len();
+    (file_len > split_size).then_some(split_size)
+}
+
+/// post the file in parts of at most split_size bytes cut at line ends, each part a document of its own.
+/// the parts posted before are deleted first, so a file that shrank doesn't keep its parts past the new
+/// last one. stops at the first part that fails, the parts before it stay indexed
+async fn post_parts(
+    context: &PostContext,
+    split_size: u64,
+    literals: &str,
+    result: &mut FileResult,
+) -> Result<(), SolrPostError> {
+    let path = result.path.clone();
+    let io_error = |source| SolrPostError::Io {
+        path: path.clone(),
+        source,
+    };
+
+    let mut file = tokio::fs::File::open(&path).await.map_err(io_error)?;
+
+    // the delete is committed along with the parts
+    let queries = file_queries(&context.document_id(&path));
+    for clean_url in context.command_urls(&context.config.clean_url()?).await? {
+        for query in &queries {
+            delete_by_query(&context.client, &clean_url, query).await?;
+        }
+    }
+
+    let split_size = split_size as usize;
+    let mut buffer = Vec::with_capacity(split_size);
+    let mut part = 0;
+    loop {
+        // top up the rest of the last part to a full buffer
+        let mut len = buffer.len();
+        buffer.resize(split_size, 0);
+        while len < split_size {
+            let read = file.read(&mut buffer[len..]).await.map_err(io_error)?;
+            if read == 0 {
+                break;
+            }
+            len += read;
+        }
+        buffer.truncate(len);
+
+        if buffer.is_empty() {
+            return Ok(());
+        }
+
+        // the end of the file is the end of the last part
+        let end = match len < split_size {
+            true => len,
+            false => part_end(&buffer),
+        };
+        part += 1;
+        let url = format!("{}{}", extract_url(context, &path, Some(part)), literals);
+        send_to_collections(context, &url, Some(&buffer[..end]), result).await?;
+        buffer.drain(..end);
+    }
+}
+
+/// send the file, or the content standing in for it, to the URL of every collection. the file is indexed once every
+/// collection accepted it, see PostConfig::extra_collections
+async fn send_to_collections(
+    context: &PostContext,
+    url: &str,
+    content: Option<&[u8]>,
+    result: &mut FileResult,
+) -> Result<(), SolrPostError> {
+    for url in context.collection_urls(url)? {
+        send_file_to_nodes(context, &url, content, result).await?;
+    }
+    Ok(())
+}
+
+/// send the file to the URL, on the next node that accepts the connection when there are several nodes
+async fn send_file_to_nodes(
+    context: &PostContext,
+    url: &str,
+    content: Option<&[u8]>,
+    result: &mut FileResult,
+) -> Result<(), SolrPostError> {
+    context.client.login().await?;
+    let Some(nodes) = context.nodes().await? else {
+        return send_file(context, url, None, content, result).await;
+    };
+
+    // a node that refuses the connection never saw the file, so the next node can take it
+    let mut attempt = 1;
+    loop {
+        let node = nodes.pick();
+        match send_file(context, url, Some(&node), content, result).await {
+            Err(SolrPostError::Request(e)) if e.is_connect() => {
+                node.failed();
+                if attempt == nodes.len() {
+                    return Err(SolrPostError::Request(e));
+                }
+                attempt += 1;
+            }
+            sent => return sent,
+        }
+    }
+}
+
+/// send the file at result.path to Solr, recording the response in result. content is sent instead of
+/// the file when set e.g. a part of it, see PostConfig::split_large_files
+async fn send_file(
+    context: &PostContext,
+    url: &str,
+    node: Option<&NodeRef<'_>>,
+    content: Option<&[u8]>,
+    result: &mut FileResult,
+) -> Result<(), SolrPostError> {
+    let config = &context.config;
+
+    let solr_post_url = match node {
+        Some(node) => node.url(url),
+        None => url.to_string(),
+    };
+    let mime_type = request_content_type(config, &result.path);
+
+    // stream the file to solr so memory stays flat regardless of file size and concurrency
+    let file_body = match content {
+        Some(content) => content_body(content, config.compress_requests).await,
+        None => file_body(&result.path, config.compress_requests).await?,
+    };
+
+    let mut permits = context.acquire_permits(file_body.file_len).await;
+
+    // post the file to solr using the Apache Tika update/extract handler
+    let mut request = context
+        .client
+        .post(solr_post_url)
+        .header(header::CONTENT_TYPE, mime_type);
+
+    // the compressed size isn't known up front so compressed bodies are sent chunked
+    request = match file_body.compressed_len {
+        Some(_) => request.header(header::CONTENT_ENCODING, "gzip"),
+        None => request.header(header::CONTENT_LENGTH, file_body.file_len),
+    };
+
+    let response = request.body(file_body.body).send().await?;
+
+    result.bytes_sent += match &file_body.compressed_len {
+        Some(compressed_len) => compressed_len.load(Ordering::Relaxed),
+        None => file_body.file_len,
+    };
+
+    let status = response.status();
+    let url = response.url().to_string();
+    result.status = Some(status);
+    result.response_body = response.text().await.unwrap_or_default();
+    result.qtime = parse_qtime(&result.response_body);
+
+    permits.completed(status);
+    if let Some(node) = node {
+        node.completed(status);
+    }
+
+    if !status.is_success() {
+        return Err(SolrPostError::Response {
+            url,
+            status,
+            solr_error: parse_error(&result.response_body),
+        });
+    }
+
+    Ok(())
+}
+
+/// the params of the extract handler selecting the XHTML content Tika indexes, see PostConfig::capture
+fn extract_params(config: &PostConfig) -> String {
+    let mut params: String = config
+        .capture
+        .iter()
+        .map(|element| format!("&capture={}", urlencoding::encode(element)))
+        .collect();
+    if config.capture_attr {
+        params.push_str("&captureAttr=true");
+    }
+    if let Some(xpath) = &config.xpath {
+        params.push_str(&format!("&xpath={}", urlencoding::encode(xpath)));
+    }
+    params
+}
+
+/// the fields as literal params of the extract handler e.g. "&literal.title=Home"
+fn literal_params<'a>(fields: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    fields
+        .into_iter()
+        .map(|(field, value)| {
+            format!(
+                "&literal.{}={}",
+                urlencoding::encode(field),
+                urlencoding::encode(value)
+            )
+        })
+        .collect()
+}
+
+/// the fields added to every document, the run id when it is indexed and the literals of the config
+fn document_fields<'a>(config: &'a PostConfig, run_id: &'a str) -> Vec<(&'a str, &'a str)> {
+    config
+        .index_run_id
+        .then_some((config.run_id_field.as_str(), run_id))
+        .into_iter()
+        .chain(
+            config
+                .literals
+                .iter()
+                .map(|(field, value)| (field.as_str(), value.as_str())),
+        )
+        .collect()
+}
+
+/// the extract handler URL a file is posted to, with its path as the resource name and document id
+pub(crate) fn file_url(context: &PostContext, path: &Path) -> String {
+    if context.config.mode.posts_as_is(path) {
+        return update_file_url(context, path);
+    }
+
+    extract_url(context, path, None)
+}
+
+/// the extract handler URL of the file, or of a part of it with the part number as the part literal
+/// and appended to the id, see PostConfig::split_large_files
+fn extract_url(context: &PostContext, path: &Path, part: Option<usize>) -> String {
+    // url encode the file path string
+    let file_path = path.to_string_lossy();
+    let file_path_encoded = urlencoding::encode(&file_path).into_owned();
+    let id = context.document_id(path);
+    let id_literal = match part {
+        Some(part) => format!(
+            "{}&literal.part={}",
+            urlencoding::encode(&part_id(&id, part)),
+            part
+        ),
+        None => urlencoding::encode(&id).into_owned(),
+    };
+
+    // format the solr post url using file_path_encoded as the resource.name and the id as literal.id
+    let mut solr_post_url = format!(
+        "{0}?resource.name={1}&literal.id={2}{3}{4}{5}{6}{7}",
+        context.update_endpoint,
+        file_path_encoded,
+        id_literal,
+        context.literal_params,
+        context.commit_within_param,
+        context.route_param(path),
+        context.extract_params,
+        context.extra_params
+    );
+
+    // the parts of a file share its URL
+    if let Some(url) = context.document_url(path) {
+        solr_post_url.push_str(&format!("&literal.url={}", urlencoding::encode(&url)));
+    }
+
+    // Solr returns the extracted content and metadata instead of indexing the document
+    if context.config.extract_only {
+        solr_post_url.push_str("&extractOnly=true");
+    }
+
+    // send the document version so Solr rejects the update if the indexed document is newer,
+    // the version is of the whole file so it doesn't apply to its parts
+    if context.config.optimistic_concurrency && part.is_none() {
+        if let Some(version) = document_version(path) {
+            solr_post_url.push_str(&format!("&literal._version_={}", version));
+        }
+    }
+
+    solr_post_url
+}
+
+/// the URL a file the mode posts as is goes to: /update/json/docs with the split and f params for JSON
+/// documents, otherwise /update. the file is sent unchanged, so the literals can't be added to it
+fn update_file_url(context: &PostContext, path: &Path) -> String {
+    let update_handler_url = context
+        .update_handler_url
+        .as_deref()
+        .expect("the update handler url is set in modes other than extract");
+
+    let mut params = String::new();
+    let handler_url = match context.config.mode {
+        PostMode::JsonDocs if !is_command_style(path) => {
+            if let Some(split) = &context.config.json_split {
+                params.push_str(&format!("&split={}", urlencoding::encode(split)));
+            }
+            for field in &context.config.json_fields {
+                params.push_str(&format!("&f={}", urlencoding::encode(field)));
+            }
+            format!("{}/json/docs", update_handler_url)
+        }
+        PostMode::Csv => {
+            // like a CSV batch, the run id and literals go to the CSV loader as literal params
+            params.push_str(&csv_params(&context.config, path));
+            params.push_str(&context.literal_params);
+            update_handler_url.to_string()
+        }
+        _ => update_handler_url.to_string(),
+    };
+    params.push_str(&context.commit_within_param);
+    params.push_str(&context.route_param(path));
+    params.push_str(&context.extra_params);
+
+    match params.strip_prefix('&') {
+        Some(params) => format!("{}?{}", handler_url, params),
+        None => handler_url,
+    }
+}
+
+/// the separator, header, fieldnames, skipLines, and encapsulator params of the CSV loader,
+/// the values of .tsv files are separated by tabs unless the config sets the separator
+fn csv_params(config: &PostConfig, path: &Path) -> String {
+    let is_tsv = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("tsv"));
+    let mut params = String::new();
+    if let Some(separator) = config.csv_separator.or(is_tsv.then_some('\t')) {
+        params.push_str(&format!(
+            "&separator={}",
+            urlencoding::encode(&separator.to_string())
+        ));
+    }
+    if !config.csv_header {
+        params.push_str("&header=false");
+    }
+    if !config.csv_fieldnames.is_empty() {
+        params.push_str(&format!(
+            "&fieldnames={}",
+            urlencoding::encode(&config.csv_fieldnames.join(","))
+        ));
+    }
+    if let Some(skip_lines) = config.csv_skip_lines {
+        params.push_str(&format!("&skipLines={}", skip_lines));
+    }
+    if let Some(encapsulator) = config.csv_encapsulator {
+        params.push_str(&format!(
+            "&encapsulator={}",
+            urlencoding::encode(&encapsulator.to_string())
+        ));
+    }
+    params
+}
+
+/// the Content-Type the file is posted with, the files a mode posts as is are sent as its format
+pub(crate) fn request_content_type(config: &PostConfig, path: &Path) -> String {
+    #[cfg(feature = "markdown")]
+    if config.markdown == Some(crate::MarkdownBody::Html) && is_markdown(path) {
+        return String::from("text/html");
+    }
+
+    match config.mode {
+        PostMode::JsonDocs if config.mode.posts_as_is(path) => String::from("application/json"),
+        PostMode::XmlUpdate if config.mode.posts_as_is(path) => String::from("text/xml"),
+        PostMode::Csv if config.mode.posts_as_is(path) => String::from("text/csv"),
+        _ => file_content_type(config, path),
+    }
+}
+
+/// the mime type of the file from its mime_overrides entry, or guessed from the file path e.g. "text/html"
+pub(crate) fn file_content_type(config: &PostConfig, path: &Path) -> String {
+    let mime_override = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(|extension| config.mime_overrides.get(&extension.to_lowercase()));
+    match mime_override {
+        Some(mime_type) => mime_type.clone(),
+        None => from_path(path).first_or_octet_stream().to_string(),
+    }
+}
+
+/// read the structured files of a batch and post them to the /update handler in as few requests as possible
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "post_batch",
+        skip_all,
+        fields(format = ?format, files = files.len())
+    )
+)]
+async fn post_batch(
+    context: &PostContext,
+    format: BatchFormat,
+    files: Vec<String>,
+) -> Vec<FileResult> {
+    let started = Instant::now();
+    let mut results: Vec<FileResult> = files.into_iter().map(FileResult::new).collect();
+
+    let mut documents = Vec::with_capacity(results.len());
+    for (index, result) in results.iter_mut().enumerate() {
+        match tokio::fs::read(&result.path).await {
+            Ok(content) => documents.push((index, content)),
+            Err(source) => {
+                result.error = Some(SolrPostError::Io {
+                    path: result.path.clone(),
+                    source,
+                })
+            }
+        }
+    }
+
+    let fields = document_fields(&context.config, &context.run_id);
+    let (bodies, invalid) =
+        batch_bodies(format, documents, &fields, context.config.batch_max_bytes);
+    for (index, message) in invalid {
+        results[index].error = Some(SolrPostError::InvalidDocument {
+            path: results[index].path.clone(),
+            message,
+        });
+    }
+
+    let url = batch_url(context, format);
+    for batch_body in bodies {
+        send_batch(context, format, &url, batch_body, &mut results).await;
+    }
+
+    for result in results.iter_mut() {
+        result.elapsed = started.elapsed();
+    }
+    results
+}
+
+/// post a JSON Lines file in chunks of jsonl_chunk_size documents, each chunk a JSON array, reading the file
+/// a line at a time. stops at the first chunk that fails, the chunks before it stay indexed
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "post_json_lines",
+        skip_all,
+        fields(
+            path = %file,
+            status = tracing::field::Empty,
+            bytes = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+            error = tracing::field::Empty,
+        )
+    )
+)]
+async fn post_json_lines(context: &PostContext, file: String) -> FileResult {
+    let started = Instant::now();
+    let mut result = FileResult::new(file);
+    let chunk_size = context
+        .config
+        .jsonl_chunk_size
+        .expect("JSON Lines are posted in chunks when the chunk size is set");
+    let url = json_lines_url(context, &result.path);
+
+    // the JSON of PostMode::JsonDocs is posted as it is, see PostConfig::literals
+    let fields = match context.config.mode {
+        PostMode::JsonDocs => Vec::new(),
+        _ => document_fields(&context.config, &context.run_id),
+    };
+    let invalid = |result: &FileResult, message: String| SolrPostError::InvalidDocument {
+        path: result.path.clone(),
+        message,
+    };
+
+    let mut lines = match tokio::fs::File::open(&result.path).await {
+        Ok(file) => BufReader::new(file).lines(),
+        Err(source) => {
+            result.error = Some(SolrPostError::Io {
+                path: result.path.clone(),
+                source,
+            });
+            result.elapsed = started.elapsed();
+            return result;
+        }
+    };
+
+    let mut docs = Vec::with_capacity(chunk_size);
+    let mut line_number = 0;
+    let mut documents = 0;
+    let mut bytes_sent = 0;
+    loop {
+        let line = match lines.next_line().await {
+            Ok(line) => line,
+            Err(source) => {
+                result.error = Some(SolrPostError::Io {
+                    path: result.path.clone(),
+                    source,
+                });
+                break;
+            }
+        };
+
+        if let Some(line) = &line {
+            line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Value>(line) {
+                Ok(Value::Object(mut doc)) => {
+                    for (field, value) in &fields {
+                        doc.insert(field.to_string(), Value::String(value.to_string()));
+                    }
+                    docs.push(Value::Object(doc));
+                }
+                Ok(_) => {
+                    let message = format!("line {}: expected a JSON object", line_number);
+                    result.error = Some(invalid(&result, message));
+                    break;
+                }
+                Err(e) => {
+                    let message = format!("line {}: {}", line_number, e);
+                    result.error = Some(invalid(&result, message));
+                    break;
+                }
+            }
+        }
+
+        if docs.len() == chunk_size || (line.is_none() && !docs.is_empty()) {
+            documents += docs.len();
+            let body = serde_json::to_vec(&Value::Array(std::mem::take(&mut docs)))
+                .expect("JSON values always serialize");
+            let batch_body = BatchBody {
+                indexes: vec![0],
+                body,
+            };
+            send_batch(
+                context,
+                BatchFormat::Json,
+                &url,
+                batch_body,
+                std::slice::from_mut(&mut result),
+            )
+            .await;
+            bytes_sent += result.bytes_sent;
+            if result.error.is_some() {
+                break;
+            }
+        }
+
+        if line.is_none() {
+            if documents == 0 {
+                result.error = Some(invalid(&result, "no JSON documents".to_string()));
+            }
+            break;
+        }
+    }
+
+    result.bytes_sent = bytes_sent;
+    result.elapsed = started.elapsed();
+    #[cfg(feature = "tracing")]
+    crate::trace::record_file_result(&result);
+    result
+}
+
+/// the URL the chunks of a JSON Lines file are posted to, /update/json/docs in PostMode::JsonDocs
+/// otherwise the /update handler like a batch
+pub(crate) fn json_lines_url(context: &PostContext, path: &Path) -> String {
+    match context.config.mode {
+        PostMode::JsonDocs => update_file_url(context, path),
+        _ => batch_url(context, BatchFormat::Json),
+    }
+}
+
+/// send one batch body to the /update handler, recording the response in the results of its files
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        name = "batch_request",
+        skip_all,
+        fields(
+            documents = batch_body.indexes.len(),
+            status = tracing::field::Empty,
+            bytes = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        )
+    )
+)]
+async fn send_batch(
+    context: &PostContext,
+    format: BatchFormat,
+    url: &str,
+    batch_body: BatchBody,
+    results: &mut [FileResult],
+) {
+    // the batch is indexed once every collection accepted it, see PostConfig::extra_collections
+    let urls = match context.collection_urls(url) {
+        Ok(urls) => urls,
+        Err(e) => {
+            for index in batch_body.indexes {
+                results[index].error = Some(SolrPostError::CommitUrl(e.clone()));
+            }
+            return;
+        }
+    };
+
+    let body_len = batch_body.body.len() as u64;
+    let body = match context.config.compress_requests {
+        true => gzip(&batch_body.body).await,
+        false => batch_body.body,
+    };
+    let bytes_sent = body.len() as u64;
+
+    let mut permits = context.acquire_permits(body_len).await;
+    #[cfg(feature = "tracing")]
+    let started = Instant::now();
+
+    // every file of the batch fails the same way when the login or the discovery of the SolrCloud nodes failed
+    if let Err(SolrPostError::Login(message)) = context.client.login().await {
+        for index in batch_body.indexes {
+            results[index].error = Some(SolrPostError::Login(message.clone()));
+        }
+        return;
+    }
+    let nodes = match context.nodes().await {
+        Ok(nodes) => nodes,
+        Err(e) => {
+            let message = match e {
+                SolrPostError::ZooKeeper(message) => message,
+                e => e.to_string(),
+            };
+            for index in batch_body.indexes {
+                results[index].error = Some(SolrPostError::ZooKeeper(message.clone()));
+            }
+            return;
+        }
+    };
+
+    let mut responses = Vec::with_capacity(urls.len());
+    for url in &urls {
+        let response = match send_batch_body(context, nodes, url, format, body.clone()).await {
+            Ok(response) => response,
+            Err(e) => {
+                let e = Arc::new(e);
+                for index in batch_body.indexes {
+                    results[index].error = Some(SolrPostError::BatchRequest(e.clone()));
+                }
+                return;
+            }
+        };
+
+        let status = response.status();
+        responses.push((
+            status,
+            response.url().to_string(),
+            response.text().await.unwrap_or_default(),
+        ));
+        if !status.is_success() {
+            break;
+        }
+    }
+
+    // the files get the response of the last collection, the one that failed if any
+    let (status, url, response_body) = responses.pop().expect("a request was sent");
+    let qtime = parse_qtime(&response_body);
+    let solr_error = parse_error(&response_body);
+    permits.completed(status);
+
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::Span::current();
+        span.record("status", status.as_u16());
+        span.record("bytes", bytes_sent);
+        span.record("latency_ms", started.elapsed().as_millis() as u64);
+    }
+
+    // split the bytes sent to every collection evenly over the files of the batch
+    let share = bytes_sent * (responses.len() as u64 + 1) / batch_body.indexes.len() as u64;
+
+    for index in batch_body.indexes {
+        let result = &mut results[index];
+        result.status = Some(status);
+        result.response_body = response_body.clone();
+        result.bytes_sent = share;
+        result.qtime = qtime;
+
+        if !status.is_success() {
+            result.error = Some(SolrPostError::Response {
+                url: url.clone(),
+                status,
+                solr_error: solr_error.clone(),
+            });
+        }
+    }
+}
+
+/// post the body of a batch, to the next SolrCloud node that accepts the connection when zk_hosts is set
+async fn send_batch_body(
+    context: &PostContext,
+    nodes: Option<&SolrNodes>,
+    url: &str,
+    format: BatchFormat,
+    body: Vec<u8>,
+) -> Result<Response, reqwest::Error> {
+    let request = |url: String| {
+        let mut request = context
+            .client
+            .post(url)
+            .header(header::CONTENT_TYPE, format.content_type());
+        if context.config.compress_requests {
+            request = request.header(header::CONTENT_ENCODING, "gzip");
+        }
+        request
+    };
+
+    let Some(nodes) = nodes else {
+        return request(url.to_string()).body(body).send().await;
+    };
+
+    let mut attempt = 1;
+    loop {
+        let node = nodes.pick();
+        match request(node.url(url)).body(body.clone()).send().await {
+            Err(e) if e.is_connect() && attempt < nodes.len() => {
+                node.failed();
+                attempt += 1;
+            }
+            Err(e) => {
+                if e.is_connect() {
+                    node.failed();
+                }
+                return Err(e);
+            }
+            Ok(response) => {
+                node.completed(response.status());
+                return Ok(response);
+            }
+        }
+    }
+}
+
+/// the /update handler URL a batch of structured files is posted to
+pub(crate) fn batch_url(context: &PostContext, format: BatchFormat) -> String {
+    let update_handler_url = context
+        .update_handler_url
+        .as_deref()
+        .expect("the update handler url is set when batching");
+
+    // JSON and XML documents have the run id and literals added to them, CSV takes them as literal params
+    let mut params = String::new();
+    if format == BatchFormat::Csv {
+        params.push_str(&context.literal_params);
+    }
+    params.push_str(&context.commit_within_param);
+    params.push_str(&context.extra_params);
+
+    // a batch holds files from any directory, so only a fixed route applies, see PostConfig::route
+    if let Some(DocumentRoute::Fixed(route)) = &context.config.route {
+        params.push_str(&format!("&_route_={}", urlencoding::encode(route)));
+    }
+
+    match params.strip_prefix('&') {
+        Some(params) => format!("{}?{}", update_handler_url, params),
+        None => update_handler_url.to_string(),
+    }
+}
+
+/// gzip encode an in memory request body
+async fn gzip(body: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    GzipEncoder::new(body)
+        .read_to_end(&mut compressed)
+        .await
+        .expect("reading from memory can't fail");
+    compressed
+}
+
+/// A file opened as a streaming request body
+struct FileBody {
+    body: Body,
+
+    /// the size of the file on disk
+    file_len: u64,
+
+    /// counts the gzip compressed bytes as they are streamed, None if the body isn't compressed
+    compressed_len: Option<Arc<AtomicU64>>,
+}
+
+/// content read from a file as a request body, gzip encoded when compress is set
+async fn content_body(content: &[u8], compress: bool) -> FileBody {
+    let file_len = content.len() as u64;
+    if !compress {
+        return FileBody {
+            body: Body::from(content.to_vec()),
+            file_len,
+            compressed_len: None,
+        };
+    }
+
+    let compressed = gzip(content).await;
+    FileBody {
+        compressed_len: Some(Arc::new(AtomicU64::new(compressed.len() as u64))),
+        body: Body::from(compressed),
+        file_len,
+    }
+}
+
+/// open the file as a streaming request body, gzip encoding it as it is read when compress is set
+async fn file_body(path: &Path, compress: bool) -> Result<FileBody, SolrPostError> {
+    let io_error = |source| SolrPostError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let file = tokio::fs::File::open(path).await.map_err(io_error)?;
+    let file_len = file.metadata().await.map_err(io_error)?.len();
+
+    if !compress {
+        return Ok(FileBody {
+            body: Body::wrap_stream(ReaderStream::new(file)),
+            file_len,
+            compressed_len: None,
+        });
+    }
+
+    let compressed_len = Arc::new(AtomicU64::new(0));
+    let counter = compressed_len.clone();
+    let compressed =
+        ReaderStream::new(GzipEncoder::new(BufReader::new(file))).inspect(move |chunk| {
+            if let Ok(chunk) = chunk {
+                counter.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+            }
+        });
+
+    Ok(FileBody {
+        body: Body::wrap_stream(compressed),
+        file_len,
+        compressed_len: Some(compressed_len),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn csv_config() -> PostConfig {
+        PostConfig {
+            mode: PostMode::Csv,
+            ..PostConfig::default()
+        }
+    }
+
+    #[test]
+    fn csv_params_default_to_none() {
+        assert_eq!(csv_params(&csv_config(), Path::new("a.csv")), "");
+    }
+
+    #[test]
+    fn tsv_files_are_separated_by_tabs() {
+        assert_eq!(
+            csv_params(&csv_config(), Path::new("a.TSV")),
+            "&separator=%09"
+        );
+
+        let config = PostConfig {
+            csv_separator: Some(';'),
+            ..csv_config()
+        };
+        assert_eq!(csv_params(&config, Path::new("a.tsv")), "&separator=%3B");
+    }
+
+    #[test]
+    fn csv_without_header_sends_the_fieldnames() {
+        let config = PostConfig {
+            csv_header: false,
+            csv_fieldnames: vec!["id".to_string(), String::new(), "title".to_string()],
+            csv_skip_lines: Some(2),
+            ..csv_config()
+        };
+        assert_eq!(
+            csv_params(&config, Path::new("a.csv")),
+            "&header=false&fieldnames=id%2C%2Ctitle&skipLines=2"
+        );
+    }
+
+    #[test]
+    fn csv_files_get_the_literal_params() {
+        let config = PostConfig {
+            literals: vec![("source".to_string(), "export".to_string())],
+            csv_header: false,
+            csv_fieldnames: vec!["id".to_string()],
+            ..csv_config()
+        };
+        let context = PostContext::new(config).unwrap();
+        assert_eq!(
+            update_file_url(&context, Path::new("a.csv")),
+            "http://localhost:8983/solr/collection1/update?header=false&fieldnames=id&literal.source=export"
+        );
+    }
+
+    #[test]
+    fn csv_encapsulator_is_encoded() {
+        let config = PostConfig {
+            csv_separator: Some('|'),
+            csv_encapsulator: Some('"'),
+            ..csv_config()
+        };
+        assert_eq!(
+            csv_params(&config, Path::new("a.csv")),
+            "&separator=%7C&encapsulator=%22"
+        );
+    }
+
+    #[tokio::test]
+    async fn sidecar_fields_are_merged_into_the_literals() {
+        let directory = std::env::temp_dir().join(format!("solr_post_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(directory.join("en")).unwrap();
+        let page = directory.join("en").join("page.html");
+        std::fs::write(&page, "<p>page</p>").unwrap();
+        std::fs::write(
+            directory.join("en").join("page.html.meta.json"),
+            r#"{"author": "Ann", "tags": ["a b", "c"]}"#,
+        )
+        .unwrap();
+
+        let config = PostConfig {
+            directory_path: directory.clone(),
+            path_fields: vec![regex::Regex::new("^(?P<lang>[a-z]{2})/").unwrap()],
+            sidecar_suffix: Some(crate::DEFAULT_SIDECAR_SUFFIX.to_string()),
+            ..PostConfig::default()
+        };
+        let context = PostContext::new(config).unwrap();
+        let literals = file_literal_params(&context, &page).await;
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            literals.unwrap(),
+            "&literal.lang=en&literal.author=Ann&literal.tags=a%20b&literal.tags=c"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashSet;
+
+use log::info;
+use serde_json::Value;
+
+use crate::solr_response::error_for_status;
+use crate::split::part_file;
+use crate::stream::{PostContext, SolrClient};
+use crate::SolrPostError;
+
+/// the number of ids fetched per page and deleted per request
+const PAGE_SIZE: usize = 1000;
+
+/// delete the documents under the directories whose file no longer exists from every collection,
+/// returns the number deleted
+pub(crate) async fn delete_missing(context: &PostContext) -> Result<usize, SolrPostError> {
+    let select_urls = context
+        .command_urls(
+            context
+                .select_url
+                .as_deref()
+                .expect("the select url is set when syncing"),
+        )
+        .await?;
+    let delete_urls = context.command_urls(&context.config.delete_url()?).await?;
+
+    let mut deleted = 0;
+    for (select_url, delete_url) in select_urls.iter().zip(&delete_urls) {
+        deleted += delete_missing_from(context, select_url, delete_url).await?;
+    }
+    Ok(deleted)
+}
+
+/// delete the documents of removed files from one collection.
+/// the document ids are the absolute paths or the URLs of the files after the id_prefix, so the documents
+/// under each directory are paged through with a prefix query.
+/// the parts of a split file are kept while the file exists
+async fn delete_missing_from(
+    context: &PostContext,
+    select_url: &str,
+    delete_url: &str,
+) -> Result<usize, SolrPostError> {
+    let prefixes = directory_prefixes(context)?;
+
+    // the documents under overlapping directories are found once for each
+    let mut missing = HashSet::new();
+    for prefix in &prefixes {
+        missing.extend(missing_under(context, select_url, prefix).await?);
+    }
+    let missing: Vec<String> = missing.into_iter().collect();
+
+    delete_ids(&context.client, delete_url, &missing).await?;
+
+    info!("deleted {} documents of removed files", missing.len());
+    Ok(missing.len())
+}
+
+/// the id prefix of the documents under each directory, the same directory is only listed once
+/// e.g. "docs:/var/www/" or "https://example.com/"
+fn directory_prefixes(context: &PostContext) -> Result<Vec<String>, SolrPostError> {
+    let mut prefixes = Vec::new();
+    for directory in context.config.directories() {
+        let directory = directory
+            .canonicalize()
+            .map_err(|source| SolrPostError::Io {
+                path: directory.clone(),
+                source,
+            })?;
+
+        // the trailing separator keeps /docs from matching /docs2
+        let separator = match context.document_url(&directory) {
+            Some(_) => '/',
+            None => std::path::MAIN_SEPARATOR,
+        };
+        let prefix = format!(
+            "{}{}",
+            context.document_id(&directory).trim_end_matches(separator),
+            separator
+        );
+        if !prefixes.contains(&prefix) {
+            prefixes.push(prefix);
+        }
+    }
+    Ok(prefixes)
+}
+
+/// the ids of the documents starting with the prefix whose file no longer exists
+async fn missing_under(
+    context: &PostContext,
+    select_url: &str,
+    prefix: &str,
+) -> Result<Vec<String>, SolrPostError> {
+    let mut missing = Vec::new();
+    let mut cursor_mark = String::from("*");
+
+    loop {
+        let rows = PAGE_SIZE.to_string();
+        let response = context
+            .client
+            .get(select_url)
+            .query(&[
+                ("q", "{!prefix f=id v=$prefix}"),
+                ("prefix", prefix),
+                ("fl", "id"),
+                ("sort", "id asc"),
+                ("rows", rows.as_str()),
+                ("cursorMark", cursor_mark.as_str()),
+                ("wt", "json"),
+            ])
+            .send()
+            .await?;
+
+        let response = error_for_status(response).await?;
+
+        let url = response.url().to_string();
+        let page: Value = serde_json::from_slice(&response.bytes().await?).map_err(|e| {
+            SolrPostError::InvalidResponse {
+                url,
+                message: e.to_string(),
+            }
+        })?;
+        let docs = page["response"]["docs"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+
+        missing.extend(
+            docs.iter()
+                .filter_map(|doc| doc["id"].as_str())
+                .filter(|id| {
+                    context
+                        .document_path(part_file(id))
+                        .is_some_and(|path| !path.exists())
+                })
+                .map(str::to_string),
+        );
+
+        // the cursor stops moving once every document has been returned
+        let next_cursor_mark = page["nextCursorMark"].as_str().unwrap_or_default();
+        if docs.is_empty() || next_cursor_mark.is_empty() || next_cursor_mark == cursor_mark {
+            break;
+        }
+        cursor_mark = next_cursor_mark.to_string();
+    }
+
+    Ok(missing)
+}
+
+/// delete the documents with these ids, in requests of at most PAGE_SIZE ids
+pub(crate) async fn delete_ids(
+    client: &SolrClient,
+    delete_url: &str,
+    ids: &[String],
+) -> Result<(), SolrPostError> {
+    for ids in ids.chunks(PAGE_SIZE) {
+        let body = serde_json::json!({ "delete": ids });
+        let response = client
+            .post(delete_url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await?;
+
+        error_for_status(response).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{PathBuf, MAIN_SEPARATOR};
+
+    use super::*;
+    use crate::PostConfig;
+
+    /// a directory with the docs and blog directories, removed when dropped
+    struct Site(PathBuf);
+
+    impl Site {
+        fn new() -> Self {
+            let root = std::env::temp_dir().join(format!("solr_post_{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(root.join("docs")).unwrap();
+            fs::create_dir_all(root.join("blog")).unwrap();
+            Site(root.canonicalize().unwrap())
+        }
+
+        fn prefixes(&self, config: PostConfig) -> Vec<String> {
+            let config = PostConfig {
+                directory_path: self.0.join("docs"),
+                directory_paths: vec![self.0.join("blog"), self.0.join("docs")],
+                ..config
+            };
+            directory_prefixes(&PostContext::new(config).unwrap()).unwrap()
+        }
+
+        fn prefix(&self, directory: &str) -> String {
+            format!("{}{}", self.0.join(directory).display(), MAIN_SEPARATOR)
+        }
+    }
+
+    impl Drop for Site {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn prefixes_of_the_directories() {
+        let site = Site::new();
+        assert_eq!(
+            site.prefixes(PostConfig::default()),
+            [site.prefix("docs"), site.prefix("blog")]
+        );
+    }
+
+    #[test]
+    fn prefixes_with_the_id_prefix() {
+        let site = Site::new();
+        let config = PostConfig {
+            id_prefix: Some("site:".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            site.prefixes(config),
+            [
+                format!("site:{}", site.prefix("docs")),
+                format!("site:{}", site.prefix("blog"))
+            ]
+        );
+    }
+
+    #[test]
+    fn prefixes_of_the_base_url() {
+        let site = Site::new();
+        let config = PostConfig {
+            base_url: Some("https://example.com/".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(site.prefixes(config), ["https://example.com/"]);
+    }
+
+    #[test]
+    fn prefixes_of_a_missing_directory() {
+        let site = Site::new();
+        let config = PostConfig {
+            directory_path: site.0.join("missing"),
+            ..Default::default()
+        };
+        assert!(matches!(
+            directory_prefixes(&PostContext::new(config).unwrap()),
+            Err(SolrPostError::Io { .. })
+        ));
+    }
+}
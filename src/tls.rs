@@ -0,0 +1,172 @@
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+use std::path::Path;
+
+use reqwest::ClientBuilder;
+
+use crate::{PostConfig, SolrPostError};
+
+/// trust the ca_cert, present the client_cert, and skip the verification with accept_invalid_certs.
+/// rustls is used when the rustls feature is enabled, even if native-tls is enabled too
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+pub(crate) fn configure(
+    config: &PostConfig,
+    mut client_builder: ClientBuilder,
+) -> Result<ClientBuilder, SolrPostError> {
+    use reqwest::Certificate;
+
+    #[cfg(feature = "rustls")]
+    {
+        client_builder = client_builder.use_rustls_tls();
+    }
+
+    // trust the internal CA next to the system's root certificates
+    if let Some(ca_cert) = &config.ca_cert {
+        let pem = read(ca_cert)?;
+        let certificates =
+            Certificate::from_pem_bundle(&pem).map_err(|e| invalid_certificate(ca_cert, &e))?;
+        if certificates.is_empty() {
+            return Err(SolrPostError::Certificate {
+                path: ca_cert.clone(),
+                message: String::from("no PEM certificates found"),
+            });
+        }
+        for certificate in certificates {
+            client_builder = client_builder.add_root_certificate(certificate);
+        }
+    }
+
+    // present a client certificate for mutual TLS
+    if let Some(client_cert) = &config.client_cert {
+        client_builder = client_builder.identity(client_identity(config, client_cert)?);
+    }
+
+    if config.accept_invalid_certs {
+        log::warn!("TLS certificate verification is disabled");
+        client_builder = client_builder.danger_accept_invalid_certs(true);
+    }
+
+    Ok(client_builder)
+}
+
+/// without a TLS backend only http URLs can be posted to, the certificates can't be used
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+pub(crate) fn configure(
+    config: &PostConfig,
+    client_builder: ClientBuilder,
+) -> Result<ClientBuilder, SolrPostError> {
+    match config.ca_cert.as_ref().or(config.client_cert.as_ref()) {
+        Some(path) => Err(SolrPostError::Certificate {
+            path: path.clone(),
+            message: String::from("TLS requires the native-tls or rustls feature"),
+        }),
+        None => Ok(client_builder),
+    }
+}
+
+/// load the client certificate and its key, a PKCS#12 archive or a PEM certificate with a PKCS#8 PEM key.
+/// rustls also accepts PKCS#1 and SEC1 keys but no PKCS#12 archives
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+fn client_identity(
+    config: &PostConfig,
+    client_cert: &Path,
+) -> Result<reqwest::Identity, SolrPostError> {
+    let cert = read(client_cert)?;
+    let is_pkcs12 = client_cert
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| matches!(extension.to_ascii_lowercase().as_str(), "p12" | "pfx"));
+
+    let key = match &config.client_key {
+        Some(client_key) => Some(read(client_key)?),
+        None => None,
+    };
+
+    if is_pkcs12 {
+        return pkcs12_identity(client_cert, &cert, config.client_cert_password.as_deref());
+    }
+    pem_identity(cert, key).map_err(|e| invalid_certificate(client_cert, &e))
+}
+
+#[cfg(feature = "rustls")]
+fn pkcs12_identity(
+    path: &Path,
+    _der: &[u8],
+    _password: Option<&str>,
+) -> Result<reqwest::Identity, SolrPostError> {
+    Err(SolrPostError::Certificate {
+        path: path.to_path_buf(),
+        message: String::from("PKCS#12 client certificates require the native-tls feature"),
+    })
+}
+
+#[cfg(not(feature = "rustls"))]
+#[cfg(feature = "native-tls")]
+fn pkcs12_identity(
+    path: &Path,
+    der: &[u8],
+    password: Option<&str>,
+) -> Result<reqwest::Identity, SolrPostError> {
+    reqwest::Identity::from_pkcs12_der(der, password.unwrap_or_default())
+        .map_err(|e| invalid_certificate(path, &e))
+}
+
+/// rustls reads the certificates and the key from one PEM
+#[cfg(feature = "rustls")]
+fn pem_identity(mut cert: Vec<u8>, key: Option<Vec<u8>>) -> reqwest::Result<reqwest::Identity> {
+    if let Some(key) = key {
+        cert.push(b'\n');
+        cert.extend_from_slice(&key);
+    }
+    reqwest::Identity::from_pem(&cert)
+}
+
+/// native-tls takes the certificates and the key apart
+#[cfg(not(feature = "rustls"))]
+#[cfg(feature = "native-tls")]
+fn pem_identity(cert: Vec<u8>, key: Option<Vec<u8>>) -> reqwest::Result<reqwest::Identity> {
+    match key {
+        Some(key) => reqwest::Identity::from_pkcs8_pem(&cert, &key),
+        None => {
+            let (cert, key) = split_pem_key(&cert);
+            reqwest::Identity::from_pkcs8_pem(&cert, &key)
+        }
+    }
+}
+
+/// split a PEM file holding both into the certificates and the private key
+#[cfg(not(feature = "rustls"))]
+#[cfg(feature = "native-tls")]
+fn split_pem_key(pem: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let (mut cert, mut key) = (Vec::new(), Vec::new());
+    let mut in_key = false;
+    for line in pem.split_inclusive(|&byte| byte == b'\n') {
+        if line.starts_with(b"-----BEGIN") {
+            in_key = line.windows(11).any(|window| window == b"PRIVATE KEY");
+        }
+        match in_key {
+            true => key.extend_from_slice(line),
+            false => cert.extend_from_slice(line),
+        }
+    }
+    (cert, key)
+}
+
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+fn read(path: &Path) -> Result<Vec<u8>, SolrPostError> {
+    std::fs::read(path).map_err(|source| SolrPostError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// the builder error itself only says "builder error", the TLS library's error is the source
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+fn invalid_certificate(path: &Path, e: &reqwest::Error) -> SolrPostError {
+    SolrPostError::Certificate {
+        path: path.to_path_buf(),
+        message: match std::error::Error::source(e) {
+            Some(source) => source.to_string(),
+            None => e.to_string(),
+        },
+    }
+}
@@ -0,0 +1,18 @@
+use tracing::{field, Span};
+
+use crate::FileResult;
+
+/// record the outcome of a post on the current span, declared by post_file with these fields empty
+pub(crate) fn record_file_result(result: &FileResult) {
+    let span = Span::current();
+
+    if let Some(status) = result.status {
+        span.record("status", status.as_u16());
+    }
+    span.record("bytes", result.bytes_sent);
+    span.record("latency_ms", result.elapsed.as_millis() as u64);
+
+    if let Some(error) = &result.error {
+        span.record("error", field::display(error));
+    }
+}
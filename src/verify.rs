@@ -0,0 +1,75 @@
+use log::info;
+use serde_json::Value;
+
+use crate::solr_response::error_for_status;
+use crate::stream::PostContext;
+use crate::SolrPostError;
+
+/// count the committed documents of this run, or of the whole collection, or with the id_prefix, when the
+/// documents aren't tagged with the run id, and fail if Solr has fewer documents than files were indexed.
+/// a structured file can hold several documents so more documents than files is fine.
+/// with extra_collections every collection is counted and the smallest count is returned
+pub(crate) async fn verify_count(
+    context: &PostContext,
+    indexed: usize,
+) -> Result<usize, SolrPostError> {
+    let select_urls = context
+        .command_urls(&context.config.select_url()?)
+        .await
+        .map_err(|e| SolrPostError::Verify(Box::new(e)))?;
+
+    let mut found = usize::MAX;
+    for select_url in &select_urls {
+        let count = count_documents(context, select_url)
+            .await
+            .map_err(|e| SolrPostError::Verify(Box::new(e)))?;
+
+        if count < indexed {
+            return Err(SolrPostError::VerifyMismatch {
+                indexed,
+                found: count,
+            });
+        }
+        found = found.min(count);
+    }
+
+    info!("verified {} documents for {} indexed files", found, indexed);
+    Ok(found)
+}
+
+/// the number of documents found by the /select handler
+async fn count_documents(context: &PostContext, select_url: &str) -> Result<usize, SolrPostError> {
+    let mut query = vec![
+        ("q", context.config.documents_query()),
+        ("rows", String::from("0")),
+    ];
+    if context.config.index_run_id {
+        query.push((
+            "fq",
+            format!(
+                "{{!term f={}}}{}",
+                context.config.run_id_field, context.run_id
+            ),
+        ));
+    }
+    query.push(("wt", String::from("json")));
+
+    let response = context.client.get(select_url).query(&query).send().await?;
+
+    let response = error_for_status(response).await?;
+
+    let url = response.url().to_string();
+    let body: Value = serde_json::from_slice(&response.bytes().await?).map_err(|e| {
+        SolrPostError::InvalidResponse {
+            url: url.clone(),
+            message: e.to_string(),
+        }
+    })?;
+    body["response"]["numFound"]
+        .as_u64()
+        .map(|found| found as usize)
+        .ok_or_else(|| SolrPostError::InvalidResponse {
+            url,
+            message: String::from("missing response.numFound"),
+        })
+}
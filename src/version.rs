@@ -0,0 +1,32 @@
+use std::{fs, path::Path};
+
+/// the suffix of the sidecar file holding the document version e.g. "page.html.version"
+pub(crate) const VERSION_SIDECAR_SUFFIX: &str = ".version";
+
+/// Look up the `_version_` to send for optimistic concurrency.
+/// A sidecar file next to the document e.g. "page.html.version" takes precedence,
+/// otherwise the top level `_version_` field of a JSON document is used
+pub(crate) fn document_version(path: &Path) -> Option<i64> {
+    let mut sidecar_path = path.as_os_str().to_owned();
+    sidecar_path.push(VERSION_SIDECAR_SUFFIX);
+
+    if let Ok(sidecar) = fs::read_to_string(&sidecar_path) {
+        return sidecar.trim().parse().ok();
+    }
+
+    let is_json = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+
+    if !is_json {
+        return None;
+    }
+
+    // only JSON documents are read here, other files are streamed to Solr without being read into memory
+    let contents = fs::read(path).ok()?;
+
+    serde_json::from_slice::<serde_json::Value>(&contents)
+        .ok()?
+        .get("_version_")?
+        .as_i64()
+}
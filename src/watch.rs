@@ -0,0 +1,165 @@
+use std::{collections::HashSet, path::PathBuf, sync::Arc, time::Duration};
+
+use futures::StreamExt;
+use log::info;
+use notify::{event::ModifyKind, EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::events::{ChannelSink, EventSink};
+use crate::scan::{is_selected, should_index};
+use crate::stream::{post_files, PostContext};
+use crate::sync::delete_ids;
+use crate::{commit, ping_solr, PostConfig, PostEvent, SolrPostError};
+
+/// how long the tree must be quiet before the collected changes are posted, an editor save or a site
+/// build touches many files at once
+const QUIET_PERIOD: Duration = Duration::from_millis(500);
+
+/// Watch directory_path and the directory_paths and keep the collection in sync until the cancellation_token is cancelled.
+/// Created and modified files are posted and the documents of removed files are deleted, each batch
+/// of changes is committed according to PostConfig::commit. Run solr_post first for the initial pass
+pub async fn solr_watch(
+    config: PostConfig,
+    sender: mpsc::Sender<PostEvent>,
+) -> Result<(), SolrPostError> {
+    let mut sink = ChannelSink { sender };
+
+    // the document ids are absolute paths, watching the canonical directories reports absolute paths
+    let directories = config
+        .directories()
+        .map(|directory| {
+            directory
+                .canonicalize()
+                .map_err(|source| SolrPostError::Io {
+                    path: directory.clone(),
+                    source,
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (change_sender, mut changes) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        // reading the files to post them reports access events, those must not trigger another post
+        let Ok(event) = event else { return };
+        let changed = match event.kind {
+            EventKind::Create(_) | EventKind::Remove(_) => true,
+            EventKind::Modify(kind) => !matches!(kind, ModifyKind::Metadata(_)),
+            _ => false,
+        };
+
+        if changed {
+            for path in event.paths {
+                let _ = change_sender.send(path);
+            }
+        }
+    })
+    .map_err(|e| SolrPostError::Watch(e.to_string()))?;
+    for directory in &directories {
+        watcher
+            .watch(directory, RecursiveMode::Recursive)
+            .map_err(|e| SolrPostError::Watch(e.to_string()))?;
+    }
+
+    let cancellation_token = config.cancellation_token.clone().unwrap_or_default();
+    let context = Arc::new(PostContext::new(config)?);
+    ping_solr(&context).await?;
+    let delete_urls = context.command_urls(&context.config.clean_url()?).await?;
+    for directory in &directories {
+        info!("watching {}", directory.display());
+    }
+
+    loop {
+        // wait for the first change, then collect the changes until the tree is quiet
+        let mut changed = HashSet::new();
+        tokio::select! {
+            path = changes.recv() => match path {
+                Some(path) => changed.insert(path),
+                None => break,
+            },
+            _ = cancellation_token.cancelled() => break,
+        };
+
+        while let Ok(Some(path)) = tokio::time::timeout(QUIET_PERIOD, changes.recv()).await {
+            changed.insert(path);
+        }
+
+        apply_changes(&context, &delete_urls, changed, &mut sink).await;
+    }
+
+    Ok(())
+}
+
+/// post the changed files that still exist and delete the documents of the removed ones
+async fn apply_changes(
+    context: &Arc<PostContext>,
+    delete_urls: &[String],
+    changed: HashSet<PathBuf>,
+    sink: &mut dyn EventSink,
+) {
+    let mut files = HashSet::new();
+    let mut removed = Vec::new();
+
+    for path in changed {
+        if !path.exists() {
+            removed.push(path);
+            continue;
+        }
+
+        match should_index(&context.config, context.relative_path(&path), &path) {
+            Ok(true) => {
+                files.insert(path.to_string_lossy().into_owned());
+            }
+            Ok(false) => {}
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    let mut changes = 0;
+
+    let mut posts = post_files(context.clone(), files);
+    while let Some(file_result) = posts.next().await {
+        changes += 1;
+        match file_result.is_success() {
+            true => sink.emit(PostEvent::FileIndexed(file_result)).await,
+            false => sink.emit(PostEvent::FileFailed(file_result)).await,
+        }
+    }
+
+    // only the files matched by the globs have documents
+    removed.retain(|path| is_selected(&context.config, context.relative_path(path), path));
+    if !removed.is_empty() {
+        let ids: Vec<String> = removed
+            .iter()
+            .map(|path| context.document_id(path))
+            .collect();
+
+        let deleted = async {
+            for delete_url in delete_urls {
+                delete_ids(&context.client, delete_url, &ids).await?;
+            }
+            Ok::<(), SolrPostError>(())
+        };
+        match deleted.await {
+            Ok(()) => {
+                changes += removed.len();
+                for path in removed {
+                    sink.emit(PostEvent::FileDeleted { path }).await;
+                }
+            }
+            Err(e) => eprintln!("failed to delete {} documents: {}", ids.len(), e),
+        }
+    }
+
+    if changes > 0 && context.config.commit.is_explicit() {
+        let committed = async {
+            for commit_url in context.command_urls(&context.commit_url).await? {
+                commit(&context.client, &commit_url).await?;
+            }
+            Ok::<(), SolrPostError>(())
+        };
+        match committed.await {
+            Ok(()) => sink.emit(PostEvent::Committed).await,
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
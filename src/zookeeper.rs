@@ -0,0 +1,431 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use log::{info, warn};
+use reqwest::Url;
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::SolrPostError;
+
+/// give up on a ZooKeeper server after this long and try the next one of the ensemble
+const ZK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// the session timeout asked for, the session only lives for the few reads of the discovery
+const SESSION_TIMEOUT_MS: i32 = 30_000;
+
+/// the jute request types, see ZooDefs.OpCode
+const OP_GET_DATA: i32 = 4;
+const OP_GET_CHILDREN: i32 = 8;
+const OP_CLOSE_SESSION: i32 = -11;
+
+/// the error code of a read of a node that doesn't exist, see KeeperException.Code
+const ERR_NO_NODE: i32 = -101;
+
+/// the longest frame read, a few times the 1 MiB jute.maxbuffer ZooKeeper limits its nodes to by default,
+/// so a corrupt reply or a server that isn't ZooKeeper can't make the discovery allocate gigabytes
+const MAX_FRAME_LEN: usize = 4 * 1024 * 1024;
+
+/// find the base URLs of the live Solr nodes hosting an active replica of the collection from the
+/// cluster state in ZooKeeper. zk_hosts is the ZooKeeper connection string of Solr e.g.
+/// "zk1:2181,zk2:2181,zk3:2181/solr", the servers of the ensemble are tried in order
+pub(crate) async fn discover_nodes(
+    zk_hosts: &str,
+    collection: &str,
+) -> Result<Vec<Url>, SolrPostError> {
+    let (hosts, chroot) = match zk_hosts.find('/') {
+        Some(slash) => (&zk_hosts[..slash], zk_hosts[slash..].trim_end_matches('/')),
+        None => (zk_hosts, ""),
+    };
+
+    let mut last_error = SolrPostError::ZooKeeper(format!("no servers in {}", zk_hosts));
+    for host in hosts.split(',').map(str::trim).filter(|h| !h.is_empty()) {
+        let read = async {
+            let mut session = Session::connect(host).await?;
+            let nodes = read_cluster(&mut session, chroot, collection).await;
+            session.close().await;
+            nodes
+        };
+
+        match tokio::time::timeout(ZK_TIMEOUT, read).await {
+            Ok(Ok(nodes)) => {
+                info!(
+                    "found {} Solr nodes for {} in {}",
+                    nodes.len(),
+                    collection,
+                    host
+                );
+                return Ok(nodes);
+            }
+            Ok(Err(e)) => last_error = e,
+            Err(_) => last_error = SolrPostError::ZooKeeper(format!("{} timed out", host)),
+        }
+        warn!("{}", last_error);
+    }
+
+    Err(last_error)
+}
+
+/// read the live nodes and the replicas of the collection, one URL per node
+async fn read_cluster(
+    session: &mut Session,
+    chroot: &str,
+    collection: &str,
+) -> Result<Vec<Url>, SolrPostError> {
+    let live_nodes: HashSet<String> = session
+        .get_children(&format!("{}/live_nodes", chroot))
+        .await?
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    // http unless the cluster is configured for https
+    let url_scheme = session
+        .get_data(&format!("{}/clusterprops.json", chroot))
+        .await?
+        .and_then(|data| serde_json::from_slice::<Value>(&data).ok())
+        .and_then(|props| props["urlScheme"].as_str().map(str::to_string))
+        .unwrap_or_else(|| String::from("http"));
+
+    // Solr 7+ keeps the state of each collection in its own node, older versions share clusterstate.json
+    let state = match session
+        .get_data(&format!("{}/collections/{}/state.json", chroot, collection))
+        .await?
+    {
+        Some(state) => state,
+        None => session
+            .get_data(&format!("{}/clusterstate.json", chroot))
+            .await?
+            .unwrap_or_default(),
+    };
+    let state: Value = serde_json::from_slice(&state).unwrap_or_default();
+    let shards = state[collection]["shards"].as_object().ok_or_else(|| {
+        SolrPostError::ZooKeeper(format!(
+            "collection {} not found in the cluster state",
+            collection
+        ))
+    })?;
+
+    let mut node_names: Vec<&str> = shards
+        .values()
+        .filter_map(|shard| shard["replicas"].as_object())
+        .flat_map(|replicas| replicas.values())
+        .filter(|replica| {
+            replica["state"]
+                .as_str()
+                .is_none_or(|state| state == "active")
+        })
+        .filter_map(|replica| replica["node_name"].as_str())
+        .filter(|node_name| live_nodes.contains(*node_name))
+        .collect();
+    node_names.sort_unstable();
+    node_names.dedup();
+
+    let nodes: Vec<Url> = node_names
+        .into_iter()
+        .filter_map(|node_name| node_url(&url_scheme, node_name))
+        .collect();
+    if nodes.is_empty() {
+        return Err(SolrPostError::ZooKeeper(format!(
+            "no live node hosts an active replica of {}",
+            collection
+        )));
+    }
+
+    Ok(nodes)
+}
+
+/// the base URL of a node from its name e.g. "10.0.0.1:8983_solr" -> "http://10.0.0.1:8983/solr"
+fn node_url(url_scheme: &str, node_name: &str) -> Option<Url> {
+    let (address, context) = node_name.split_once('_')?;
+    let context = urlencoding::decode(context).ok()?;
+
+    Url::parse(&format!("{}://{}/{}", url_scheme, address, context)).ok()
+}
+
+/// the names of the children in a GetChildrenResponse. each name takes at least its 4 byte length, so the
+/// count sent isn't trusted beyond the bytes of the response
+fn children(response: &[u8]) -> Result<Vec<String>, SolrPostError> {
+    let mut reader = Reader::new(response);
+    let count = reader.i32()?.max(0) as usize;
+    let mut children = Vec::with_capacity(count.min(reader.rest().len() / 4));
+    for _ in 0..count {
+        children.push(String::from_utf8_lossy(reader.buffer()?).into_owned());
+    }
+    Ok(children)
+}
+
+/// A read only ZooKeeper session speaking the jute wire protocol, only what the discovery needs
+struct Session {
+    stream: TcpStream,
+    host: String,
+    xid: i32,
+}
+
+impl Session {
+    async fn connect(host: &str) -> Result<Self, SolrPostError> {
+        let stream = TcpStream::connect(host).await.map_err(|e| {
+            SolrPostError::ZooKeeper(format!("failed to connect to {}: {}", host, e))
+        })?;
+        let mut session = Session {
+            stream,
+            host: host.to_string(),
+            xid: 0,
+        };
+
+        // ConnectRequest: protocol version, last zxid seen, timeout, session id, password, read only
+        let mut request = Vec::new();
+        request.extend(0i32.to_be_bytes());
+        request.extend(0i64.to_be_bytes());
+        request.extend(SESSION_TIMEOUT_MS.to_be_bytes());
+        request.extend(0i64.to_be_bytes());
+        request.extend(16i32.to_be_bytes());
+        request.extend([0u8; 16]);
+        request.push(0);
+        session.send(&request).await?;
+
+        // ConnectResponse: protocol version, negotiated timeout, ... a timeout of 0 means the session was refused
+        let response = session.receive().await?;
+        let mut reader = Reader::new(&response);
+        reader.i32()?;
+        if reader.i32()? <= 0 {
+            return Err(session.error("the session was refused"));
+        }
+
+        Ok(session)
+    }
+
+    /// the data of the node, None if it doesn't exist
+    async fn get_data(&mut self, path: &str) -> Result<Option<Vec<u8>>, SolrPostError> {
+        let Some(response) = self.read(OP_GET_DATA, path).await? else {
+            return Ok(None);
+        };
+
+        let mut reader = Reader::new(&response);
+        Ok(Some(reader.buffer()?.to_vec()))
+    }
+
+    /// the names of the children of the node, None if it doesn't exist
+    async fn get_children(&mut self, path: &str) -> Result<Option<Vec<String>>, SolrPostError> {
+        let Some(response) = self.read(OP_GET_CHILDREN, path).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(children(&response)?))
+    }
+
+    /// send a path request without a watch and return the body of the reply, None if the node doesn't exist
+    async fn read(&mut self, op: i32, path: &str) -> Result<Option<Vec<u8>>, SolrPostError> {
+        self.xid += 1;
+
+        let mut request = Vec::new();
+        request.extend(self.xid.to_be_bytes());
+        request.extend(op.to_be_bytes());
+        request.extend((path.len() as i32).to_be_bytes());
+        request.extend(path.as_bytes());
+        request.push(0);
+        self.send(&request).await?;
+
+        loop {
+            let reply = self.receive().await?;
+            let mut reader = Reader::new(&reply);
+            let xid = reader.i32()?;
+            reader.i64()?;
+            let err = reader.i32()?;
+
+            // skip notifications and pings, they have negative xids
+            if xid != self.xid {
+                continue;
+            }
+
+            return match err {
+                0 => Ok(Some(reader.rest().to_vec())),
+                ERR_NO_NODE => Ok(None),
+                err => Err(self.error(&format!("reading {} failed with error {}", path, err))),
+            };
+        }
+    }
+
+    /// end the session so the ensemble doesn't keep it until it times out, best effort
+    async fn close(mut self) {
+        self.xid += 1;
+        let mut request = Vec::new();
+        request.extend(self.xid.to_be_bytes());
+        request.extend(OP_CLOSE_SESSION.to_be_bytes());
+        let _ = self.send(&request).await;
+    }
+
+    /// write a length prefixed frame
+    async fn send(&mut self, frame: &[u8]) -> Result<(), SolrPostError> {
+        let mut message = Vec::with_capacity(frame.len() + 4);
+        message.extend((frame.len() as i32).to_be_bytes());
+        message.extend(frame);
+
+        self.stream
+            .write_all(&message)
+            .await
+            .map_err(|e| self.error(&e.to_string()))
+    }
+
+    /// read a length prefixed frame
+    async fn receive(&mut self) -> Result<Vec<u8>, SolrPostError> {
+        let len = self
+            .stream
+            .read_i32()
+            .await
+            .map_err(|e| self.error(&e.to_string()))?;
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= MAX_FRAME_LEN)
+            .ok_or_else(|| self.error(&format!("invalid frame length {}", len)))?;
+        let mut frame = vec![0u8; len];
+        self.stream
+            .read_exact(&mut frame)
+            .await
+            .map_err(|e| self.error(&e.to_string()))?;
+        Ok(frame)
+    }
+
+    fn error(&self, message: &str) -> SolrPostError {
+        SolrPostError::ZooKeeper(format!("{}: {}", self.host, message))
+    }
+}
+
+/// Reads the big endian jute types of a frame
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SolrPostError> {
+        if self.bytes.len() < len {
+            return Err(SolrPostError::ZooKeeper(String::from("truncated response")));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn i32(&mut self) -> Result<i32, SolrPostError> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i64(&mut self) -> Result<i64, SolrPostError> {
+        Ok(i64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// a length prefixed buffer or string, empty for a null buffer
+    fn buffer(&mut self) -> Result<&'a [u8], SolrPostError> {
+        let len = self.i32()?;
+        self.take(len.max(0) as usize)
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_url_of_a_node_name() {
+        assert_eq!(
+            node_url("http", "10.0.0.1:8983_solr").unwrap().as_str(),
+            "http://10.0.0.1:8983/solr"
+        );
+        assert_eq!(
+            node_url("https", "solr1.example.com:8984_solr%2Fapp")
+                .unwrap()
+                .as_str(),
+            "https://solr1.example.com:8984/solr/app"
+        );
+    }
+
+    #[test]
+    fn node_url_without_a_context() {
+        assert!(node_url("http", "10.0.0.1:8983").is_none());
+    }
+
+    #[test]
+    fn children_of_a_response() {
+        let mut response = Vec::new();
+        response.extend(2i32.to_be_bytes());
+        for name in ["10.0.0.1:8983_solr", "10.0.0.2:8983_solr"] {
+            response.extend((name.len() as i32).to_be_bytes());
+            response.extend(name.as_bytes());
+        }
+
+        assert_eq!(
+            children(&response).unwrap(),
+            ["10.0.0.1:8983_solr", "10.0.0.2:8983_solr"]
+        );
+    }
+
+    #[test]
+    fn children_count_beyond_the_response() {
+        let mut response = Vec::new();
+        response.extend(i32::MAX.to_be_bytes());
+        response.extend(1i32.to_be_bytes());
+        response.push(b'a');
+
+        assert!(matches!(
+            children(&response),
+            Err(SolrPostError::ZooKeeper(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn frame_longer_than_the_limit() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.write_all(&i32::MAX.to_be_bytes()).await;
+        });
+
+        match Session::connect(&host).await {
+            Err(SolrPostError::ZooKeeper(message)) => {
+                assert!(message.contains("invalid frame length"), "{}", message)
+            }
+            _ => panic!("expected a ZooKeeper error"),
+        }
+    }
+
+    #[test]
+    fn reader_of_jute_types() {
+        let mut frame = Vec::new();
+        frame.extend(7i32.to_be_bytes());
+        frame.extend((-2i64).to_be_bytes());
+        frame.extend(3i32.to_be_bytes());
+        frame.extend(b"abc");
+        frame.extend((-1i32).to_be_bytes());
+        frame.push(9);
+
+        let mut reader = Reader::new(&frame);
+        assert_eq!(reader.i32().unwrap(), 7);
+        assert_eq!(reader.i64().unwrap(), -2);
+        assert_eq!(reader.buffer().unwrap(), b"abc");
+        assert_eq!(reader.buffer().unwrap(), b"");
+        assert_eq!(reader.rest(), [9]);
+    }
+
+    #[test]
+    fn reader_of_a_truncated_frame() {
+        let mut frame = Vec::new();
+        frame.extend(10i32.to_be_bytes());
+        frame.extend(b"abc");
+
+        assert!(matches!(
+            Reader::new(&frame).buffer(),
+            Err(SolrPostError::ZooKeeper(_))
+        ));
+        assert!(Reader::new(&[0, 0]).i32().is_err());
+    }
+}